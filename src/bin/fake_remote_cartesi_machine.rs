@@ -0,0 +1,50 @@
+// Copyright (C) 2021 Cartesi Pte. Ltd.
+//
+// Licensed under the Apache License, Version 2.0 (the "License"); you may not use
+// this file except in compliance with the License. You may obtain a copy of the
+// License at http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software distributed
+// under the License is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR
+// CONDITIONS OF ANY KIND, either express or implied. See the License for the
+// specific language governing permissions and limitations under the License.
+
+//! Fake Cartesi machine server as a standalone process
+//!
+//! Accepts the `--server-address=<host>:<port>` argument of
+//! `jsonrpc-remote-cartesi-machine`, so it can stand in for it wherever a
+//! server binary is launched, and exits once it receives `shutdown`.
+
+use std::net::SocketAddr;
+use std::process::exit;
+
+use cartesi_machine_json_rpc::fake::FakeServer;
+
+fn main() {
+    let mut address: SocketAddr = "127.0.0.1:0".parse().unwrap();
+    for arg in std::env::args().skip(1) {
+        match arg.strip_prefix("--server-address=").map(str::parse) {
+            Some(Ok(parsed)) => address = parsed,
+            _ => {
+                eprintln!("invalid argument {}", arg);
+                exit(1);
+            }
+        }
+    }
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .unwrap();
+    runtime.block_on(async {
+        match FakeServer::start_at(address).await {
+            Ok(server) => {
+                eprintln!("listening on {}", server.address());
+                server.stopped().await;
+            }
+            Err(err) => {
+                eprintln!("failed to start server: {}", err);
+                exit(1);
+            }
+        }
+    });
+}
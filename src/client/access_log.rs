@@ -0,0 +1,108 @@
+// Copyright (C) 2021 Cartesi Pte. Ltd.
+//
+// Licensed under the Apache License, Version 2.0 (the "License"); you may not use
+// this file except in compliance with the License. You may obtain a copy of the
+// License at http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software distributed
+// under the License is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR
+// CONDITIONS OF ANY KIND, either express or implied. See the License for the
+// specific language governing permissions and limitations under the License.
+
+//! Local verification of access logs, without a machine server
+
+use crate::client::merkle::{merkle_root_hash, LOG2_WORD_SIZE};
+use crate::client::{Access, AccessLog, AccessType, Error, Hash};
+
+impl Access {
+    /// Check the access against the current root hash and return the root hash after it
+    pub(crate) fn replay(&self, root_hash: &Hash) -> Result<Hash, String> {
+        let log2_size = self.log2_size as usize;
+        let size = match 1usize.checked_shl(log2_size as u32) {
+            Some(size) if log2_size >= LOG2_WORD_SIZE => size,
+            _ => return Err(format!("invalid access size 2^{}", self.log2_size)),
+        };
+        let proof = &self.proof;
+        if proof.target_address != self.address || proof.log2_target_size != log2_size {
+            return Err(format!(
+                "proof targets 2^{} bytes at {:#x}, access is 2^{} bytes at {:#x}",
+                proof.log2_target_size, proof.target_address, log2_size, self.address
+            ));
+        }
+        if proof.root_hash != *root_hash {
+            return Err(format!(
+                "proof root hash {} does not match current root hash {}",
+                proof.root_hash, root_hash
+            ));
+        }
+        if !proof.verify() {
+            return Err("proof does not verify".to_string());
+        }
+        if self.read_data.len() != size {
+            return Err(format!(
+                "read data has {} bytes, expected {}",
+                self.read_data.len(),
+                size
+            ));
+        }
+        let read_hash = merkle_root_hash(&self.read_data).map_err(|err| err.to_string())?;
+        if read_hash != proof.target_hash {
+            return Err("read data does not match proof target hash".to_string());
+        }
+        match self.r#type {
+            AccessType::Read => Ok(*root_hash),
+            AccessType::Write => {
+                if self.written_data.len() != size {
+                    return Err(format!(
+                        "written data has {} bytes, expected {}",
+                        self.written_data.len(),
+                        size
+                    ));
+                }
+                let written_hash =
+                    merkle_root_hash(&self.written_data).map_err(|err| err.to_string())?;
+                proof
+                    .root_hash_for(&written_hash)
+                    .map_err(|err| err.to_string())
+            }
+        }
+    }
+}
+
+impl AccessLog {
+    /// Replay every access starting from `root_hash_before` and return the resulting root hash
+    ///
+    /// Fails with [`Error::InconsistentAccess`] at the first access whose proof does not
+    /// match the state left by the accesses before it.
+    pub fn verify_local(&self, root_hash_before: &Hash) -> Result<Hash, Error> {
+        if !self.log_type.proofs {
+            return Err(Error::InvalidArgument(
+                "access log has no proofs".to_string(),
+            ));
+        }
+        self.accesses.iter().enumerate().try_fold(
+            *root_hash_before,
+            |root_hash, (index, access)| {
+                access
+                    .replay(&root_hash)
+                    .map_err(|reason| Error::InconsistentAccess { index, reason })
+            },
+        )
+    }
+
+    /// Check that the log takes the machine from `root_hash_before` to `root_hash_after`
+    pub fn verify_state_transition_local(
+        &self,
+        root_hash_before: &Hash,
+        root_hash_after: &Hash,
+    ) -> Result<(), Error> {
+        let computed = self.verify_local(root_hash_before)?;
+        if computed != *root_hash_after {
+            return Err(Error::RootHashMismatch {
+                expected: *root_hash_after,
+                computed,
+            });
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,171 @@
+// Copyright (C) 2021 Cartesi Pte. Ltd.
+//
+// Licensed under the Apache License, Version 2.0 (the "License"); you may not use
+// this file except in compliance with the License. You may obtain a copy of the
+// License at http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software distributed
+// under the License is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR
+// CONDITIONS OF ANY KIND, either express or implied. See the License for the
+// specific language governing permissions and limitations under the License.
+
+//! Settings that control how the client issues calls
+
+use std::collections::hash_map::RandomState;
+use std::collections::HashMap;
+use std::hash::{BuildHasher, Hasher};
+use std::time::Duration;
+
+use crate::client::Error;
+
+/// Client methods that issue server calls, and so can have their own timeout
+pub(crate) const METHODS: &[&str] = &[
+    "check_connection",
+    "create_machine",
+    "destroy",
+    "dump_pmas",
+    "fork",
+    "get_csr_address",
+    "get_default_config",
+    "get_f_address",
+    "get_initial_config",
+    "get_proof",
+    "get_root_hash",
+    "get_uarch_x_address",
+    "get_version",
+    "get_x_address",
+    "load_machine",
+    "read_csr",
+    "read_f",
+    "read_iflags_h",
+    "read_iflags_prv",
+    "read_iflags_x",
+    "read_iflags_y",
+    "read_memory",
+    "read_uarch_halt_flag",
+    "read_uarch_x",
+    "read_virtual_memory",
+    "read_word",
+    "read_x",
+    "replace_memory_range",
+    "reset_iflags_x",
+    "reset_iflags_y",
+    "reset_uarch_state",
+    "run",
+    "run_uarch",
+    "set_iflags_h",
+    "set_iflags_x",
+    "set_iflags_y",
+    "set_uarch_halt_flag",
+    "shutdown",
+    "step",
+    "store",
+    "verify_access_log",
+    "verify_dirty_page_maps",
+    "verify_merkle_tree",
+    "verify_state_transition",
+    "write_csr",
+    "write_f",
+    "write_memory",
+    "write_uarch_x",
+    "write_virtual_memory",
+    "write_x",
+];
+
+#[doc = " Client behaviour configuration"]
+#[doc = " \\details"]
+#[doc = " Methods are named as on JsonRpcCartesiMachineClient, such as \"run\" or"]
+#[doc = " \"read_x\". A method without an override uses the default timeout."]
+#[derive(Debug, Clone, Default)]
+pub struct ClientConfig {
+    #[doc = "< Timeout of every call, None waits forever"]
+    pub default_timeout: Option<Duration>,
+    #[doc = "< Per-method timeouts, overriding the default. None waits forever"]
+    method_timeouts: HashMap<&'static str, Option<Duration>>,
+    #[doc = "< Retries of idempotent reads after transport failures, None never retries"]
+    pub retry: Option<RetryPolicy>,
+}
+
+impl ClientConfig {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Set the timeout of calls to `method`, None waiting forever.
+    /// Fails with [`Error::InvalidArgument`] if no client method of that name calls the server
+    pub fn set_method_timeout(
+        &mut self,
+        method: &str,
+        timeout: Option<Duration>,
+    ) -> Result<&mut Self, Error> {
+        let method = METHODS
+            .iter()
+            .find(|known| **known == method)
+            .ok_or_else(|| Error::InvalidArgument(format!("unknown client method {:?}", method)))?;
+        self.method_timeouts.insert(method, timeout);
+        Ok(self)
+    }
+
+    /// Timeout that applies to calls to `method`
+    pub fn timeout(&self, method: &str) -> Option<Duration> {
+        match self.method_timeouts.get(method) {
+            Some(timeout) => *timeout,
+            None => self.default_timeout,
+        }
+    }
+}
+
+#[doc = " Retry policy for idempotent reads that fail to reach the server"]
+#[doc = " \\details"]
+#[doc = " Only transport failures are retried, and only by methods that read"]
+#[doc = " state, such as read_x or get_proof. Calls that change the machine,"]
+#[doc = " such as run, step or write_memory, are never retried."]
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    #[doc = "< Total number of attempts, including the first"]
+    pub max_attempts: u32,
+    #[doc = "< Wait before the first retry, doubled for each further retry"]
+    pub initial_backoff: Duration,
+    #[doc = "< Upper bound of the wait between attempts"]
+    pub max_backoff: Duration,
+    #[doc = "< Fraction of each wait, between 0 and 1, that is randomized"]
+    pub jitter: f64,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            max_attempts: 3,
+            initial_backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_secs(5),
+            jitter: 0.5,
+        }
+    }
+}
+
+impl RetryPolicy {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Wait before the attempt following failed attempt number `attempt`, counted from 1.
+    /// None once every attempt has been used
+    pub fn backoff(&self, attempt: u32) -> Option<Duration> {
+        if attempt >= self.max_attempts {
+            return None;
+        }
+        let backoff = self
+            .initial_backoff
+            .checked_mul(1u32.checked_shl(attempt - 1).unwrap_or(u32::MAX))
+            .unwrap_or(self.max_backoff)
+            .min(self.max_backoff);
+        let jitter = self.jitter.clamp(0.0, 1.0) * random_fraction();
+        Some(backoff.mul_f64(1.0 - jitter))
+    }
+}
+
+/// Random number in [0, 1), good enough to spread retries apart
+fn random_fraction() -> f64 {
+    let bits = RandomState::new().build_hasher().finish();
+    (bits >> 11) as f64 / (1u64 << 53) as f64
+}
@@ -0,0 +1,193 @@
+// Copyright (C) 2021 Cartesi Pte. Ltd.
+//
+// Licensed under the Apache License, Version 2.0 (the "License"); you may not use
+// this file except in compliance with the License. You may obtain a copy of the
+// License at http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software distributed
+// under the License is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR
+// CONDITIONS OF ANY KIND, either express or implied. See the License for the
+// specific language governing permissions and limitations under the License.
+
+//! Client side validation and persistence of machine configurations
+
+use std::fs;
+use std::path::Path;
+
+use crate::client::{
+    Error, MachineConfig, MachineConfigBuilder, MemoryRangeConfig, MARCHID, MIMPID, MVENDORID,
+};
+
+/// Start of RAM in the physical address space
+const RAM_START: u64 = 0x80000000;
+
+/// Size of a memory page, the granularity of every memory range
+const PAGE_SIZE: u64 = 4096;
+
+/// Regions the emulator maps at fixed addresses, as (name, start, length)
+const FIXED_REGIONS: &[(&str, u64, u64)] = &[
+    ("shadow state", 0x0, 0x1000),
+    ("rom", 0x1000, 0xf000),
+    ("shadow pmas", 0x10000, 0x1000),
+    ("shadow tlb", 0x20000, 0x6000),
+    ("clint", 0x2000000, 0xc0000),
+    ("htif", 0x40008000, 0x1000),
+];
+
+/// Start of the microarchitecture RAM, whose length is configured
+const UARCH_RAM_START: u64 = 0x70000000;
+
+impl MachineConfigBuilder {
+    /// Build the machine configuration, rejecting it if it fails validation
+    pub fn build(&self) -> Result<MachineConfig, Error> {
+        let config = self
+            .build_unchecked()
+            .map_err(|err| Error::InvalidConfig(vec![err.to_string()]))?;
+        config.validate()?;
+        Ok(config)
+    }
+}
+
+impl MachineConfig {
+    /// Check the configuration for problems the server would reject
+    ///
+    /// Every problem found is reported in a single Error::InvalidConfig.
+    pub fn validate(&self) -> Result<(), Error> {
+        let mut problems = Vec::new();
+        let processor = &self.processor;
+        for (name, value, expected) in &[
+            ("mvendorid", processor.mvendorid, MVENDORID),
+            ("marchid", processor.marchid, MARCHID),
+            ("mimpid", processor.mimpid, MIMPID),
+        ] {
+            if value != expected {
+                problems.push(format!(
+                    "processor: {} {:#x} is not the emulator's {:#x}",
+                    name, value, expected
+                ));
+            }
+        }
+        if self.rom.image_filename.is_empty() {
+            problems.push("rom: image filename is missing".to_string());
+        }
+        if self.ram.length == 0 || self.ram.length & (PAGE_SIZE - 1) != 0 {
+            problems.push(format!(
+                "ram: length {:#x} is not a non-zero multiple of {:#x}",
+                self.ram.length, PAGE_SIZE
+            ));
+        }
+        let mut ranges = vec![("ram".to_string(), RAM_START, self.ram.length)];
+        for (index, drive) in self.flash_drives.iter().enumerate() {
+            let name = format!("flash drive {}", index);
+            check_range(&name, drive, &mut problems);
+            ranges.push((name, drive.start, drive.length));
+        }
+        let rollup = &self.rollup;
+        for (name, buffer) in &[
+            ("rollup rx buffer", &rollup.rx_buffer),
+            ("rollup tx buffer", &rollup.tx_buffer),
+            ("rollup input metadata", &rollup.input_metadata),
+            ("rollup voucher hashes", &rollup.voucher_hashes),
+            ("rollup notice hashes", &rollup.notice_hashes),
+        ] {
+            if let Some(buffer) = buffer {
+                check_range(name, buffer, &mut problems);
+                ranges.push((name.to_string(), buffer.start, buffer.length));
+            }
+        }
+        // Configured ranges must also stay clear of the devices at fixed addresses
+        let uarch_ram_length = self
+            .uarch
+            .ram
+            .as_ref()
+            .and_then(|ram| ram.length)
+            .unwrap_or(0);
+        ranges.push(("uarch ram".to_string(), UARCH_RAM_START, uarch_ram_length));
+        for (name, start, length) in FIXED_REGIONS {
+            ranges.push((name.to_string(), *start, *length));
+        }
+        for (i, (name, start, length)) in ranges.iter().enumerate() {
+            for (other_name, other_start, other_length) in &ranges[i + 1..] {
+                if overlaps(*start, *length, *other_start, *other_length) {
+                    problems.push(format!("{} overlaps {}", name, other_name));
+                }
+            }
+        }
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(Error::InvalidConfig(problems))
+        }
+    }
+
+    /// Load a configuration from a JSON or TOML file, chosen by extension
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        let path = path.as_ref();
+        let format = ConfigFormat::from_path(path)?;
+        let contents = fs::read_to_string(path)?;
+        match format {
+            ConfigFormat::Json => {
+                serde_json::from_str(&contents).map_err(|err| Error::Serialization(err.to_string()))
+            }
+            ConfigFormat::Toml => {
+                toml::from_str(&contents).map_err(|err| Error::Serialization(err.to_string()))
+            }
+        }
+    }
+
+    /// Save the configuration to a JSON or TOML file, chosen by extension
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), Error> {
+        let path = path.as_ref();
+        let contents = match ConfigFormat::from_path(path)? {
+            ConfigFormat::Json => serde_json::to_string_pretty(self)
+                .map_err(|err| Error::Serialization(err.to_string()))?,
+            ConfigFormat::Toml => {
+                toml::to_string_pretty(self).map_err(|err| Error::Serialization(err.to_string()))?
+            }
+        };
+        fs::write(path, contents)?;
+        Ok(())
+    }
+}
+
+/// File formats a configuration can be stored in
+enum ConfigFormat {
+    Json,
+    Toml,
+}
+
+impl ConfigFormat {
+    fn from_path(path: &Path) -> Result<Self, Error> {
+        match path.extension().and_then(|extension| extension.to_str()) {
+            Some("json") => Ok(ConfigFormat::Json),
+            Some("toml") => Ok(ConfigFormat::Toml),
+            _ => Err(Error::InvalidArgument(format!(
+                "cannot tell config format of {}, expected a .json or .toml file",
+                path.display()
+            ))),
+        }
+    }
+}
+
+/// Check that a memory range has a power-of-two length and a page aligned start
+fn check_range(name: &str, range: &MemoryRangeConfig, problems: &mut Vec<String>) {
+    if !range.length.is_power_of_two() {
+        problems.push(format!(
+            "{}: length {:#x} is not a power of two",
+            name, range.length
+        ));
+    }
+    if range.start & (PAGE_SIZE - 1) != 0 {
+        problems.push(format!(
+            "{}: start {:#x} is not aligned to {:#x}",
+            name, range.start, PAGE_SIZE
+        ));
+    }
+}
+
+fn overlaps(start: u64, length: u64, other_start: u64, other_length: u64) -> bool {
+    length != 0
+        && other_length != 0
+        && start < other_start.saturating_add(other_length)
+        && other_start < start.saturating_add(length)
+}
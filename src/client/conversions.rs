@@ -12,6 +12,20 @@
 use crate::client::*;
 use crate::interfaces::{self, FRegConfig, XRegConfig};
 
+/// Encode binary data as base64 the way the server expects it
+pub(crate) fn encode_wire_base64(data: &[u8]) -> String {
+    let mut encoded = STANDARD.encode(data);
+    if encoded.ends_with('=') {
+        encoded.push('\n');
+    }
+    encoded
+}
+
+/// Decode base64 binary data sent by the server, which may contain line breaks
+pub(crate) fn decode_wire_base64(encoded: &str) -> Result<Vec<u8>, Error> {
+    Ok(STANDARD.decode(encoded.replace('\n', ""))?)
+}
+
 impl From<&MachineRuntimeConfig> for interfaces::MachineRuntimeConfig {
     fn from(rc: &MachineRuntimeConfig) -> Self {
         interfaces::MachineRuntimeConfig {
@@ -33,24 +47,17 @@ impl From<&MerkleTreeProof> for interfaces::Proof {
             target_address: proof.target_address,
             log_2_target_size: proof.log2_target_size as u64,
             log_2_root_size: proof.log2_root_size as u64,
-            target_hash: proof.target_hash.clone(),
-            root_hash: proof.root_hash.clone(),
-            sibling_hashes: proof.sibling_hashes.clone(),
+            target_hash: proof.target_hash.to_wire(),
+            root_hash: proof.root_hash.to_wire(),
+            sibling_hashes: proof.sibling_hashes.iter().map(|h| h.to_wire()).collect(),
         }
     }
 }
 
 impl From<&Access> for interfaces::Access {
     fn from(access: &Access) -> Self {
-        let mut read = STANDARD.encode(&access.read_data);
-        let mut written = STANDARD.encode(&access.written_data);
-
-        if read.ends_with("=") {
-            read.push('\n');
-        }
-        if written.ends_with("=") {
-            written.push('\n');
-        }
+        let read = encode_wire_base64(&access.read_data);
+        let written = encode_wire_base64(&access.written_data);
 
         interfaces::Access {
             r#type: match access.r#type {
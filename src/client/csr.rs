@@ -0,0 +1,99 @@
+// Copyright (C) 2021 Cartesi Pte. Ltd.
+//
+// Licensed under the Apache License, Version 2.0 (the "License"); you may not use
+// this file except in compliance with the License. You may obtain a copy of the
+// License at http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software distributed
+// under the License is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR
+// CONDITIONS OF ANY KIND, either express or implied. See the License for the
+// specific language governing permissions and limitations under the License.
+
+//! Control and status registers accepted by the Cartesi machine server
+
+use std::fmt;
+use std::str::FromStr;
+
+use crate::client::Error;
+
+macro_rules! csrs {
+    ($($variant:ident => $name:literal,)*) => {
+        #[doc = " Control and status register of the Cartesi machine"]
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+        pub enum Csr {
+            $($variant,)*
+        }
+
+        impl Csr {
+            /// Every CSR accepted by the server, in server order
+            pub const ALL: &'static [Csr] = &[$(Csr::$variant,)*];
+
+            /// Name of the CSR on the wire
+            pub fn as_str(&self) -> &'static str {
+                match self {
+                    $(Csr::$variant => $name,)*
+                }
+            }
+        }
+
+        impl FromStr for Csr {
+            type Err = Error;
+
+            fn from_str(name: &str) -> Result<Self, Self::Err> {
+                match name {
+                    $($name => Ok(Csr::$variant),)*
+                    _ => Err(Error::InvalidArgument(format!("unknown CSR {:?}", name))),
+                }
+            }
+        }
+    };
+}
+
+csrs! {
+    Pc => "pc",
+    Fcsr => "fcsr",
+    Mvendorid => "mvendorid",
+    Marchid => "marchid",
+    Mimpid => "mimpid",
+    Mcycle => "mcycle",
+    Icycleinstret => "icycleinstret",
+    Mstatus => "mstatus",
+    Mtvec => "mtvec",
+    Mscratch => "mscratch",
+    Mepc => "mepc",
+    Mcause => "mcause",
+    Mtval => "mtval",
+    Misa => "misa",
+    Mie => "mie",
+    Mip => "mip",
+    Medeleg => "medeleg",
+    Mideleg => "mideleg",
+    Mcounteren => "mcounteren",
+    Menvcfg => "menvcfg",
+    Stvec => "stvec",
+    Sscratch => "sscratch",
+    Sepc => "sepc",
+    Scause => "scause",
+    Stval => "stval",
+    Satp => "satp",
+    Scounteren => "scounteren",
+    Senvcfg => "senvcfg",
+    Ilrsc => "ilrsc",
+    Iflags => "iflags",
+    ClintMtimecmp => "clint_mtimecmp",
+    HtifTohost => "htif_tohost",
+    HtifFromhost => "htif_fromhost",
+    HtifIhalt => "htif_ihalt",
+    HtifIconsole => "htif_iconsole",
+    HtifIyield => "htif_iyield",
+    UarchPc => "uarch_pc",
+    UarchCycle => "uarch_cycle",
+    UarchHaltFlag => "uarch_halt_flag",
+    UarchRamLength => "uarch_ram_length",
+}
+
+impl fmt::Display for Csr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
@@ -0,0 +1,141 @@
+// Copyright (C) 2021 Cartesi Pte. Ltd.
+//
+// Licensed under the Apache License, Version 2.0 (the "License"); you may not use
+// this file except in compliance with the License. You may obtain a copy of the
+// License at http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software distributed
+// under the License is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR
+// CONDITIONS OF ANY KIND, either express or implied. See the License for the
+// specific language governing permissions and limitations under the License.
+
+//! Error type returned by the Cartesi machine client
+
+use std::fmt;
+use std::process::ExitStatus;
+use std::time::Duration;
+
+use crate::client::Hash;
+
+/// Message the server uses to reject machine methods when no machine is loaded
+const NO_MACHINE_MESSAGE: &str = "no machine";
+
+#[doc = " Errors returned by the Cartesi machine client"]
+#[derive(Debug)]
+pub enum Error {
+    #[doc = "< Failure to reach the server or to exchange messages with it"]
+    Transport(jsonrpsee::core::Error),
+    #[doc = "< JSON-RPC error returned by the server"]
+    Server { code: i32, message: String },
+    #[doc = "< Reply from the server could not be decoded"]
+    Decode(String),
+    #[doc = "< Server has no machine instance loaded"]
+    NoMachine,
+    #[doc = "< Argument rejected by the client before reaching the server"]
+    InvalidArgument(String),
+    #[doc = "< Merkle tree proof is malformed"]
+    InvalidProof(String),
+    #[doc = "< Access at `index` of an access log does not match the state before it"]
+    InconsistentAccess { index: usize, reason: String },
+    #[doc = "< Replayed root hash differs from the expected one"]
+    RootHashMismatch { expected: Hash, computed: Hash },
+    #[doc = "< Uarch instruction cannot be executed"]
+    UarchException(String),
+    #[doc = "< Machine configuration failed validation, with every problem found"]
+    InvalidConfig(Vec<String>),
+    #[doc = "< Failure to read or write a file"]
+    Io(std::io::Error),
+    #[doc = "< Value could not be serialized or deserialized"]
+    Serialization(String),
+    #[doc = "< Call to `method` did not complete within `timeout`"]
+    Timeout {
+        method: &'static str,
+        timeout: Duration,
+    },
+    #[doc = "< Server process exited while starting, with what it wrote to stderr"]
+    ServerExited { status: ExitStatus, stderr: String },
+    #[doc = "< Operation stopped by its cancellation token"]
+    Cancelled,
+    #[doc = "< Rollup machine yielded or wrote outputs against the rollup protocol"]
+    Rollup(String),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Transport(err) => write!(f, "transport error: {}", err),
+            Error::Server { code, message } => {
+                write!(f, "server error {}: {}", code, message)
+            }
+            Error::Decode(message) => write!(f, "unable to decode server reply: {}", message),
+            Error::NoMachine => f.write_str("no machine loaded on the server"),
+            Error::InvalidArgument(message) => write!(f, "invalid argument: {}", message),
+            Error::InvalidProof(message) => write!(f, "invalid proof: {}", message),
+            Error::InconsistentAccess { index, reason } => {
+                write!(f, "inconsistent access {}: {}", index, reason)
+            }
+            Error::RootHashMismatch { expected, computed } => write!(
+                f,
+                "root hash mismatch: expected {}, computed {}",
+                expected, computed
+            ),
+            Error::UarchException(message) => write!(f, "uarch exception: {}", message),
+            Error::InvalidConfig(problems) => {
+                write!(f, "invalid machine config: {}", problems.join("; "))
+            }
+            Error::Io(err) => write!(f, "io error: {}", err),
+            Error::Serialization(message) => write!(f, "serialization error: {}", message),
+            Error::Timeout { method, timeout } => {
+                write!(f, "{} timed out after {:?}", method, timeout)
+            }
+            Error::ServerExited { status, stderr } => {
+                write!(f, "server exited with {}: {}", status, stderr.trim())
+            }
+            Error::Cancelled => write!(f, "cancelled"),
+            Error::Rollup(message) => write!(f, "rollup protocol violation: {}", message),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Transport(err) => Some(err),
+            Error::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<jsonrpsee::core::Error> for Error {
+    fn from(err: jsonrpsee::core::Error) -> Self {
+        use jsonrpsee::core::Error as RpcError;
+        match err {
+            RpcError::Call(object) => {
+                if object.message().eq_ignore_ascii_case(NO_MACHINE_MESSAGE) {
+                    Error::NoMachine
+                } else {
+                    Error::Server {
+                        code: object.code(),
+                        message: object.message().to_string(),
+                    }
+                }
+            }
+            RpcError::ParseError(err) => Error::Decode(err.to_string()),
+            RpcError::InvalidResponse(mismatch) => Error::Decode(mismatch.to_string()),
+            err => Error::Transport(err),
+        }
+    }
+}
+
+impl From<base64::DecodeError> for Error {
+    fn from(err: base64::DecodeError) -> Self {
+        Error::Decode(err.to_string())
+    }
+}
+
+impl From<std::io::Error> for Error {
+    fn from(err: std::io::Error) -> Self {
+        Error::Io(err)
+    }
+}
@@ -0,0 +1,99 @@
+// Copyright (C) 2021 Cartesi Pte. Ltd.
+//
+// Licensed under the Apache License, Version 2.0 (the "License"); you may not use
+// this file except in compliance with the License. You may obtain a copy of the
+// License at http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software distributed
+// under the License is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR
+// CONDITIONS OF ANY KIND, either express or implied. See the License for the
+// specific language governing permissions and limitations under the License.
+
+//! Lifetime management of forked Cartesi machine servers
+
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime};
+
+use jsonrpsee::core::client::ClientT;
+
+use crate::interfaces;
+
+#[doc = " Information about the server a forked client was created from"]
+#[derive(Debug, Clone)]
+pub struct ForkParent {
+    #[doc = "< Address of the parent server"]
+    pub address: String,
+    #[doc = "< Time at which the fork was requested"]
+    pub forked_at: SystemTime,
+}
+
+/// Time a forked client dropped outside a runtime waits for its server to shut down
+const BLOCKING_SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Request that shuts the forked server down, not yet issued
+type ShutdownFuture = Pin<Box<dyn Future<Output = ()> + Send>>;
+
+/// Shuts down a forked server once every client handle to it is dropped.
+/// Within a runtime the shutdown is spawned; outside one, drop blocks until it
+/// completes. A runtime that is shutting down may never run the spawned request
+pub(crate) struct ForkGuard {
+    shutdown: Mutex<Option<ShutdownFuture>>,
+    shutdown_on_drop: AtomicBool,
+}
+
+impl ForkGuard {
+    pub(crate) fn new<T>(client: interfaces::RemoteCartesiMachine<T>) -> Self
+    where
+        T: ClientT + Send + Sync + 'static,
+    {
+        let shutdown = async move {
+            let _ = client.Shutdown().await;
+        };
+        ForkGuard {
+            shutdown: Mutex::new(Some(Box::pin(shutdown))),
+            shutdown_on_drop: AtomicBool::new(true),
+        }
+    }
+
+    pub(crate) fn set_shutdown_on_drop(&self, enabled: bool) {
+        self.shutdown_on_drop.store(enabled, Ordering::SeqCst);
+    }
+
+    pub(crate) fn shutdown_on_drop(&self) -> bool {
+        self.shutdown_on_drop.load(Ordering::SeqCst)
+    }
+}
+
+impl Drop for ForkGuard {
+    fn drop(&mut self) {
+        if !self.shutdown_on_drop() {
+            return;
+        }
+        let shutdown = match self.shutdown.get_mut().ok().and_then(Option::take) {
+            Some(shutdown) => shutdown,
+            None => return,
+        };
+        match tokio::runtime::Handle::try_current() {
+            Ok(handle) => {
+                handle.spawn(shutdown);
+            }
+            // Outside a runtime, shut down on a thread of its own and wait for it
+            Err(_) => {
+                let thread = std::thread::spawn(move || {
+                    let runtime = tokio::runtime::Builder::new_current_thread()
+                        .enable_all()
+                        .build();
+                    if let Ok(runtime) = runtime {
+                        runtime.block_on(async {
+                            let _ = tokio::time::timeout(BLOCKING_SHUTDOWN_TIMEOUT, shutdown).await;
+                        });
+                    }
+                });
+                let _ = thread.join();
+            }
+        }
+    }
+}
@@ -0,0 +1,160 @@
+// Copyright (C) 2021 Cartesi Pte. Ltd.
+//
+// Licensed under the Apache License, Version 2.0 (the "License"); you may not use
+// this file except in compliance with the License. You may obtain a copy of the
+// License at http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software distributed
+// under the License is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR
+// CONDITIONS OF ANY KIND, either express or implied. See the License for the
+// specific language governing permissions and limitations under the License.
+
+//! Hash type used for Merkle tree nodes of the Cartesi machine state
+
+use std::convert::TryFrom;
+use std::fmt;
+use std::str::FromStr;
+
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::client::conversions::{decode_wire_base64, encode_wire_base64};
+use crate::client::Error;
+
+#[doc = " Keccak-256 hash of a node in the machine state Merkle tree"]
+#[doc = " \\details"]
+#[doc = " Displays and serializes as lowercase hex. Parses from hex, with or"]
+#[doc = " without a 0x prefix, or from base64."]
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub struct Hash([u8; Hash::LENGTH]);
+
+impl Hash {
+    #[doc = " Length of a hash in bytes"]
+    pub const LENGTH: usize = 32;
+
+    pub const fn new(bytes: [u8; Hash::LENGTH]) -> Self {
+        Hash(bytes)
+    }
+
+    pub fn as_bytes(&self) -> &[u8; Hash::LENGTH] {
+        &self.0
+    }
+
+    /// Encode as lowercase hex, without prefix
+    pub fn to_hex(&self) -> String {
+        self.0.iter().map(|byte| format!("{:02x}", byte)).collect()
+    }
+
+    /// Encode as standard base64
+    pub fn to_base64(&self) -> String {
+        STANDARD.encode(self.0)
+    }
+
+    /// Decode from hex, with or without a 0x prefix
+    pub fn from_hex(hex: &str) -> Result<Self, Error> {
+        let hex = hex.strip_prefix("0x").unwrap_or(hex);
+        if hex.len() != 2 * Hash::LENGTH || !hex.is_ascii() {
+            return Err(Error::Decode(format!("invalid hex hash {:?}", hex)));
+        }
+        let mut bytes = [0u8; Hash::LENGTH];
+        for (i, byte) in bytes.iter_mut().enumerate() {
+            *byte = u8::from_str_radix(&hex[2 * i..2 * i + 2], 16)
+                .map_err(|_| Error::Decode(format!("invalid hex hash {:?}", hex)))?;
+        }
+        Ok(Hash(bytes))
+    }
+
+    /// Decode from standard base64
+    pub fn from_base64(base64: &str) -> Result<Self, Error> {
+        Hash::try_from(STANDARD.decode(base64)?.as_slice())
+    }
+
+    /// Decode a hash as sent by the server
+    pub(crate) fn from_wire(wire: &str) -> Result<Self, Error> {
+        Hash::try_from(decode_wire_base64(wire)?.as_slice())
+    }
+
+    /// Encode a hash as expected by the server
+    pub(crate) fn to_wire(self) -> String {
+        encode_wire_base64(&self.0)
+    }
+}
+
+impl From<[u8; Hash::LENGTH]> for Hash {
+    fn from(bytes: [u8; Hash::LENGTH]) -> Self {
+        Hash(bytes)
+    }
+}
+
+impl From<Hash> for [u8; Hash::LENGTH] {
+    fn from(hash: Hash) -> Self {
+        hash.0
+    }
+}
+
+impl TryFrom<&[u8]> for Hash {
+    type Error = Error;
+
+    fn try_from(bytes: &[u8]) -> Result<Self, Self::Error> {
+        <[u8; Hash::LENGTH]>::try_from(bytes)
+            .map(Hash)
+            .map_err(|_| {
+                Error::Decode(format!(
+                    "hash has {} bytes, expected {}",
+                    bytes.len(),
+                    Hash::LENGTH
+                ))
+            })
+    }
+}
+
+impl AsRef<[u8]> for Hash {
+    fn as_ref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl PartialEq<[u8; Hash::LENGTH]> for Hash {
+    fn eq(&self, other: &[u8; Hash::LENGTH]) -> bool {
+        &self.0 == other
+    }
+}
+
+impl fmt::Display for Hash {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.to_hex())
+    }
+}
+
+impl fmt::Debug for Hash {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Hash(0x{})", self.to_hex())
+    }
+}
+
+impl FromStr for Hash {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        if s.starts_with("0x") || s.len() == 2 * Hash::LENGTH {
+            Hash::from_hex(s)
+        } else {
+            Hash::from_base64(s)
+        }
+    }
+}
+
+impl Serialize for Hash {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.to_hex())
+    }
+}
+
+impl<'de> Deserialize<'de> for Hash {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        s.parse().map_err(serde::de::Error::custom)
+    }
+}
@@ -0,0 +1,449 @@
+// Copyright (C) 2021 Cartesi Pte. Ltd.
+//
+// Licensed under the Apache License, Version 2.0 (the "License"); you may not use
+// this file except in compliance with the License. You may obtain a copy of the
+// License at http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software distributed
+// under the License is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR
+// CONDITIONS OF ANY KIND, either express or implied. See the License for the
+// specific language governing permissions and limitations under the License.
+
+//! Backend-independent interface to a Cartesi machine
+
+use std::collections::BTreeMap;
+
+use async_trait::async_trait;
+use jsonrpsee::core::client::ClientT;
+
+use crate::client::*;
+use crate::interfaces;
+
+#[doc = " Operations on a Cartesi machine, whatever backend hosts it"]
+#[doc = " \\details"]
+#[doc = " Implemented by JsonRpcCartesiMachineClient. Drivers written against this"]
+#[doc = " trait can run on mocks, recorders or other transports."]
+#[async_trait]
+pub trait CartesiMachine: Send + Sync {
+    /// Get Cartesi machine server version
+    async fn get_version(&self) -> Result<SemanticVersion, Error>;
+
+    /// Instantiate a machine from a configuration
+    async fn create_machine(
+        &self,
+        machine_config: &MachineConfig,
+        machine_runtime_config: &MachineRuntimeConfig,
+    ) -> Result<bool, Error>;
+
+    /// Load a machine previously stored in a directory
+    async fn load_machine(
+        &self,
+        directory: &str,
+        machine_runtime_config: &MachineRuntimeConfig,
+    ) -> Result<bool, Error>;
+
+    /// Run the machine until mcycle reaches `limit` or the machine halts or yields
+    async fn run(&self, limit: u64) -> Result<InterpreterBreakReason, Error>;
+
+    /// Run the machine until mcycle reaches `target` in chunks, reporting progress and
+    /// checking for cancellation between them, and stopping early on halt or yield
+    async fn run_until(
+        &self,
+        target: u64,
+        options: &RunOptions,
+    ) -> Result<InterpreterBreakReason, Error>;
+
+    /// Run the microarchitecture until its cycle reaches `limit` or it halts
+    async fn run_uarch(&self, limit: u64) -> Result<UarchInterpreterBreakReason, Error>;
+
+    /// Store the machine to a directory
+    async fn store(&self, directory: &str) -> Result<bool, Error>;
+
+    /// Destroy the machine instance
+    async fn destroy(&self) -> Result<bool, Error>;
+
+    /// Shut down the backend
+    async fn shutdown(&self) -> Result<bool, Error>;
+
+    /// Execute one microarchitecture instruction and return its access log
+    async fn step(&self, log_type: &AccessLogType, one_based: bool) -> Result<AccessLog, Error>;
+
+    /// Read a chunk of physical memory
+    async fn read_memory(&self, address: u64, length: u64) -> Result<Vec<u8>, Error>;
+
+    /// Write base64 encoded data to physical memory
+    async fn write_memory(&self, address: u64, data: String) -> Result<bool, Error>;
+
+    /// Read a chunk of virtual memory
+    async fn read_virtual_memory(&self, address: u64, length: u64) -> Result<Vec<u8>, Error>;
+
+    /// Write data to virtual memory
+    async fn write_virtual_memory(&self, address: u64, data: &[u8]) -> Result<bool, Error>;
+
+    /// Read the word at an aligned physical address
+    async fn read_word(&self, address: u64) -> Result<u64, Error>;
+
+    /// Get the root hash of the machine state
+    async fn get_root_hash(&self) -> Result<Hash, Error>;
+
+    /// Get the proof of a node of the machine state Merkle tree
+    async fn get_proof(&self, address: u64, log2_size: u64) -> Result<MerkleTreeProof, Error>;
+
+    /// Replace a flash drive or rollup memory range
+    async fn replace_memory_range(
+        &self,
+        config: interfaces::MemoryRangeConfig,
+    ) -> Result<bool, Error>;
+
+    /// Get the address of general purpose register `index`
+    async fn get_x_address(&self, index: u64) -> Result<u64, Error>;
+
+    /// Read general purpose register `index`
+    async fn read_x(&self, index: u64) -> Result<u64, Error>;
+
+    /// Write general purpose register `index`
+    async fn write_x(&self, index: u64, value: u64) -> Result<bool, Error>;
+
+    /// Get the address of floating point register `index`
+    async fn get_f_address(&self, index: u64) -> Result<u64, Error>;
+
+    /// Read floating point register `index`
+    async fn read_f(&self, index: u64) -> Result<u64, Error>;
+
+    /// Write floating point register `index`
+    async fn write_f(&self, index: u64, value: u64) -> Result<bool, Error>;
+
+    /// Get the address of microarchitecture register `index`
+    async fn get_uarch_x_address(&self, index: u64) -> Result<u64, Error>;
+
+    /// Read microarchitecture register `index`
+    async fn read_uarch_x(&self, index: u64) -> Result<u64, Error>;
+
+    /// Write microarchitecture register `index`
+    async fn write_uarch_x(&self, index: u64, value: u64) -> Result<bool, Error>;
+
+    /// Get the address of a CSR
+    async fn get_csr_address(&self, csr: Csr) -> Result<u64, Error>;
+
+    /// Read a CSR
+    async fn read_csr(&self, csr: Csr) -> Result<u64, Error>;
+
+    /// Write a CSR
+    async fn write_csr(&self, csr: Csr, value: u64) -> Result<bool, Error>;
+
+    /// Read every CSR
+    async fn read_all_csrs(&self) -> Result<BTreeMap<Csr, u64>, Error> {
+        let mut csrs = BTreeMap::new();
+        for csr in Csr::ALL {
+            csrs.insert(*csr, self.read_csr(*csr).await?);
+        }
+        Ok(csrs)
+    }
+
+    /// Read the decoded iflags CSR
+    async fn read_iflags(&self) -> Result<IFlags, Error>;
+
+    /// Read the privilege level stored in iflags
+    async fn read_iflags_prv(&self) -> Result<PrivilegeLevel, Error>;
+
+    /// Read the iflags H (halted) flag
+    async fn read_iflags_h(&self) -> Result<bool, Error>;
+
+    /// Read the iflags X (automatic yield) flag
+    async fn read_iflags_x(&self) -> Result<bool, Error>;
+
+    /// Read the iflags Y (manual yield) flag
+    async fn read_iflags_y(&self) -> Result<bool, Error>;
+
+    /// Set the iflags H (halted) flag
+    async fn set_iflags_h(&self) -> Result<bool, Error>;
+
+    /// Set the iflags X (automatic yield) flag
+    async fn set_iflags_x(&self) -> Result<bool, Error>;
+
+    /// Reset the iflags X (automatic yield) flag
+    async fn reset_iflags_x(&self) -> Result<bool, Error>;
+
+    /// Set the iflags Y (manual yield) flag
+    async fn set_iflags_y(&self) -> Result<bool, Error>;
+
+    /// Reset the iflags Y (manual yield) flag
+    async fn reset_iflags_y(&self) -> Result<bool, Error>;
+
+    /// Read the microarchitecture halt flag
+    async fn read_uarch_halt_flag(&self) -> Result<bool, Error>;
+
+    /// Set the microarchitecture halt flag
+    async fn set_uarch_halt_flag(&self) -> Result<bool, Error>;
+
+    /// Reset the microarchitecture to its initial state
+    async fn reset_uarch_state(&self) -> Result<bool, Error>;
+
+    /// Get the configuration the machine was created with
+    async fn get_initial_config(&self) -> Result<MachineConfig, Error>;
+
+    /// Get the default machine configuration
+    async fn get_default_config(&self) -> Result<MachineConfig, Error>;
+
+    /// Verify an access log
+    async fn verify_access_log(
+        &self,
+        log: &AccessLog,
+        runtime: &MachineRuntimeConfig,
+        one_based: bool,
+    ) -> Result<bool, Error>;
+
+    /// Verify that an access log takes the machine from one root hash to another
+    async fn verify_state_transition(
+        &self,
+        root_hash_before: &Hash,
+        log: &AccessLog,
+        root_hash_after: &Hash,
+        one_based: bool,
+        runtime: &MachineRuntimeConfig,
+    ) -> Result<bool, Error>;
+}
+
+#[async_trait]
+impl<T: ClientT + Send + Sync + 'static> CartesiMachine for JsonRpcCartesiMachineClient<T> {
+    async fn get_version(&self) -> Result<SemanticVersion, Error> {
+        JsonRpcCartesiMachineClient::get_version(self).await
+    }
+
+    async fn create_machine(
+        &self,
+        machine_config: &MachineConfig,
+        machine_runtime_config: &MachineRuntimeConfig,
+    ) -> Result<bool, Error> {
+        JsonRpcCartesiMachineClient::create_machine(self, machine_config, machine_runtime_config)
+            .await
+    }
+
+    async fn load_machine(
+        &self,
+        directory: &str,
+        machine_runtime_config: &MachineRuntimeConfig,
+    ) -> Result<bool, Error> {
+        JsonRpcCartesiMachineClient::load_machine(self, directory, machine_runtime_config).await
+    }
+
+    async fn run(&self, limit: u64) -> Result<InterpreterBreakReason, Error> {
+        JsonRpcCartesiMachineClient::run(self, limit).await
+    }
+
+    async fn run_until(
+        &self,
+        target: u64,
+        options: &RunOptions,
+    ) -> Result<InterpreterBreakReason, Error> {
+        JsonRpcCartesiMachineClient::run_until(self, target, options).await
+    }
+
+    async fn run_uarch(&self, limit: u64) -> Result<UarchInterpreterBreakReason, Error> {
+        JsonRpcCartesiMachineClient::run_uarch(self, limit).await
+    }
+
+    async fn store(&self, directory: &str) -> Result<bool, Error> {
+        JsonRpcCartesiMachineClient::store(self, directory).await
+    }
+
+    async fn destroy(&self) -> Result<bool, Error> {
+        JsonRpcCartesiMachineClient::destroy(self).await
+    }
+
+    async fn shutdown(&self) -> Result<bool, Error> {
+        JsonRpcCartesiMachineClient::shutdown(self).await
+    }
+
+    async fn step(&self, log_type: &AccessLogType, one_based: bool) -> Result<AccessLog, Error> {
+        JsonRpcCartesiMachineClient::step(self, log_type, one_based).await
+    }
+
+    async fn read_memory(&self, address: u64, length: u64) -> Result<Vec<u8>, Error> {
+        JsonRpcCartesiMachineClient::read_memory(self, address, length).await
+    }
+
+    async fn write_memory(&self, address: u64, data: String) -> Result<bool, Error> {
+        JsonRpcCartesiMachineClient::write_memory(self, address, data).await
+    }
+
+    async fn read_virtual_memory(&self, address: u64, length: u64) -> Result<Vec<u8>, Error> {
+        JsonRpcCartesiMachineClient::read_virtual_memory(self, address, length).await
+    }
+
+    async fn write_virtual_memory(&self, address: u64, data: &[u8]) -> Result<bool, Error> {
+        JsonRpcCartesiMachineClient::write_virtual_memory(self, address, data).await
+    }
+
+    async fn read_word(&self, address: u64) -> Result<u64, Error> {
+        JsonRpcCartesiMachineClient::read_word(self, address).await
+    }
+
+    async fn get_root_hash(&self) -> Result<Hash, Error> {
+        JsonRpcCartesiMachineClient::get_root_hash(self).await
+    }
+
+    async fn get_proof(&self, address: u64, log2_size: u64) -> Result<MerkleTreeProof, Error> {
+        JsonRpcCartesiMachineClient::get_proof(self, address, log2_size).await
+    }
+
+    async fn replace_memory_range(
+        &self,
+        config: interfaces::MemoryRangeConfig,
+    ) -> Result<bool, Error> {
+        JsonRpcCartesiMachineClient::replace_memory_range(self, config).await
+    }
+
+    async fn get_x_address(&self, index: u64) -> Result<u64, Error> {
+        JsonRpcCartesiMachineClient::get_x_address(self, index).await
+    }
+
+    async fn read_x(&self, index: u64) -> Result<u64, Error> {
+        JsonRpcCartesiMachineClient::read_x(self, index).await
+    }
+
+    async fn write_x(&self, index: u64, value: u64) -> Result<bool, Error> {
+        JsonRpcCartesiMachineClient::write_x(self, index, value).await
+    }
+
+    async fn get_f_address(&self, index: u64) -> Result<u64, Error> {
+        JsonRpcCartesiMachineClient::get_f_address(self, index).await
+    }
+
+    async fn read_f(&self, index: u64) -> Result<u64, Error> {
+        JsonRpcCartesiMachineClient::read_f(self, index).await
+    }
+
+    async fn write_f(&self, index: u64, value: u64) -> Result<bool, Error> {
+        JsonRpcCartesiMachineClient::write_f(self, index, value).await
+    }
+
+    async fn get_uarch_x_address(&self, index: u64) -> Result<u64, Error> {
+        JsonRpcCartesiMachineClient::get_uarch_x_address(self, index).await
+    }
+
+    async fn read_uarch_x(&self, index: u64) -> Result<u64, Error> {
+        JsonRpcCartesiMachineClient::read_uarch_x(self, index).await
+    }
+
+    async fn write_uarch_x(&self, index: u64, value: u64) -> Result<bool, Error> {
+        JsonRpcCartesiMachineClient::write_uarch_x(self, index, value).await
+    }
+
+    async fn get_csr_address(&self, csr: Csr) -> Result<u64, Error> {
+        JsonRpcCartesiMachineClient::get_csr_address(self, csr).await
+    }
+
+    async fn read_csr(&self, csr: Csr) -> Result<u64, Error> {
+        JsonRpcCartesiMachineClient::read_csr(self, csr).await
+    }
+
+    async fn write_csr(&self, csr: Csr, value: u64) -> Result<bool, Error> {
+        JsonRpcCartesiMachineClient::write_csr(self, csr, value).await
+    }
+
+    async fn read_iflags(&self) -> Result<IFlags, Error> {
+        JsonRpcCartesiMachineClient::read_iflags(self).await
+    }
+
+    async fn read_iflags_prv(&self) -> Result<PrivilegeLevel, Error> {
+        JsonRpcCartesiMachineClient::read_iflags_prv(self).await
+    }
+
+    async fn read_iflags_h(&self) -> Result<bool, Error> {
+        JsonRpcCartesiMachineClient::read_iflags_h(self).await
+    }
+
+    async fn read_iflags_x(&self) -> Result<bool, Error> {
+        JsonRpcCartesiMachineClient::read_iflags_x(self).await
+    }
+
+    async fn read_iflags_y(&self) -> Result<bool, Error> {
+        JsonRpcCartesiMachineClient::read_iflags_y(self).await
+    }
+
+    async fn set_iflags_h(&self) -> Result<bool, Error> {
+        JsonRpcCartesiMachineClient::set_iflags_h(self).await
+    }
+
+    async fn set_iflags_x(&self) -> Result<bool, Error> {
+        JsonRpcCartesiMachineClient::set_iflags_x(self).await
+    }
+
+    async fn reset_iflags_x(&self) -> Result<bool, Error> {
+        JsonRpcCartesiMachineClient::reset_iflags_x(self).await
+    }
+
+    async fn set_iflags_y(&self) -> Result<bool, Error> {
+        JsonRpcCartesiMachineClient::set_iflags_y(self).await
+    }
+
+    async fn reset_iflags_y(&self) -> Result<bool, Error> {
+        JsonRpcCartesiMachineClient::reset_iflags_y(self).await
+    }
+
+    async fn read_uarch_halt_flag(&self) -> Result<bool, Error> {
+        JsonRpcCartesiMachineClient::read_uarch_halt_flag(self).await
+    }
+
+    async fn set_uarch_halt_flag(&self) -> Result<bool, Error> {
+        JsonRpcCartesiMachineClient::set_uarch_halt_flag(self).await
+    }
+
+    async fn reset_uarch_state(&self) -> Result<bool, Error> {
+        JsonRpcCartesiMachineClient::reset_uarch_state(self).await
+    }
+
+    async fn get_initial_config(&self) -> Result<MachineConfig, Error> {
+        JsonRpcCartesiMachineClient::get_initial_config(self).await
+    }
+
+    async fn get_default_config(&self) -> Result<MachineConfig, Error> {
+        JsonRpcCartesiMachineClient::get_default_config(self).await
+    }
+
+    async fn verify_access_log(
+        &self,
+        log: &AccessLog,
+        runtime: &MachineRuntimeConfig,
+        one_based: bool,
+    ) -> Result<bool, Error> {
+        JsonRpcCartesiMachineClient::verify_access_log(self, log, runtime, one_based).await
+    }
+
+    async fn verify_state_transition(
+        &self,
+        root_hash_before: &Hash,
+        log: &AccessLog,
+        root_hash_after: &Hash,
+        one_based: bool,
+        runtime: &MachineRuntimeConfig,
+    ) -> Result<bool, Error> {
+        JsonRpcCartesiMachineClient::verify_state_transition(
+            self,
+            root_hash_before,
+            log,
+            root_hash_after,
+            one_based,
+            runtime,
+        )
+        .await
+    }
+}
+
+#[doc = " Cartesi machine that can be forked into an independent copy"]
+#[doc = " \\details"]
+#[doc = " Separate from CartesiMachine because forking a JsonRpcCartesiMachineClient"]
+#[doc = " needs a transport that implements Reconnect, which other uses do not."]
+#[async_trait]
+pub trait ForkableCartesiMachine: CartesiMachine + Sized {
+    /// Fork the backend and return a handle to the copy
+    async fn fork_client(&self) -> Result<Self, Error>;
+}
+
+#[async_trait]
+impl<T: Reconnect> ForkableCartesiMachine for JsonRpcCartesiMachineClient<T> {
+    async fn fork_client(&self) -> Result<Self, Error> {
+        JsonRpcCartesiMachineClient::fork_client(self).await
+    }
+}
@@ -0,0 +1,257 @@
+// Copyright (C) 2021 Cartesi Pte. Ltd.
+//
+// Licensed under the Apache License, Version 2.0 (the "License"); you may not use
+// this file except in compliance with the License. You may obtain a copy of the
+// License at http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software distributed
+// under the License is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR
+// CONDITIONS OF ANY KIND, either express or implied. See the License for the
+// specific language governing permissions and limitations under the License.
+
+//! Offline verification of machine state Merkle tree proofs
+//!
+//! The emulator keeps its state in a binary Merkle tree whose leaves are
+//! 64-bit words. A leaf hash is the Keccak-256 of the word, and an inner node
+//! hash is the Keccak-256 of the concatenation of its children hashes.
+
+use std::collections::BTreeMap;
+
+use sha3::{Digest, Keccak256};
+
+use crate::client::{Error, Hash, MerkleTreeProof};
+
+#[doc = " Log2 of the size in bytes of a Merkle tree leaf"]
+pub const LOG2_WORD_SIZE: usize = 3;
+#[doc = " Log2 of the size in bytes of the whole machine state"]
+pub const LOG2_ROOT_SIZE: usize = 64;
+
+const WORD_MASK: u64 = (1 << LOG2_WORD_SIZE) - 1;
+
+/// Keccak-256 hash of arbitrary data
+pub fn keccak256(data: &[u8]) -> Hash {
+    Hash::new(Keccak256::digest(data).into())
+}
+
+/// Hash of an inner node given the hashes of its children
+pub fn hash_children(left: &Hash, right: &Hash) -> Hash {
+    let mut hasher = Keccak256::new();
+    hasher.update(left.as_bytes());
+    hasher.update(right.as_bytes());
+    Hash::new(hasher.finalize().into())
+}
+
+/// Hash of the Merkle tree spanning `data`, whose length must be a power of two no smaller than a word
+pub fn merkle_root_hash(data: &[u8]) -> Result<Hash, Error> {
+    if !data.len().is_power_of_two() || data.len() < 1 << LOG2_WORD_SIZE {
+        return Err(Error::InvalidArgument(format!(
+            "cannot hash {} bytes: length must be a power of two of at least {}",
+            data.len(),
+            1 << LOG2_WORD_SIZE
+        )));
+    }
+    Ok(subtree_hash(data))
+}
+
+fn subtree_hash(data: &[u8]) -> Hash {
+    if data.len() == 1 << LOG2_WORD_SIZE {
+        keccak256(data)
+    } else {
+        let (left, right) = data.split_at(data.len() / 2);
+        hash_children(&subtree_hash(left), &subtree_hash(right))
+    }
+}
+
+impl MerkleTreeProof {
+    /// Sibling hash at the level spanning 2^log2_size bytes
+    fn sibling_hash(&self, log2_size: usize) -> &Hash {
+        &self.sibling_hashes[self.log2_root_size - 1 - log2_size]
+    }
+
+    /// Recompute the root hash the tree would have if the target node hashed to `target_hash`,
+    /// all other nodes being left untouched
+    pub fn root_hash_for(&self, target_hash: &Hash) -> Result<Hash, Error> {
+        if self.log2_target_size < LOG2_WORD_SIZE
+            || self.log2_target_size > self.log2_root_size
+            || self.log2_root_size > LOG2_ROOT_SIZE
+        {
+            return Err(Error::InvalidProof(format!(
+                "invalid sizes: log2_target_size {}, log2_root_size {}",
+                self.log2_target_size, self.log2_root_size
+            )));
+        }
+        let levels = self.log2_root_size - self.log2_target_size;
+        if self.sibling_hashes.len() != levels {
+            return Err(Error::InvalidProof(format!(
+                "expected {} sibling hashes, got {}",
+                levels,
+                self.sibling_hashes.len()
+            )));
+        }
+        let target_mask = (1u128 << self.log2_target_size) - 1;
+        if self.target_address as u128 & target_mask != 0 {
+            return Err(Error::InvalidProof(format!(
+                "target address {:#x} is not aligned to 2^{}",
+                self.target_address, self.log2_target_size
+            )));
+        }
+        let mut hash = *target_hash;
+        for log2_size in self.log2_target_size..self.log2_root_size {
+            let sibling = self.sibling_hash(log2_size);
+            hash = if (self.target_address >> log2_size) & 1 == 0 {
+                hash_children(&hash, sibling)
+            } else {
+                hash_children(sibling, &hash)
+            };
+        }
+        Ok(hash)
+    }
+
+    /// Check that the sibling hashes link the target hash to the root hash
+    pub fn verify(&self) -> bool {
+        match self.root_hash_for(&self.target_hash) {
+            Ok(root_hash) => root_hash == self.root_hash,
+            Err(_) => false,
+        }
+    }
+}
+
+#[doc = " Sparse Merkle tree over the whole 64-bit machine address space"]
+#[doc = " \\details"]
+#[doc = " Only words that differ from zero are stored. Everything else hashes as"]
+#[doc = " pristine memory, so the tree is cheap to keep for a handful of words."]
+#[derive(Debug, Clone)]
+pub struct MerkleTree {
+    words: BTreeMap<u64, [u8; 1 << LOG2_WORD_SIZE]>,
+    pristine_hashes: Vec<Hash>,
+}
+
+impl Default for MerkleTree {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MerkleTree {
+    pub fn new() -> Self {
+        let mut pristine_hashes = vec![Hash::default(); LOG2_ROOT_SIZE + 1];
+        pristine_hashes[LOG2_WORD_SIZE] = keccak256(&[0u8; 1 << LOG2_WORD_SIZE]);
+        for log2_size in LOG2_WORD_SIZE + 1..=LOG2_ROOT_SIZE {
+            let child = pristine_hashes[log2_size - 1];
+            pristine_hashes[log2_size] = hash_children(&child, &child);
+        }
+        MerkleTree {
+            words: BTreeMap::new(),
+            pristine_hashes,
+        }
+    }
+
+    /// Hash of a pristine (all zeros) node spanning 2^log2_size bytes
+    pub fn pristine_hash(&self, log2_size: usize) -> Hash {
+        self.pristine_hashes[log2_size]
+    }
+
+    /// Read `length` bytes starting at `address`
+    pub fn read(&self, address: u64, length: usize) -> Vec<u8> {
+        (0..length as u64)
+            .map(|offset| {
+                let byte_address = address.wrapping_add(offset);
+                let word_address = byte_address & !WORD_MASK;
+                self.words
+                    .get(&word_address)
+                    .map_or(0, |word| word[(byte_address & WORD_MASK) as usize])
+            })
+            .collect()
+    }
+
+    /// Read the word at an aligned `address`
+    pub fn read_word(&self, address: u64) -> u64 {
+        self.words
+            .get(&(address & !WORD_MASK))
+            .map_or(0, |word| u64::from_le_bytes(*word))
+    }
+
+    /// Write `data` starting at `address`
+    pub fn write(&mut self, address: u64, data: &[u8]) {
+        for (offset, byte) in data.iter().enumerate() {
+            let byte_address = address.wrapping_add(offset as u64);
+            let word_address = byte_address & !WORD_MASK;
+            let mut word = self.words.get(&word_address).copied().unwrap_or_default();
+            word[(byte_address & WORD_MASK) as usize] = *byte;
+            if word == [0u8; 1 << LOG2_WORD_SIZE] {
+                self.words.remove(&word_address);
+            } else {
+                self.words.insert(word_address, word);
+            }
+        }
+    }
+
+    /// Write the word at an aligned `address`
+    pub fn write_word(&mut self, address: u64, value: u64) {
+        self.write(address & !WORD_MASK, &value.to_le_bytes());
+    }
+
+    /// Reset `length` bytes starting at an aligned `address` to zero
+    pub fn clear(&mut self, address: u64, length: u64) {
+        if length == 0 {
+            return;
+        }
+        let last = address.saturating_add(length - 1);
+        let cleared: Vec<u64> = self.words.range(address..=last).map(|(a, _)| *a).collect();
+        for word_address in cleared {
+            self.words.remove(&word_address);
+        }
+    }
+
+    /// Hash of the whole tree
+    pub fn root_hash(&self) -> Hash {
+        self.node_hash(0, LOG2_ROOT_SIZE)
+    }
+
+    /// Hash of the node spanning 2^log2_size bytes starting at an aligned `address`
+    pub fn node_hash(&self, address: u64, log2_size: usize) -> Hash {
+        let last = if log2_size >= 64 {
+            u64::MAX
+        } else {
+            address | ((1u64 << log2_size) - 1)
+        };
+        if self.words.range(address..=last).next().is_none() {
+            return self.pristine_hash(log2_size);
+        }
+        if log2_size == LOG2_WORD_SIZE {
+            return keccak256(&self.words[&address]);
+        }
+        let half = 1u64 << (log2_size - 1);
+        hash_children(
+            &self.node_hash(address, log2_size - 1),
+            &self.node_hash(address | half, log2_size - 1),
+        )
+    }
+
+    /// Proof that the node spanning 2^log2_size bytes at `address` belongs to the tree
+    pub fn proof(&self, address: u64, log2_size: usize) -> Result<MerkleTreeProof, Error> {
+        if !(LOG2_WORD_SIZE..=LOG2_ROOT_SIZE).contains(&log2_size)
+            || (log2_size < 64 && address & ((1u64 << log2_size) - 1) != 0)
+        {
+            return Err(Error::InvalidArgument(format!(
+                "no node of size 2^{} at address {:#x}",
+                log2_size, address
+            )));
+        }
+        let sibling_hashes = (log2_size..LOG2_ROOT_SIZE)
+            .rev()
+            .map(|level| {
+                let sibling_address = (address ^ (1u64 << level)) & !((1u64 << level) - 1);
+                self.node_hash(sibling_address, level)
+            })
+            .collect();
+        Ok(MerkleTreeProof {
+            target_address: address,
+            log2_target_size: log2_size,
+            target_hash: self.node_hash(address, log2_size),
+            log2_root_size: LOG2_ROOT_SIZE,
+            root_hash: self.root_hash(),
+            sibling_hashes,
+        })
+    }
+}
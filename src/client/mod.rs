@@ -15,12 +15,55 @@
 
 use base64::engine::general_purpose::STANDARD;
 use base64::Engine;
-pub use jsonrpsee::core::Error;
-
-use crate::interfaces::{self, Base64Hash};
-
-mod conversions;
+use derive_builder::Builder;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::convert::TryFrom;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime};
+
+use jsonrpsee::core::client::ClientT;
+
+use crate::interfaces;
+pub use crate::interfaces::{InterpreterBreakReason, UarchInterpreterBreakReason};
+
+mod access_log;
+mod client_config;
+pub use client_config::{ClientConfig, RetryPolicy};
+mod config;
+pub(crate) mod conversions;
 use conversions::*;
+mod csr;
+pub use csr::Csr;
+mod error;
+pub use error::Error;
+mod fork;
+use fork::ForkGuard;
+pub use fork::ForkParent;
+mod hash;
+pub use hash::Hash;
+mod machine;
+pub use machine::{CartesiMachine, ForkableCartesiMachine};
+pub mod merkle;
+pub(crate) mod rollup;
+use rollup::{
+    check_fits, decode_address, decode_word, decode_yield, encode_bytes, RollupRanges, WORD,
+};
+pub use rollup::{AdvanceResult, AdvanceStatus, InputMetadata, Voucher};
+mod run_options;
+pub use run_options::{CancellationToken, ProgressCallback, RunOptions, RunProgress};
+mod serde_hex;
+mod snapshot;
+pub use snapshot::Snapshot;
+use snapshot::SnapshotTracker;
+mod server_pool;
+pub use server_pool::{ServerLease, ServerPool, ServerPoolConfig};
+mod server_process;
+pub use server_process::{ServerProcess, ServerProcessConfig};
+mod transport;
+pub use transport::{HttpConnection, Reconnect, Transport, WebSocketConnection};
+pub mod uarch_step;
 
 #[doc = " Server version"]
 #[derive(Debug, Clone, Default)]
@@ -64,86 +107,205 @@ impl From<&interfaces::SemanticVersion> for SemanticVersion {
     }
 }
 
+impl InterpreterBreakReason {
+    /// Name of the break reason as reported by the server
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            InterpreterBreakReason::Failed => "failed",
+            InterpreterBreakReason::Halted => "halted",
+            InterpreterBreakReason::YieldedManually => "yielded_manually",
+            InterpreterBreakReason::YieldedAutomatically => "yielded_automatically",
+            InterpreterBreakReason::ReachedTargetMcycle => "reached_target_mcycle",
+            InterpreterBreakReason::Unknown => "unknown",
+        }
+    }
+
+    /// True if the machine yielded, either manually or automatically
+    pub fn is_yield(&self) -> bool {
+        matches!(
+            self,
+            InterpreterBreakReason::YieldedManually | InterpreterBreakReason::YieldedAutomatically
+        )
+    }
+}
+
+impl std::fmt::Display for InterpreterBreakReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl UarchInterpreterBreakReason {
+    /// Name of the break reason as reported by the server
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            UarchInterpreterBreakReason::ReachedTargetCycle => "reached_target_cycle",
+            UarchInterpreterBreakReason::UarchHalted => "uarch_halted",
+            UarchInterpreterBreakReason::Unknown => "unknown",
+        }
+    }
+}
+
+impl std::fmt::Display for UarchInterpreterBreakReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Machine identification CSRs the emulator requires, as (mvendorid, marchid, mimpid)
+pub(crate) const MVENDORID: u64 = 0x6361727465736920;
+pub(crate) const MARCHID: u64 = 0xf;
+pub(crate) const MIMPID: u64 = 0x1;
+
 #[doc = " Cartesi machine processor state configuration"]
-#[derive(Debug, Copy, Clone, Default)]
+#[derive(Debug, Copy, Clone, Builder, Serialize, Deserialize)]
+#[builder(setter(into, strip_option), default)]
+#[serde(default, deny_unknown_fields)]
 pub struct ProcessorConfig {
     #[doc = "< Value of general-purpose registers"]
+    #[serde(with = "serde_hex::registers")]
     pub x: [u64; 32usize],
     #[doc = "< Value of f registers"]
+    #[serde(with = "serde_hex::registers")]
     pub f: [u64; 32usize],
     #[doc = "< Value of pc"]
+    #[serde(with = "serde_hex::word")]
     pub pc: u64,
     #[doc = "< Value of mvendorid CSR"]
+    #[serde(with = "serde_hex::word")]
     pub mvendorid: u64,
     #[doc = "< Value of marchid CSR"]
+    #[serde(with = "serde_hex::word")]
     pub marchid: u64,
     #[doc = "< Value of mimpid CSR"]
+    #[serde(with = "serde_hex::word")]
     pub mimpid: u64,
     #[doc = "< Value of mcycle CSR"]
+    #[serde(with = "serde_hex::word")]
     pub mcycle: u64,
     #[doc = "< Value of icycleinstret CSR"]
+    #[serde(with = "serde_hex::word")]
     pub icycleinstret: u64,
     #[doc = "< Value of mstatus CSR"]
+    #[serde(with = "serde_hex::word")]
     pub mstatus: u64,
     #[doc = "< Value of mtvec CSR"]
+    #[serde(with = "serde_hex::word")]
     pub mtvec: u64,
     #[doc = "< Value of mscratch CSR"]
+    #[serde(with = "serde_hex::word")]
     pub mscratch: u64,
     #[doc = "< Value of mepc CSR"]
+    #[serde(with = "serde_hex::word")]
     pub mepc: u64,
     #[doc = "< Value of mcause CSR"]
+    #[serde(with = "serde_hex::word")]
     pub mcause: u64,
     #[doc = "< Value of mtval CSR"]
+    #[serde(with = "serde_hex::word")]
     pub mtval: u64,
     #[doc = "< Value of misa CSR"]
+    #[serde(with = "serde_hex::word")]
     pub misa: u64,
     #[doc = "< Value of mie CSR"]
+    #[serde(with = "serde_hex::word")]
     pub mie: u64,
     #[doc = "< Value of mip CSR"]
+    #[serde(with = "serde_hex::word")]
     pub mip: u64,
     #[doc = "< Value of medeleg CSR"]
+    #[serde(with = "serde_hex::word")]
     pub medeleg: u64,
     #[doc = "< Value of mideleg CSR"]
+    #[serde(with = "serde_hex::word")]
     pub mideleg: u64,
     #[doc = "< Value of mcounteren CSR"]
+    #[serde(with = "serde_hex::word")]
     pub mcounteren: u64,
     #[doc = "< Value of stvec CSR"]
+    #[serde(with = "serde_hex::word")]
     pub stvec: u64,
     #[doc = "< Value of sscratch CSR"]
+    #[serde(with = "serde_hex::word")]
     pub sscratch: u64,
     #[doc = "< Value of sepc CSR"]
+    #[serde(with = "serde_hex::word")]
     pub sepc: u64,
     #[doc = "< Value of scause CSR"]
+    #[serde(with = "serde_hex::word")]
     pub scause: u64,
     #[doc = "< Value of stval CSR"]
+    #[serde(with = "serde_hex::word")]
     pub stval: u64,
     #[doc = "< Value of satp CSR"]
+    #[serde(with = "serde_hex::word")]
     pub satp: u64,
     #[doc = "< Value of scounteren CSR"]
+    #[serde(with = "serde_hex::word")]
     pub scounteren: u64,
     #[doc = "< Value of ilrsc CSR"]
+    #[serde(with = "serde_hex::word")]
     pub ilrsc: u64,
     #[doc = "< Value of iflags CSR"]
+    #[serde(with = "serde_hex::word")]
     pub iflags: u64,
     #[doc = "< Value of senvcfg CSR"]
+    #[serde(with = "serde_hex::word")]
     pub senvcfg: u64,
     #[doc = "< Value of menvcfg CSR"]
+    #[serde(with = "serde_hex::word")]
     pub menvcfg: u64,
     #[doc = "< Value of fcsr CSR"]
+    #[serde(with = "serde_hex::word")]
     pub fcsr: u64,
 }
 
 impl ProcessorConfig {
+    /// Zeroed state with the identification CSRs the emulator requires
     pub fn new() -> Self {
         ProcessorConfig {
-            mvendorid: 0x6361727465736920,
-            marchid: 0x7,
-            mimpid: 0x1,
-            ..Default::default()
+            x: [0; 32],
+            f: [0; 32],
+            pc: 0,
+            mvendorid: MVENDORID,
+            marchid: MARCHID,
+            mimpid: MIMPID,
+            mcycle: 0,
+            icycleinstret: 0,
+            mstatus: 0,
+            mtvec: 0,
+            mscratch: 0,
+            mepc: 0,
+            mcause: 0,
+            mtval: 0,
+            misa: 0,
+            mie: 0,
+            mip: 0,
+            medeleg: 0,
+            mideleg: 0,
+            mcounteren: 0,
+            stvec: 0,
+            sscratch: 0,
+            sepc: 0,
+            scause: 0,
+            stval: 0,
+            satp: 0,
+            scounteren: 0,
+            ilrsc: 0,
+            iflags: 0,
+            senvcfg: 0,
+            menvcfg: 0,
+            fcsr: 0,
         }
     }
 }
 
+impl Default for ProcessorConfig {
+    fn default() -> Self {
+        ProcessorConfig::new()
+    }
+}
+
 impl From<&interfaces::ProcessorConfig> for ProcessorConfig {
     fn from(config: &interfaces::ProcessorConfig) -> Self {
         ProcessorConfig {
@@ -184,7 +346,9 @@ impl From<&interfaces::ProcessorConfig> for ProcessorConfig {
 }
 
 #[doc = " Cartesi machine RAM state configuration"]
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, Builder, Serialize, Deserialize)]
+#[builder(setter(into, strip_option), default)]
+#[serde(default, deny_unknown_fields)]
 pub struct RamConfig {
     #[doc = "< RAM length"]
     pub length: u64,
@@ -208,7 +372,9 @@ impl From<&interfaces::RAMConfig> for RamConfig {
 }
 
 #[doc = " Cartesi machine Tlb"]
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, Builder, Serialize, Deserialize)]
+#[builder(setter(into, strip_option), default)]
+#[serde(default, deny_unknown_fields)]
 pub struct TlbConfig {
     #[doc = "< Tlb image file name"]
     pub image_filename: String,
@@ -229,11 +395,18 @@ impl From<&interfaces::TLBConfig> for TlbConfig {
 }
 
 #[doc = " Cartesi machine Uarch"]
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, Builder, Serialize, Deserialize)]
+#[builder(setter(into, strip_option), default)]
+#[serde(default, deny_unknown_fields)]
 pub struct UarchConfig {
     #[doc = "< Uarch processor"]
+    #[serde(
+        with = "serde_hex::uarch_processor",
+        skip_serializing_if = "Option::is_none"
+    )]
     pub processor: ::core::option::Option<interfaces::UarchProcessorConfig>,
     #[doc = "< Uarch ram"]
+    #[serde(with = "serde_hex::uarch_ram", skip_serializing_if = "Option::is_none")]
     pub ram: ::core::option::Option<interfaces::UarchRAMConfig>,
 }
 
@@ -253,7 +426,9 @@ impl From<&interfaces::UarchConfig> for UarchConfig {
 }
 
 #[doc = " Cartesi machine ROM state configuration"]
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, Builder, Serialize, Deserialize)]
+#[builder(setter(into, strip_option), default)]
+#[serde(default, deny_unknown_fields)]
 pub struct RomConfig {
     #[doc = "< Bootargs to pass to kernel"]
     pub bootargs: String,
@@ -277,7 +452,9 @@ impl From<&interfaces::ROMConfig> for RomConfig {
 }
 
 #[doc = " Cartesi machine memory range state configuration"]
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, Builder, Serialize, Deserialize)]
+#[builder(setter(into, strip_option), default)]
+#[serde(default, deny_unknown_fields)]
 pub struct MemoryRangeConfig {
     #[doc = "< Memory range start position"]
     pub start: u64,
@@ -307,12 +484,19 @@ impl From<&interfaces::MemoryRangeConfig> for MemoryRangeConfig {
 }
 
 #[doc = " Cartesi machine rollup configuration"]
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, Builder, Serialize, Deserialize)]
+#[builder(setter(into, strip_option), default)]
+#[serde(default, deny_unknown_fields)]
 pub struct RollupConfig {
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub rx_buffer: Option<MemoryRangeConfig>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub tx_buffer: Option<MemoryRangeConfig>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub input_metadata: Option<MemoryRangeConfig>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub voucher_hashes: Option<MemoryRangeConfig>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub notice_hashes: Option<MemoryRangeConfig>,
 }
 
@@ -350,13 +534,19 @@ impl From<&interfaces::RollupConfig> for RollupConfig {
 }
 
 #[doc = " Machine state configuration"]
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, Builder, Serialize, Deserialize)]
+#[builder(setter(into), default, build_fn(private, name = "build_unchecked"))]
+#[serde(default, deny_unknown_fields)]
 pub struct MachineConfig {
+    #[builder(default = "ProcessorConfig::new()")]
     pub processor: ProcessorConfig,
     pub ram: RamConfig,
     pub rom: RomConfig,
+    #[builder(setter(each(name = "flash_drive")))]
     pub flash_drives: Vec<MemoryRangeConfig>,
+    #[serde(with = "serde_hex::clint")]
     pub clint: interfaces::CLINTConfig,
+    #[serde(with = "serde_hex::htif")]
     pub htif: interfaces::HTIFConfig,
     pub rollup: RollupConfig,
     pub tlb: TlbConfig,
@@ -411,7 +601,8 @@ impl From<&interfaces::MachineConfig> for MachineConfig {
 }
 
 #[doc = " Concurrency runtime configuration"]
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
 pub struct ConcurrencyConfig {
     pub update_merkle_tree: u64,
 }
@@ -425,7 +616,8 @@ impl From<&interfaces::ConcurrencyConfig> for ConcurrencyConfig {
 }
 
 #[doc = " Machine runtime configuration"]
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
 pub struct MachineRuntimeConfig {
     pub concurrency: ConcurrencyConfig,
     pub htif: interfaces::HTIFRuntimeConfig,
@@ -455,38 +647,45 @@ impl From<&interfaces::MachineRuntimeConfig> for MachineRuntimeConfig {
 #[doc = " \\details"]
 #[doc = " This structure holds a proof that the node spanning a log2_target_size"]
 #[doc = " at a given address in the tree has a certain hash."]
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct MerkleTreeProof {
     pub target_address: u64,
     pub log2_target_size: usize,
-    pub target_hash: Base64Hash,
+    pub target_hash: Hash,
     pub log2_root_size: usize,
-    pub root_hash: Base64Hash,
-    pub sibling_hashes: Vec<Base64Hash>,
+    pub root_hash: Hash,
+    pub sibling_hashes: Vec<Hash>,
 }
 
-impl From<&interfaces::Proof> for MerkleTreeProof {
-    fn from(proof: &interfaces::Proof) -> Self {
-        MerkleTreeProof {
+impl TryFrom<&interfaces::Proof> for MerkleTreeProof {
+    type Error = Error;
+
+    fn try_from(proof: &interfaces::Proof) -> Result<Self, Self::Error> {
+        Ok(MerkleTreeProof {
             target_address: proof.target_address,
             log2_target_size: proof.log_2_target_size as usize,
             log2_root_size: proof.log_2_root_size as usize,
-            target_hash: proof.target_hash.clone(),
-            root_hash: proof.root_hash.clone(),
-            sibling_hashes: proof.sibling_hashes.clone(),
-        }
+            target_hash: Hash::from_wire(&proof.target_hash)?,
+            root_hash: Hash::from_wire(&proof.root_hash)?,
+            sibling_hashes: proof
+                .sibling_hashes
+                .iter()
+                .map(|hash| Hash::from_wire(hash))
+                .collect::<Result<_, _>>()?,
+        })
     }
 }
 
 #[doc = " Type of state access"]
-#[derive(Debug, Clone, Copy, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum AccessType {
     Read = 0,
     Write,
 }
 
 #[doc = " Access log type"]
-#[derive(Debug, Clone, Copy, Default)]
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
 pub struct AccessLogType {
     pub proofs: bool,
     pub annotations: bool,
@@ -502,7 +701,7 @@ impl From<&interfaces::AccessLogType> for AccessLogType {
 }
 
 #[doc = " Records an access to the machine state"]
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Access {
     #[doc = "< Type of access"]
     pub r#type: AccessType,
@@ -511,56 +710,50 @@ pub struct Access {
     #[doc = "< Log2 of size of access"]
     pub log2_size: i32,
     #[doc = "< Data before access"]
+    #[serde(with = "serde_hex::bytes")]
     pub read_data: Vec<u8>,
     #[doc = "< Data after access (if writing)"]
+    #[serde(with = "serde_hex::bytes")]
     pub written_data: Vec<u8>,
     #[doc = "< Proof of data before access"]
     pub proof: MerkleTreeProof,
 }
 
-impl From<&interfaces::Access> for Access {
-    fn from(access: &interfaces::Access) -> Self {
-        let mut read_data = access.read.clone();
-        let mut written_data: String = match access.written.clone() {
-            Some(written_data) => written_data.clone(),
-            None => Default::default(),
-        };
+impl TryFrom<&interfaces::Access> for Access {
+    type Error = Error;
 
-        if written_data.ends_with('\n') {
-            written_data.pop();
-        }
-
-        if read_data.ends_with('\n') {
-            read_data.pop();
-        }
-
-        Access {
+    fn try_from(access: &interfaces::Access) -> Result<Self, Self::Error> {
+        Ok(Access {
             r#type: match access.r#type.to_string().as_str() {
                 "\"read\"" => AccessType::Read,
                 "\"write\"" => AccessType::Write,
                 _ => AccessType::Read,
             },
-            read_data: STANDARD.decode(read_data).unwrap(),
-            written_data: STANDARD.decode(written_data).unwrap(),
+            read_data: decode_wire_base64(&access.read)?,
+            written_data: match &access.written {
+                Some(written_data) => decode_wire_base64(written_data)?,
+                None => Default::default(),
+            },
             proof: match &access.proof {
-                Some(x) => MerkleTreeProof::from(x),
+                Some(x) => MerkleTreeProof::try_from(x)?,
                 None => Default::default(),
             },
             address: access.address,
             log2_size: access.log_2_size as i32,
-        }
+        })
     }
 }
 
 #[doc = " Bracket type"]
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum BracketType {
     Begin = 0,
     End,
 }
 
 #[doc = " Bracket note"]
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct BracketNote {
     #[doc = "< Bracket type"]
     pub r#type: BracketType,
@@ -584,7 +777,7 @@ impl std::convert::From<&interfaces::Bracket> for BracketNote {
 }
 
 #[doc = " Access log"]
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AccessLog {
     pub accesses: Vec<Access>,
     pub brackets: Vec<BracketNote>,
@@ -592,15 +785,21 @@ pub struct AccessLog {
     pub log_type: AccessLogType,
 }
 
-impl From<&interfaces::AccessLog> for AccessLog {
-    fn from(log: &interfaces::AccessLog) -> Self {
+impl TryFrom<&interfaces::AccessLog> for AccessLog {
+    type Error = Error;
+
+    fn try_from(log: &interfaces::AccessLog) -> Result<Self, Self::Error> {
         let log_type = AccessLogType {
             proofs: log.log_type.has_proofs,
             annotations: log.log_type.has_annotations,
         };
-        AccessLog {
+        Ok(AccessLog {
             log_type,
-            accesses: log.accesses.iter().map(Access::from).collect(),
+            accesses: log
+                .accesses
+                .iter()
+                .map(Access::try_from)
+                .collect::<Result<_, _>>()?,
             brackets: log
                 .brackets
                 .clone()
@@ -609,47 +808,299 @@ impl From<&interfaces::AccessLog> for AccessLog {
                 .map(|e| BracketNote::from(e))
                 .collect(),
             notes: log.notes.clone().unwrap_or_default(),
+        })
+    }
+}
+
+#[doc = " Processor privilege level"]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PrivilegeLevel {
+    User = 0,
+    Supervisor = 1,
+    Machine = 3,
+}
+
+impl TryFrom<u64> for PrivilegeLevel {
+    type Error = Error;
+
+    fn try_from(value: u64) -> Result<Self, Self::Error> {
+        match value {
+            0 => Ok(PrivilegeLevel::User),
+            1 => Ok(PrivilegeLevel::Supervisor),
+            3 => Ok(PrivilegeLevel::Machine),
+            _ => Err(Error::Decode(format!("invalid privilege level {}", value))),
         }
     }
 }
 
+const IFLAGS_H_SHIFT: u64 = 0;
+const IFLAGS_Y_SHIFT: u64 = 1;
+const IFLAGS_X_SHIFT: u64 = 2;
+const IFLAGS_PRV_SHIFT: u64 = 3;
+const IFLAGS_PRV_MASK: u64 = 3;
+
+#[doc = " Decoded contents of the iflags CSR"]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IFlags {
+    #[doc = "< Current privilege level"]
+    pub prv: PrivilegeLevel,
+    #[doc = "< Machine yielded automatically"]
+    pub x: bool,
+    #[doc = "< Machine yielded manually"]
+    pub y: bool,
+    #[doc = "< Machine halted"]
+    pub h: bool,
+}
+
+impl TryFrom<u64> for IFlags {
+    type Error = Error;
+
+    fn try_from(iflags: u64) -> Result<Self, Self::Error> {
+        Ok(IFlags {
+            prv: PrivilegeLevel::try_from((iflags >> IFLAGS_PRV_SHIFT) & IFLAGS_PRV_MASK)?,
+            x: (iflags >> IFLAGS_X_SHIFT) & 1 != 0,
+            y: (iflags >> IFLAGS_Y_SHIFT) & 1 != 0,
+            h: (iflags >> IFLAGS_H_SHIFT) & 1 != 0,
+        })
+    }
+}
+
+impl From<IFlags> for u64 {
+    fn from(iflags: IFlags) -> Self {
+        ((iflags.prv as u64) << IFLAGS_PRV_SHIFT)
+            | ((iflags.x as u64) << IFLAGS_X_SHIFT)
+            | ((iflags.y as u64) << IFLAGS_Y_SHIFT)
+            | ((iflags.h as u64) << IFLAGS_H_SHIFT)
+    }
+}
+
+#[doc = " Number of general-purpose registers"]
+pub const X_REG_COUNT: u64 = 32;
+#[doc = " Number of floating-point registers"]
+pub const F_REG_COUNT: u64 = 32;
+#[doc = " Number of microarchitecture general-purpose registers"]
+pub const UARCH_X_REG_COUNT: u64 = 32;
+
+fn check_register_index(kind: &str, index: u64, count: u64) -> Result<(), Error> {
+    if index < count {
+        Ok(())
+    } else {
+        Err(Error::InvalidArgument(format!(
+            "{} register index {} out of range 0..{}",
+            kind, index, count
+        )))
+    }
+}
+
+/// Wait between attempts of JsonRpcCartesiMachineClient::connect_with_retry
+const CONNECT_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
 #[doc = "Client for Cartesi emulator machine server"]
+#[doc = " \\details"]
+#[doc = " Generic over the JSON-RPC transport. The default Transport speaks HTTP"]
+#[doc = " or WebSocket; any other ClientT can be supplied with from_transport."]
 #[derive(Clone)]
 
-pub struct JsonRpcCartesiMachineClient {
+pub struct JsonRpcCartesiMachineClient<T = Transport> {
     server_address: String,
-    client: interfaces::RemoteCartesiMachine<jsonrpsee::http_client::HttpClient>,
+    client: interfaces::RemoteCartesiMachine<T>,
+    parent: Option<ForkParent>,
+    fork_guard: Option<Arc<ForkGuard>>,
+    config: ClientConfig,
+    deadline: Option<Instant>,
+    snapshots: Arc<SnapshotTracker>,
+    rollup_ranges: Arc<Mutex<Option<RollupRanges>>>,
 }
 
 impl JsonRpcCartesiMachineClient {
-    /// Create new client instance. Connect to the server as part of client instantiation
+    /// Create new client instance. Connect to the server as part of client instantiation.
+    /// ws:// and wss:// addresses are reached over a persistent WebSocket connection, others over HTTP
     pub async fn new<'a>(server_address: String) -> Result<Self, Error> {
-        let transport = jsonrpsee::http_client::HttpClientBuilder::default()
-            .request_timeout(core::time::Duration::MAX)
-            .build(&server_address)?;
+        let transport = Transport::connect(&server_address).await?;
+        Self::from_transport(server_address, transport).await
+    }
+
+    /// Connect to a server that may still be starting up, polling it until it answers
+    /// or `timeout` passes, in which case Error::Timeout is returned
+    pub async fn connect_with_retry(
+        server_address: String,
+        timeout: Duration,
+    ) -> Result<Self, Error> {
+        Self::connect_while(server_address, timeout, "connect_with_retry", || Ok(())).await
+    }
+
+    /// Poll like [`JsonRpcCartesiMachineClient::connect_with_retry`], timing out as `method`.
+    /// `check` runs before every attempt and after connecting, and its error ends the wait
+    pub(crate) async fn connect_while<F>(
+        server_address: String,
+        timeout: Duration,
+        method: &'static str,
+        mut check: F,
+    ) -> Result<Self, Error>
+    where
+        F: FnMut() -> Result<(), Error>,
+    {
+        let deadline = Instant::now() + timeout;
+        loop {
+            check()?;
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            match tokio::time::timeout(remaining, Self::new(server_address.clone())).await {
+                Ok(Ok(client)) => {
+                    check()?;
+                    return Ok(client);
+                }
+                // Not listening yet
+                Ok(Err(Error::Transport(_))) | Err(_) => {}
+                Ok(Err(err)) => return Err(err),
+            }
+            if Instant::now() + CONNECT_POLL_INTERVAL >= deadline {
+                return Err(Error::Timeout { method, timeout });
+            }
+            tokio::time::sleep(CONNECT_POLL_INTERVAL).await;
+        }
+    }
 
+    /// Create new client instance that issues one HTTP request per call
+    pub async fn new_http(server_address: String) -> Result<Self, Error> {
+        let transport = Transport::http(&server_address)?;
+        Self::from_transport(server_address, transport).await
+    }
+
+    /// Create new client instance over a persistent WebSocket connection
+    pub async fn new_ws(server_address: String) -> Result<Self, Error> {
+        let transport = Transport::web_socket(&server_address).await?;
+        Self::from_transport(server_address, transport).await
+    }
+}
+
+impl<T> JsonRpcCartesiMachineClient<T>
+where
+    T: ClientT + Send + Sync + 'static,
+{
+    /// Create new client instance over any JSON-RPC transport, such as middleware
+    /// or an in-memory transport. `server_address` is only used for reporting
+    pub async fn from_transport(server_address: String, transport: T) -> Result<Self, Error> {
         let remote_machine = interfaces::RemoteCartesiMachine::new(transport);
-        remote_machine.GetVersion().await?;
-        // somehow CheckConnection won't work
-        // remote_machine.CheckConnection().await
+        remote_machine.CheckConnection().await?;
 
         Ok(JsonRpcCartesiMachineClient {
             server_address,
             client: remote_machine,
+            parent: None,
+            fork_guard: None,
+            config: ClientConfig::default(),
+            deadline: None,
+            snapshots: Default::default(),
+            rollup_ranges: Default::default(),
         })
     }
 
+    /// Replace the client configuration
+    pub fn set_config(&mut self, config: ClientConfig) {
+        self.config = config;
+    }
+
+    /// Client configuration in effect
+    pub fn config(&self) -> &ClientConfig {
+        &self.config
+    }
+
+    /// Copy of the client whose calls fail with Error::Timeout once `deadline` passes,
+    /// on top of any configured timeout
+    pub fn with_deadline(&self, deadline: Instant) -> Self
+    where
+        T: Clone,
+    {
+        let mut client = self.clone();
+        client.deadline = Some(deadline);
+        client
+    }
+
+    /// Copy of the client whose calls must complete within `timeout` from now
+    pub fn with_timeout(&self, timeout: Duration) -> Self
+    where
+        T: Clone,
+    {
+        self.with_deadline(Instant::now() + timeout)
+    }
+
+    /// Issue an idempotent server call, retrying transport failures as allowed by the
+    /// retry policy. Never use it for calls that change the machine
+    async fn call_idempotent<R, F, Fut>(&self, method: &'static str, request: F) -> Result<R, Error>
+    where
+        F: Fn() -> Fut,
+        Fut: Future<Output = Result<R, jsonrpsee::core::Error>>,
+    {
+        let mut attempt = 1;
+        loop {
+            let result = self.call(method, request()).await;
+            let backoff = match (&result, &self.config.retry) {
+                (Err(Error::Transport(_)), Some(policy)) => policy.backoff(attempt),
+                _ => None,
+            };
+            let backoff = match backoff {
+                Some(backoff) if !self.expires_within(backoff) => backoff,
+                _ => return result,
+            };
+            tokio::time::sleep(backoff).await;
+            attempt += 1;
+        }
+    }
+
+    /// Whether the client deadline passes within `duration` from now
+    fn expires_within(&self, duration: Duration) -> bool {
+        match self.deadline {
+            Some(deadline) => Instant::now() + duration >= deadline,
+            None => false,
+        }
+    }
+
+    /// Await a server call, giving up once its timeout or the client deadline expires
+    async fn call<R>(
+        &self,
+        method: &'static str,
+        request: impl Future<Output = Result<R, jsonrpsee::core::Error>>,
+    ) -> Result<R, Error> {
+        debug_assert!(
+            client_config::METHODS.contains(&method),
+            "{} is missing from the client methods",
+            method
+        );
+        let remaining = self
+            .deadline
+            .map(|deadline| deadline.saturating_duration_since(Instant::now()));
+        let timeout = match (self.config.timeout(method), remaining) {
+            (Some(timeout), Some(remaining)) => Some(timeout.min(remaining)),
+            (timeout, remaining) => timeout.or(remaining),
+        };
+        match timeout {
+            // Calls are not issued at all once the deadline has passed
+            Some(timeout) if timeout.is_zero() => Err(Error::Timeout { method, timeout }),
+            Some(timeout) => match tokio::time::timeout(timeout, request).await {
+                Ok(result) => Ok(result?),
+                Err(_) => Err(Error::Timeout { method, timeout }),
+            },
+            None => Ok(request.await?),
+        }
+    }
+
     /// Create new client instance. Connect to the server as part of client instantiation
     pub fn get_address(&self) -> &String {
         &self.server_address
     }
 
+    /// Check that the server is up and answering calls
+    pub async fn check_connection(&self) -> Result<(), Error> {
+        self.call_idempotent("check_connection", || self.client.CheckConnection())
+            .await
+    }
+
     /// Get Cartesi machine server version
     pub async fn get_version(&self) -> Result<SemanticVersion, Error> {
-        self.client
-            .GetVersion()
-            .await
-            .map(|res| SemanticVersion::from(&res))
+        let version = self
+            .call_idempotent("get_version", || self.client.GetVersion())
+            .await?;
+        Ok(SemanticVersion::from(&version))
     }
 
     // /// Create machine instance on remote Cartesi machine server
@@ -658,11 +1109,14 @@ impl JsonRpcCartesiMachineClient {
         machine_config: &MachineConfig,
         machine_runtime_config: &MachineRuntimeConfig,
     ) -> Result<bool, Error> {
+        self.rollup_ranges.lock().unwrap().take();
         let runtime = interfaces::MachineRuntimeConfig::from(machine_runtime_config);
         let machine_oneof = interfaces::MachineConfig::from(machine_config);
-        self.client
-            .MachineMachineConfig(machine_oneof, runtime)
-            .await
+        self.call(
+            "create_machine",
+            self.client.MachineMachineConfig(machine_oneof, runtime),
+        )
+        .await
     }
 
     /// Create machine from storage on remote Cartesi machine server
@@ -671,40 +1125,333 @@ impl JsonRpcCartesiMachineClient {
         directory: &str,
         machine_runtime_config: &MachineRuntimeConfig,
     ) -> Result<bool, Error> {
+        self.rollup_ranges.lock().unwrap().take();
         let runtime = interfaces::MachineRuntimeConfig::from(machine_runtime_config);
-        self.client
-            .MachineMachineDirectory(directory.to_string(), runtime)
-            .await
+        self.call(
+            "load_machine",
+            self.client
+                .MachineMachineDirectory(directory.to_string(), runtime),
+        )
+        .await
     }
 
     /// Run remote machine to maximum limit cycle
-    pub async fn run(&self, limit: u64) -> Result<serde_json::Value, Error> {
-        self.client.MachineRun(limit).await
+    pub async fn run(&self, limit: u64) -> Result<InterpreterBreakReason, Error> {
+        self.call("run", self.client.MachineRun(limit)).await
+    }
+
+    /// Run the machine until mcycle reaches `target`, in chunks of `options.chunk` mcycles.
+    /// Progress is reported after every chunk and cancellation checked before each one.
+    /// Stops early when the machine halts or yields, returning the reason
+    pub async fn run_until(
+        &self,
+        target: u64,
+        options: &RunOptions,
+    ) -> Result<InterpreterBreakReason, Error> {
+        if options.chunk == 0 {
+            return Err(Error::InvalidArgument(
+                "run chunk must not be zero".to_string(),
+            ));
+        }
+        let started = Instant::now();
+        let mut mcycle = self.read_csr(Csr::Mcycle).await?;
+        loop {
+            if options.is_cancelled() {
+                return Err(Error::Cancelled);
+            }
+            let limit = target.min(mcycle.saturating_add(options.chunk));
+            let reason = self.run(limit).await?;
+            mcycle = self.read_csr(Csr::Mcycle).await?;
+            options.report(&RunProgress {
+                mcycle,
+                target,
+                elapsed: started.elapsed(),
+            });
+            if reason != InterpreterBreakReason::ReachedTargetMcycle || limit == target {
+                return Ok(reason);
+            }
+        }
+    }
+
+    /// Advance a rollup machine by one input, until the dapp accepts or rejects it
+    pub async fn advance(
+        &self,
+        payload: &[u8],
+        metadata: &InputMetadata,
+    ) -> Result<AdvanceResult, Error> {
+        self.advance_with(payload, metadata, &RunOptions::default())
+            .await
+    }
+
+    /// Advance a rollup machine by one input, running in chunks as set by `options`.
+    /// The machine must be stopped at a manual yield, as it is once the dapp first asks
+    /// for an input and after every advance that did not halt, and the metadata and
+    /// payload must fit in their ranges; otherwise Error::Rollup is returned. Writes the input to the rollup ranges, resets iflags_Y and runs until
+    /// the next manual yield, collecting the outputs of the automatic yields along the way
+    pub async fn advance_with(
+        &self,
+        payload: &[u8],
+        metadata: &InputMetadata,
+        options: &RunOptions,
+    ) -> Result<AdvanceResult, Error> {
+        let ranges = self.rollup_ranges().await?;
+        let metadata = metadata.encode();
+        check_fits(
+            "input metadata",
+            &metadata,
+            "input_metadata",
+            &ranges.input_metadata,
+        )?;
+        let input = encode_bytes(payload);
+        check_fits(
+            "encoded input payload",
+            &input,
+            "rx_buffer",
+            &ranges.rx_buffer,
+        )?;
+        if !self.read_iflags_y().await? {
+            return Err(Error::Rollup(
+                "machine is not stopped at a manual yield".to_string(),
+            ));
+        }
+        self.write_memory(ranges.input_metadata.start, STANDARD.encode(metadata))
+            .await?;
+        self.write_memory(ranges.rx_buffer.start, STANDARD.encode(input))
+            .await?;
+        self.reset_iflags_y().await?;
+
+        let mut result = AdvanceResult::new(AdvanceStatus::Accepted);
+        loop {
+            let reason = self.run_until(u64::MAX, options).await?;
+            let command = match reason {
+                InterpreterBreakReason::YieldedAutomatically => rollup::HTIF_YIELD_AUTOMATIC,
+                InterpreterBreakReason::YieldedManually => rollup::HTIF_YIELD_MANUAL,
+                InterpreterBreakReason::Halted => {
+                    result.status = AdvanceStatus::Halted;
+                    return Ok(result);
+                }
+                reason => {
+                    return Err(Error::Rollup(format!(
+                        "machine stopped with {} instead of yielding",
+                        reason
+                    )))
+                }
+            };
+            let tx_buffer = &ranges.tx_buffer;
+            match decode_yield(self.read_csr(Csr::HtifTohost).await?)? {
+                (rollup::HTIF_YIELD_AUTOMATIC, rollup::HTIF_YIELD_REASON_PROGRESS, _) => {}
+                (rollup::HTIF_YIELD_AUTOMATIC, rollup::HTIF_YIELD_REASON_TX_VOUCHER, _) => {
+                    let (head, payload) = self.read_tx_output(tx_buffer, 1).await?;
+                    result.vouchers.push(Voucher {
+                        destination: decode_address(&head, 0)?,
+                        payload,
+                    });
+                }
+                (rollup::HTIF_YIELD_AUTOMATIC, rollup::HTIF_YIELD_REASON_TX_NOTICE, _) => {
+                    result
+                        .notices
+                        .push(self.read_tx_output(tx_buffer, 0).await?.1);
+                }
+                (rollup::HTIF_YIELD_AUTOMATIC, rollup::HTIF_YIELD_REASON_TX_REPORT, _) => {
+                    result
+                        .reports
+                        .push(self.read_tx_output(tx_buffer, 0).await?.1);
+                }
+                (rollup::HTIF_YIELD_MANUAL, rollup::HTIF_YIELD_REASON_RX_ACCEPTED, _) => {
+                    return Ok(result)
+                }
+                (rollup::HTIF_YIELD_MANUAL, rollup::HTIF_YIELD_REASON_RX_REJECTED, _) => {
+                    result.status = AdvanceStatus::Rejected;
+                    return Ok(result);
+                }
+                (rollup::HTIF_YIELD_MANUAL, rollup::HTIF_YIELD_REASON_TX_EXCEPTION, _) => {
+                    let payload = self.read_tx_output(tx_buffer, 0).await?.1;
+                    result.status = AdvanceStatus::Exception(payload);
+                    return Ok(result);
+                }
+                (command, reason, _) => {
+                    return Err(Error::Rollup(format!(
+                        "unexpected yield with command {} and reason {}",
+                        command, reason
+                    )))
+                }
+            }
+            if command == rollup::HTIF_YIELD_AUTOMATIC {
+                self.reset_iflags_x().await?;
+            }
+        }
+    }
+
+    /// Rollup ranges of the machine, read from its initial config on first use and
+    /// cached until the machine is created, loaded or destroyed through this client
+    async fn rollup_ranges(&self) -> Result<RollupRanges, Error> {
+        if let Some(ranges) = self.rollup_ranges.lock().unwrap().clone() {
+            return Ok(ranges);
+        }
+        let ranges = RollupRanges::from_config(&self.get_initial_config().await?.rollup)?;
+        *self.rollup_ranges.lock().unwrap() = Some(ranges.clone());
+        Ok(ranges)
+    }
+
+    /// Output ABI-encoded in the tx buffer, as its head words up to the offset of its
+    /// bytes field at `offset_index`, and the contents of that field
+    async fn read_tx_output(
+        &self,
+        tx_buffer: &MemoryRangeConfig,
+        offset_index: usize,
+    ) -> Result<(Vec<u8>, Vec<u8>), Error> {
+        let word = WORD as u64;
+        let head = self
+            .read_memory(tx_buffer.start, (offset_index as u64 + 1) * word)
+            .await?;
+        let offset = decode_word(&head, offset_index)?;
+        let fits = |end: Option<u64>| matches!(end, Some(end) if end <= tx_buffer.length);
+        if !fits(offset.checked_add(word)) {
+            return Err(Error::Rollup(format!(
+                "output offset {} is outside tx_buffer",
+                offset
+            )));
+        }
+        let length = decode_word(&self.read_memory(tx_buffer.start + offset, word).await?, 0)?;
+        if !fits(
+            offset
+                .checked_add(word)
+                .and_then(|start| start.checked_add(length)),
+        ) {
+            return Err(Error::Rollup(format!(
+                "output of {} bytes overflows tx_buffer",
+                length
+            )));
+        }
+        if length == 0 {
+            return Ok((head, Vec::new()));
+        }
+        let payload = self
+            .read_memory(tx_buffer.start + offset + word, length)
+            .await?;
+        Ok((head, payload))
     }
 
     /// Run uarch remote machine to maximum limit cycle
-    pub async fn run_uarch(&self, limit: u64) -> Result<serde_json::Value, Error> {
-        self.client.MachineRunUarch(limit).await
+    pub async fn run_uarch(&self, limit: u64) -> Result<UarchInterpreterBreakReason, Error> {
+        self.call("run_uarch", self.client.MachineRunUarch(limit))
+            .await
     }
 
     /// Serialize entire remote machine state to directory on cartesi machine server host
     pub async fn store(&self, directory: &str) -> Result<bool, Error> {
-        self.client.MachineStore(directory.to_string()).await
+        self.call("store", self.client.MachineStore(directory.to_string()))
+            .await
     }
 
     /// Destroy remote machine instance
     pub async fn destroy(&self) -> Result<bool, Error> {
-        self.client.MachineDestroy().await
+        self.rollup_ranges.lock().unwrap().take();
+        self.call("destroy", self.client.MachineDestroy()).await
     }
 
     /// Fork remote machine
     pub async fn fork(&self) -> Result<String, Error> {
-        self.client.Fork().await
+        self.call("fork", self.client.Fork()).await
+    }
+
+    /// Fork remote machine and connect to the forked server.
+    /// The forked server is shut down once every clone of the returned client is dropped,
+    /// unless disabled with [`JsonRpcCartesiMachineClient::set_shutdown_on_drop`].
+    /// Dropping the last clone outside a Tokio runtime blocks until the shutdown completes,
+    /// for up to five seconds. Dropping it while its runtime shuts down may leave the
+    /// forked server running
+    pub async fn fork_client(&self) -> Result<Self, Error>
+    where
+        T: Reconnect,
+    {
+        let forked_at = SystemTime::now();
+        let address = self.fork().await?;
+        let transport = self.client.transport();
+        let address = format!("{}://{}", transport.scheme(), address);
+        let transport = transport.reconnect(&address).await?;
+        let mut child = Self::from_transport(address, transport).await?;
+        child.config = self.config.clone();
+        child.parent = Some(ForkParent {
+            address: self.server_address.clone(),
+            forked_at,
+        });
+        child.fork_guard = Some(Arc::new(ForkGuard::new(child.client.clone())));
+        Ok(child)
+    }
+
+    /// Server this client was forked from, if it was created by [`JsonRpcCartesiMachineClient::fork_client`]
+    pub fn parent(&self) -> Option<&ForkParent> {
+        self.parent.as_ref()
+    }
+
+    /// Choose whether a forked server is shut down when its last client is dropped.
+    /// Has no effect on clients that were not created by forking
+    pub fn set_shutdown_on_drop(&self, enabled: bool) {
+        if let Some(guard) = &self.fork_guard {
+            guard.set_shutdown_on_drop(enabled);
+        }
     }
 
     /// Shutdown the server
     pub async fn shutdown(&self) -> Result<bool, Error> {
-        self.client.Shutdown().await
+        let result = self.call("shutdown", self.client.Shutdown()).await?;
+        self.set_shutdown_on_drop(false);
+        Ok(result)
+    }
+
+    /// Save the machine state in a forked server, to return to with
+    /// [`JsonRpcCartesiMachineClient::rollback`] or discard with [`JsonRpcCartesiMachineClient::commit`]
+    pub async fn snapshot(&self) -> Result<Snapshot<T>, Error>
+    where
+        T: Reconnect,
+    {
+        let client = self.fork_client().await?;
+        Ok(Snapshot::new(client, self.snapshots.clone()))
+    }
+
+    /// Continue from the state saved in `snapshot`, then shut down the server whose
+    /// state diverged from it. The client keeps its configuration and deadline.
+    /// A diverged server that is already gone is not an error. Any other failure to
+    /// shut it down is returned, after the client has switched to the snapshot, and
+    /// may leave that server running
+    pub async fn rollback(&mut self, snapshot: Snapshot<T>) -> Result<(), Error> {
+        self.check_snapshot(&snapshot)?;
+        let mut client = snapshot.into_client();
+        client.config = self.config.clone();
+        client.deadline = self.deadline;
+        client.snapshots = self.snapshots.clone();
+        let diverged = std::mem::replace(self, client);
+        match diverged.shutdown().await {
+            Ok(_) => Ok(()),
+            // The diverged server may already be gone, which is what rollback recovers from
+            Err(Error::Transport(_)) if diverged.check_connection().await.is_err() => Ok(()),
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Keep the current state and discard `snapshot`, shutting its server down
+    pub async fn commit(&self, snapshot: Snapshot<T>) -> Result<(), Error> {
+        self.check_snapshot(&snapshot)?;
+        snapshot.into_client().shutdown().await?;
+        Ok(())
+    }
+
+    /// Number of snapshots taken through this client, or the clients it rolled back
+    /// from, that were neither rolled back to, committed nor dropped
+    pub fn snapshot_count(&self) -> usize {
+        self.snapshots.live()
+    }
+
+    fn check_snapshot(&self, snapshot: &Snapshot<T>) -> Result<(), Error> {
+        if snapshot.is_tracked_by(&self.snapshots) {
+            Ok(())
+        } else {
+            Err(Error::InvalidArgument(format!(
+                "snapshot {} was not taken by this client",
+                snapshot.address()
+            )))
+        }
     }
 
     /// Runs the remote machine for one cycle logging all accesses to the state
@@ -717,52 +1464,74 @@ impl JsonRpcCartesiMachineClient {
             has_proofs: log_type.proofs,
             has_annotations: log_type.annotations,
         };
-        self.client
-            .MachineStepUarch(log_type, one_based)
-            .await
-            .map(|op| AccessLog::from(&op))
+        let log = self
+            .call("step", self.client.MachineStepUarch(log_type, one_based))
+            .await?;
+        AccessLog::try_from(&log)
     }
 
     /// Reads a chunk of data from the remote machine memory
     pub async fn read_memory(&self, address: u64, length: u64) -> Result<Vec<u8>, Error> {
-        let response = self.client.MachineReadMemory(address, length).await?;
-
-        let response = response.replace("\n", "");
-
-        Ok(STANDARD.decode(response).unwrap())
+        let response = self
+            .call_idempotent("read_memory", || {
+                self.client.MachineReadMemory(address, length)
+            })
+            .await?;
+        decode_wire_base64(&response)
     }
 
     /// Writes a chunk of data to the remote machine memory
     pub async fn write_memory(&self, address: u64, data: String) -> Result<bool, Error> {
-        self.client.MachineWriteMemory(address, data).await
+        self.call(
+            "write_memory",
+            self.client.MachineWriteMemory(address, data),
+        )
+        .await
+    }
+
+    /// Reads a chunk of data from the remote machine virtual memory
+    pub async fn read_virtual_memory(&self, address: u64, length: u64) -> Result<Vec<u8>, Error> {
+        let response = self
+            .call_idempotent("read_virtual_memory", || {
+                self.client.MachineReadVirtualMemory(address, length)
+            })
+            .await?;
+        decode_wire_base64(&response)
+    }
+
+    /// Writes a chunk of data to the remote machine virtual memory
+    pub async fn write_virtual_memory(&self, address: u64, data: &[u8]) -> Result<bool, Error> {
+        self.call(
+            "write_virtual_memory",
+            self.client
+                .MachineWriteVirtualMemory(address, STANDARD.encode(data)),
+        )
+        .await
     }
 
     /// Read the value of a word in the remote machine state
     pub async fn read_word(&self, address: u64) -> Result<u64, Error> {
-        self.client.MachineReadWord(address).await
+        self.call_idempotent("read_word", || self.client.MachineReadWord(address))
+            .await
     }
 
     /// Obtains the root hash of the Merkle tree for the remote machine
-    pub async fn get_root_hash(&self) -> Result<[u8; 32], Error> {
-        self.client.MachineGetRootHash().await.map(|op| {
-            let mut hash = op;
-            if hash.ends_with('\n') {
-                hash.pop();
-            }
-            let mut root_hash = [0u8; 32];
-            STANDARD
-                .decode_slice_unchecked(hash.clone(), &mut root_hash as &mut [u8])
-                .unwrap();
-            root_hash
-        })
+    pub async fn get_root_hash(&self) -> Result<Hash, Error> {
+        Hash::from_wire(
+            &self
+                .call_idempotent("get_root_hash", || self.client.MachineGetRootHash())
+                .await?,
+        )
     }
 
     /// Obtains the proof for a node in the Merkle tree from remote machine
     pub async fn get_proof(&self, address: u64, log2_size: u64) -> Result<MerkleTreeProof, Error> {
-        self.client
-            .MachineGetProof(address, log2_size)
-            .await
-            .map(|op| MerkleTreeProof::from(&op))
+        let proof = self
+            .call_idempotent("get_proof", || {
+                self.client.MachineGetProof(address, log2_size)
+            })
+            .await?;
+        MerkleTreeProof::try_from(&proof)
     }
 
     /// Replaces a flash drive on a remote machine
@@ -770,94 +1539,226 @@ impl JsonRpcCartesiMachineClient {
         &self,
         config: interfaces::MemoryRangeConfig,
     ) -> Result<bool, Error> {
-        self.client.MachineReplaceMemoryRange(config).await
+        self.call(
+            "replace_memory_range",
+            self.client.MachineReplaceMemoryRange(config),
+        )
+        .await
     }
 
     /// Gets the address of a general-purpose register
     pub async fn get_x_address(&self, index: u64) -> Result<u64, Error> {
-        self.client.MachineGetXAddress(index).await
+        check_register_index("x", index, X_REG_COUNT)?;
+        self.call_idempotent("get_x_address", || self.client.MachineGetXAddress(index))
+            .await
     }
 
     /// Reads the value of a general-purpose register from the remote machine
     pub async fn read_x(&self, index: u64) -> Result<u64, Error> {
-        self.client.MachineReadX(index).await
+        check_register_index("x", index, X_REG_COUNT)?;
+        self.call_idempotent("read_x", || self.client.MachineReadX(index))
+            .await
+    }
+
+    /// Gets the address of a floating-point register
+    pub async fn get_f_address(&self, index: u64) -> Result<u64, Error> {
+        check_register_index("f", index, F_REG_COUNT)?;
+        self.call_idempotent("get_f_address", || self.client.MachineGetFAddress(index))
+            .await
+    }
+
+    /// Reads the value of a floating-point register from the remote machine
+    pub async fn read_f(&self, index: u64) -> Result<u64, Error> {
+        check_register_index("f", index, F_REG_COUNT)?;
+        self.call_idempotent("read_f", || self.client.MachineReadF(index))
+            .await
+    }
+
+    /// Writes the value of a floating-point register for the remote machine
+    pub async fn write_f(&self, index: u64, value: u64) -> Result<bool, Error> {
+        check_register_index("f", index, F_REG_COUNT)?;
+        self.call("write_f", self.client.MachineWriteF(index, value))
+            .await
+    }
+
+    /// Gets the address of a microarchitecture general-purpose register
+    pub async fn get_uarch_x_address(&self, index: u64) -> Result<u64, Error> {
+        check_register_index("uarch x", index, UARCH_X_REG_COUNT)?;
+        self.call_idempotent("get_uarch_x_address", || {
+            self.client.MachineGetUarchXAddress(index)
+        })
+        .await
+    }
+
+    /// Reads the value of a microarchitecture general-purpose register from the remote machine
+    pub async fn read_uarch_x(&self, index: u64) -> Result<u64, Error> {
+        check_register_index("uarch x", index, UARCH_X_REG_COUNT)?;
+        self.call_idempotent("read_uarch_x", || self.client.MachineReadUarchX(index))
+            .await
+    }
+
+    /// Writes the value of a microarchitecture general-purpose register for the remote machine
+    pub async fn write_uarch_x(&self, index: u64, value: u64) -> Result<bool, Error> {
+        check_register_index("uarch x", index, UARCH_X_REG_COUNT)?;
+        self.call(
+            "write_uarch_x",
+            self.client.MachineWriteUarchX(index, value),
+        )
+        .await
     }
 
     pub async fn read_iflags_h(&self) -> Result<bool, Error> {
-        self.client.MachineReadIflagsH().await
+        self.call_idempotent("read_iflags_h", || self.client.MachineReadIflagsH())
+            .await
     }
 
     pub async fn read_iflags_x(&self) -> Result<bool, Error> {
-        self.client.MachineReadIflagsX().await
+        self.call_idempotent("read_iflags_x", || self.client.MachineReadIflagsX())
+            .await
     }
 
     pub async fn read_iflags_y(&self) -> Result<bool, Error> {
-        self.client.MachineReadIflagsY().await
+        self.call_idempotent("read_iflags_y", || self.client.MachineReadIflagsY())
+            .await
     }
 
     pub async fn read_uarch_halt_flag(&self) -> Result<bool, Error> {
-        self.client.MachineReadUarchHaltFlag().await
+        self.call_idempotent("read_uarch_halt_flag", || {
+            self.client.MachineReadUarchHaltFlag()
+        })
+        .await
+    }
+
+    /// Reads the current privilege level of the remote machine
+    pub async fn read_iflags_prv(&self) -> Result<PrivilegeLevel, Error> {
+        PrivilegeLevel::try_from(
+            self.call_idempotent("read_iflags_prv", || self.client.MachineReadIflagsPRV())
+                .await?,
+        )
+    }
+
+    /// Reads and decodes the whole iflags CSR of the remote machine
+    pub async fn read_iflags(&self) -> Result<IFlags, Error> {
+        IFlags::try_from(self.read_csr(Csr::Iflags).await?)
+    }
+
+    /// Sets the iflags_H flag on the remote machine
+    pub async fn set_iflags_h(&self) -> Result<bool, Error> {
+        self.call("set_iflags_h", self.client.MachineSetIflagsH())
+            .await
+    }
+
+    /// Sets the iflags_X flag on the remote machine
+    pub async fn set_iflags_x(&self) -> Result<bool, Error> {
+        self.call("set_iflags_x", self.client.MachineSetIflagsX())
+            .await
+    }
+
+    /// Resets the value of the iflags_X flag on the remote machine
+    pub async fn reset_iflags_x(&self) -> Result<bool, Error> {
+        self.call("reset_iflags_x", self.client.MachineResetIflagsX())
+            .await
+    }
+
+    /// Sets the iflags_Y flag on the remote machine
+    pub async fn set_iflags_y(&self) -> Result<bool, Error> {
+        self.call("set_iflags_y", self.client.MachineSetIflagsY())
+            .await
+    }
+
+    /// Sets the uarch halt flag on the remote machine
+    pub async fn set_uarch_halt_flag(&self) -> Result<bool, Error> {
+        self.call("set_uarch_halt_flag", self.client.MachineSetUarchHaltFlag())
+            .await
     }
 
     /// Writes the value of a general-purpose register for the remote machine
     pub async fn write_x(&self, index: u64, value: u64) -> Result<bool, Error> {
-        self.client.MachineWriteX(index, value).await
+        check_register_index("x", index, X_REG_COUNT)?;
+        self.call("write_x", self.client.MachineWriteX(index, value))
+            .await
     }
 
     /// Resets the value of the iflags_Y flag on the remote machine
     pub async fn reset_iflags_y(&self) -> Result<bool, Error> {
-        self.client.MachineResetIflagsY().await
+        self.call("reset_iflags_y", self.client.MachineResetIflagsY())
+            .await
     }
 
     /// Resets uarch state on the remote machine
     pub async fn reset_uarch_state(&self) -> Result<bool, Error> {
-        self.client.MachineResetUarchState().await
+        self.call("reset_uarch_state", self.client.MachineResetUarchState())
+            .await
     }
 
     /// Gets the address of any CSR
-    pub async fn get_csr_address(&self, csr: String) -> Result<u64, Error> {
-        self.client.MachineGetCsrAddress(csr).await
+    pub async fn get_csr_address(&self, csr: Csr) -> Result<u64, Error> {
+        self.call_idempotent("get_csr_address", || {
+            self.client.MachineGetCsrAddress(csr.to_string())
+        })
+        .await
     }
 
     /// Read the value of any CSR from remote machine
-    pub async fn read_csr(&self, csr: String) -> Result<u64, Error> {
-        self.client.MachineReadCsr(csr).await
+    pub async fn read_csr(&self, csr: Csr) -> Result<u64, Error> {
+        self.call_idempotent("read_csr", || self.client.MachineReadCsr(csr.to_string()))
+            .await
     }
 
     /// Writes the value of any CSR on remote machine
-    pub async fn write_csr(&self, csr: String, value: u64) -> Result<bool, Error> {
-        self.client.MachineWriteCsr(csr, value).await
+    pub async fn write_csr(&self, csr: Csr, value: u64) -> Result<bool, Error> {
+        self.call(
+            "write_csr",
+            self.client.MachineWriteCsr(csr.to_string(), value),
+        )
+        .await
+    }
+
+    /// Reads the value of every CSR from remote machine
+    pub async fn read_all_csrs(&self) -> Result<BTreeMap<Csr, u64>, Error> {
+        // The trait default reads them one by one; this client does not override it
+        CartesiMachine::read_all_csrs(self).await
     }
 
     /// Returns copy of initialization config of the remote machine
     pub async fn get_initial_config(&self) -> Result<MachineConfig, Error> {
-        self.client
-            .MachineGetInitialConfig()
-            .await
-            .map(|op| MachineConfig::from(&op))
+        let config = self
+            .call_idempotent("get_initial_config", || {
+                self.client.MachineGetInitialConfig()
+            })
+            .await?;
+        Ok(MachineConfig::from(&config))
     }
 
     /// Verifies integrity of Merkle tree on the remote machine
     pub async fn verify_merkle_tree(&self) -> Result<bool, Error> {
-        self.client.MachineVerifyMerkleTree().await
+        self.call_idempotent("verify_merkle_tree", || {
+            self.client.MachineVerifyMerkleTree()
+        })
+        .await
     }
 
     /// Verify if dirty page maps are consistent on the remote machine
     pub async fn verify_dirty_page_maps(&self) -> Result<bool, Error> {
-        self.client.MachineVerifyDirtyPageMaps().await
+        self.call_idempotent("verify_dirty_page_maps", || {
+            self.client.MachineVerifyDirtyPageMaps()
+        })
+        .await
     }
 
     /// Dump all memory ranges to files in current working directory on the server (for debugging purporses)
     pub async fn dump_pmas(&self) -> Result<bool, Error> {
-        self.client.MachineDumpPmas().await
+        self.call("dump_pmas", self.client.MachineDumpPmas()).await
     }
 
     /// Returns copy of default system config from remote Cartesi machine server
     pub async fn get_default_config(&self) -> Result<MachineConfig, Error> {
-        self.client
-            .MachineGetDefaultConfig()
-            .await
-            .map(|op| MachineConfig::from(&op))
+        let config = self
+            .call_idempotent("get_default_config", || {
+                self.client.MachineGetDefaultConfig()
+            })
+            .await?;
+        Ok(MachineConfig::from(&config))
     }
 
     /// Checks the internal consistency of an access log
@@ -870,40 +1771,36 @@ impl JsonRpcCartesiMachineClient {
         let log = interfaces::AccessLog::from(log);
         let runtime = interfaces::MachineRuntimeConfig::from(runtime);
 
-        self.client
-            .MachineVerifyAccessLog(log, runtime, one_based)
-            .await
+        self.call_idempotent("verify_access_log", || {
+            self.client
+                .MachineVerifyAccessLog(log.clone(), runtime.clone(), one_based)
+        })
+        .await
     }
 
     /// Checks the validity of a state transition
     pub async fn verify_state_transition(
         &self,
-        root_hash_before: Vec<u8>,
+        root_hash_before: &Hash,
         log: &AccessLog,
-        root_hash_after: Vec<u8>,
+        root_hash_after: &Hash,
         one_based: bool,
         runtime: &MachineRuntimeConfig,
     ) -> Result<bool, Error> {
-        let mut root_hash_before = STANDARD.encode(&root_hash_before.clone());
-        let mut root_hash_after = STANDARD.encode(&root_hash_after.clone());
-
-        if root_hash_before.ends_with("=") {
-            root_hash_before.push('\n');
-        }
-        if root_hash_after.ends_with("=") {
-            root_hash_after.push('\n');
-        }
+        let root_hash_before = root_hash_before.to_wire();
+        let root_hash_after = root_hash_after.to_wire();
         let log = interfaces::AccessLog::from(log);
         let runtime = interfaces::MachineRuntimeConfig::from(runtime);
 
-        self.client
-            .MachineVerifyStateTransition(
-                root_hash_before,
-                log,
-                root_hash_after,
-                runtime,
+        self.call_idempotent("verify_state_transition", || {
+            self.client.MachineVerifyStateTransition(
+                root_hash_before.clone(),
+                log.clone(),
+                root_hash_after.clone(),
+                runtime.clone(),
                 one_based,
             )
-            .await
+        })
+        .await
     }
 }
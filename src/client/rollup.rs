@@ -0,0 +1,223 @@
+// Copyright (C) 2021 Cartesi Pte. Ltd.
+//
+// Licensed under the Apache License, Version 2.0 (the "License"); you may not use
+// this file except in compliance with the License. You may obtain a copy of the
+// License at http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software distributed
+// under the License is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR
+// CONDITIONS OF ANY KIND, either express or implied. See the License for the
+// specific language governing permissions and limitations under the License.
+
+//! Rollup inputs and outputs, and their encoding in the rollup memory ranges
+//!
+//! Inputs are written ABI-encoded to `input_metadata` and `rx_buffer`. The
+//! machine yields through HTIF once per output it writes to `tx_buffer`, and
+//! once more to accept or reject the input.
+
+use crate::client::{Error, MemoryRangeConfig, RollupConfig};
+
+/// Size of an ABI word
+pub(crate) const WORD: usize = 32;
+
+/// HTIF device of yields, in the top byte of tohost
+pub(crate) const HTIF_DEVICE_YIELD: u64 = 2;
+/// HTIF yield commands
+pub(crate) const HTIF_YIELD_AUTOMATIC: u64 = 0;
+pub(crate) const HTIF_YIELD_MANUAL: u64 = 1;
+
+/// Reasons of rollup yields
+pub(crate) const HTIF_YIELD_REASON_PROGRESS: u64 = 0;
+pub(crate) const HTIF_YIELD_REASON_RX_ACCEPTED: u64 = 1;
+pub(crate) const HTIF_YIELD_REASON_RX_REJECTED: u64 = 2;
+pub(crate) const HTIF_YIELD_REASON_TX_VOUCHER: u64 = 3;
+pub(crate) const HTIF_YIELD_REASON_TX_NOTICE: u64 = 4;
+pub(crate) const HTIF_YIELD_REASON_TX_REPORT: u64 = 5;
+pub(crate) const HTIF_YIELD_REASON_TX_EXCEPTION: u64 = 6;
+
+#[doc = " Metadata of a rollup input, as seen by the dapp"]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct InputMetadata {
+    #[doc = "< Address of the account that sent the input"]
+    pub msg_sender: [u8; 20],
+    #[doc = "< Block in which the input was added"]
+    pub block_number: u64,
+    #[doc = "< Time of that block, in seconds since the epoch"]
+    pub timestamp: u64,
+    #[doc = "< Epoch the input belongs to"]
+    pub epoch_index: u64,
+    #[doc = "< Index of the input within its epoch"]
+    pub input_index: u64,
+}
+
+impl InputMetadata {
+    /// ABI encoding, as (address, uint256, uint256, uint256, uint256)
+    pub fn encode(&self) -> Vec<u8> {
+        let mut encoded = vec![0; 5 * WORD];
+        encoded[WORD - 20..WORD].copy_from_slice(&self.msg_sender);
+        let fields = [
+            self.block_number,
+            self.timestamp,
+            self.epoch_index,
+            self.input_index,
+        ];
+        for (index, field) in fields.iter().enumerate() {
+            let end = (index + 2) * WORD;
+            encoded[end - 8..end].copy_from_slice(&field.to_be_bytes());
+        }
+        encoded
+    }
+}
+
+#[doc = " Voucher emitted by a rollup input"]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Voucher {
+    #[doc = "< Contract the voucher is to be executed on"]
+    pub destination: [u8; 20],
+    #[doc = "< Call data of the execution"]
+    pub payload: Vec<u8>,
+}
+
+#[doc = " How a rollup input was completed"]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AdvanceStatus {
+    #[doc = "< Dapp accepted the input"]
+    Accepted,
+    #[doc = "< Dapp rejected the input"]
+    Rejected,
+    #[doc = "< Dapp failed with an exception, with its payload"]
+    Exception(Vec<u8>),
+    #[doc = "< Machine halted before completing the input"]
+    Halted,
+}
+
+#[doc = " Result of advancing a rollup machine by one input"]
+#[doc = " \\details"]
+#[doc = " Outputs are listed in the order the dapp emitted them. They are returned"]
+#[doc = " whatever the status, although only those of accepted inputs take effect."]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AdvanceResult {
+    #[doc = "< How the input was completed"]
+    pub status: AdvanceStatus,
+    #[doc = "< Vouchers emitted"]
+    pub vouchers: Vec<Voucher>,
+    #[doc = "< Notices emitted"]
+    pub notices: Vec<Vec<u8>>,
+    #[doc = "< Reports emitted"]
+    pub reports: Vec<Vec<u8>>,
+}
+
+impl AdvanceResult {
+    pub(crate) fn new(status: AdvanceStatus) -> Self {
+        AdvanceResult {
+            status,
+            vouchers: Vec::new(),
+            notices: Vec::new(),
+            reports: Vec::new(),
+        }
+    }
+}
+
+/// Rollup memory ranges used to advance the machine
+#[derive(Clone)]
+pub(crate) struct RollupRanges {
+    pub(crate) rx_buffer: MemoryRangeConfig,
+    pub(crate) tx_buffer: MemoryRangeConfig,
+    pub(crate) input_metadata: MemoryRangeConfig,
+}
+
+impl RollupRanges {
+    pub(crate) fn from_config(config: &RollupConfig) -> Result<Self, Error> {
+        let range = |range: &Option<MemoryRangeConfig>, name: &str| {
+            range
+                .clone()
+                .ok_or_else(|| format!("rollup {} range is missing", name))
+        };
+        match (
+            range(&config.rx_buffer, "rx_buffer"),
+            range(&config.tx_buffer, "tx_buffer"),
+            range(&config.input_metadata, "input_metadata"),
+        ) {
+            (Ok(rx_buffer), Ok(tx_buffer), Ok(input_metadata)) => Ok(RollupRanges {
+                rx_buffer,
+                tx_buffer,
+                input_metadata,
+            }),
+            (rx_buffer, tx_buffer, input_metadata) => Err(Error::InvalidConfig(
+                vec![rx_buffer.err(), tx_buffer.err(), input_metadata.err()]
+                    .into_iter()
+                    .flatten()
+                    .collect(),
+            )),
+        }
+    }
+}
+
+/// Error::Rollup naming both sizes unless `data`, described as `what`, fits in the range `name`
+pub(crate) fn check_fits(
+    what: &str,
+    data: &[u8],
+    name: &str,
+    range: &MemoryRangeConfig,
+) -> Result<(), Error> {
+    if data.len() as u64 > range.length {
+        return Err(Error::Rollup(format!(
+            "{} of {} bytes does not fit in {} of {} bytes",
+            what,
+            data.len(),
+            name,
+            range.length
+        )));
+    }
+    Ok(())
+}
+
+/// ABI encoding of `payload` as bytes, with its offset and length words
+pub(crate) fn encode_bytes(payload: &[u8]) -> Vec<u8> {
+    let padded = payload.len() + (WORD - payload.len() % WORD) % WORD;
+    let mut encoded = vec![0; 2 * WORD + padded];
+    encoded[WORD - 8..WORD].copy_from_slice(&(WORD as u64).to_be_bytes());
+    encoded[2 * WORD - 8..2 * WORD].copy_from_slice(&(payload.len() as u64).to_be_bytes());
+    encoded[2 * WORD..2 * WORD + payload.len()].copy_from_slice(payload);
+    encoded
+}
+
+/// Yield command, reason and data held in tohost
+pub(crate) fn decode_yield(tohost: u64) -> Result<(u64, u64, u64), Error> {
+    if tohost >> 56 != HTIF_DEVICE_YIELD {
+        return Err(Error::Rollup(format!(
+            "tohost {:#x} does not hold a yield",
+            tohost
+        )));
+    }
+    Ok((
+        (tohost >> 48) & 0xff,
+        (tohost >> 32) & 0xffff,
+        tohost & 0xffff_ffff,
+    ))
+}
+
+/// ABI word at `index` of `data` as an integer
+pub(crate) fn decode_word(data: &[u8], index: usize) -> Result<u64, Error> {
+    let word = data
+        .get(index * WORD..(index + 1) * WORD)
+        .ok_or_else(|| Error::Rollup("output is truncated".to_string()))?;
+    if word[..WORD - 8].iter().any(|byte| *byte != 0) {
+        return Err(Error::Rollup(
+            "output field does not fit in 64 bits".to_string(),
+        ));
+    }
+    let mut bytes = [0; 8];
+    bytes.copy_from_slice(&word[WORD - 8..]);
+    Ok(u64::from_be_bytes(bytes))
+}
+
+/// ABI word at `index` of `data` as an address
+pub(crate) fn decode_address(data: &[u8], index: usize) -> Result<[u8; 20], Error> {
+    let word = data
+        .get(index * WORD..(index + 1) * WORD)
+        .ok_or_else(|| Error::Rollup("output is truncated".to_string()))?;
+    let mut address = [0; 20];
+    address.copy_from_slice(&word[WORD - 20..]);
+    Ok(address)
+}
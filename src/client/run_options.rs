@@ -0,0 +1,120 @@
+// Copyright (C) 2021 Cartesi Pte. Ltd.
+//
+// Licensed under the Apache License, Version 2.0 (the "License"); you may not use
+// this file except in compliance with the License. You may obtain a copy of the
+// License at http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software distributed
+// under the License is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR
+// CONDITIONS OF ANY KIND, either express or implied. See the License for the
+// specific language governing permissions and limitations under the License.
+
+//! Options of runs split into chunks, with progress reporting and cancellation
+
+use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Callback receiving the progress of a run after every chunk
+pub type ProgressCallback = Arc<dyn Fn(&RunProgress) + Send + Sync>;
+
+#[doc = " Progress of a run, reported after every chunk"]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RunProgress {
+    #[doc = "< Machine mcycle at the end of the chunk"]
+    pub mcycle: u64,
+    #[doc = "< Mcycle the run stops at"]
+    pub target: u64,
+    #[doc = "< Wall time since the run started"]
+    pub elapsed: Duration,
+}
+
+#[doc = " Token that cancels the runs it is passed to"]
+#[doc = " \\details"]
+#[doc = " Clones share the same state. Cancellation is noticed between chunks, so"]
+#[doc = " the chunk running when cancel is called completes first."]
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken {
+    cancelled: Arc<AtomicBool>,
+}
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Cancel every run using this token
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::SeqCst);
+    }
+
+    /// Whether cancel was called on this token or a clone of it
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::SeqCst)
+    }
+}
+
+#[doc = " Options of JsonRpcCartesiMachineClient::run_until"]
+#[derive(Clone)]
+pub struct RunOptions {
+    #[doc = "< Mcycles advanced by each run call"]
+    pub chunk: u64,
+    #[doc = "< Called after every chunk, None reports nothing"]
+    pub progress: Option<ProgressCallback>,
+    #[doc = "< Checked before every chunk, None never cancels"]
+    pub cancel: Option<CancellationToken>,
+}
+
+impl Default for RunOptions {
+    fn default() -> Self {
+        RunOptions {
+            chunk: 1 << 24,
+            progress: None,
+            cancel: None,
+        }
+    }
+}
+
+impl RunOptions {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Report progress to `callback` after every chunk
+    pub fn set_progress(
+        &mut self,
+        callback: impl Fn(&RunProgress) + Send + Sync + 'static,
+    ) -> &mut Self {
+        self.progress = Some(Arc::new(callback));
+        self
+    }
+
+    /// Stop the run once `token` is cancelled
+    pub fn set_cancel(&mut self, token: CancellationToken) -> &mut Self {
+        self.cancel = Some(token);
+        self
+    }
+
+    pub(crate) fn is_cancelled(&self) -> bool {
+        self.cancel
+            .as_ref()
+            .is_some_and(CancellationToken::is_cancelled)
+    }
+
+    pub(crate) fn report(&self, progress: &RunProgress) {
+        if let Some(callback) = &self.progress {
+            callback(progress);
+        }
+    }
+}
+
+impl fmt::Debug for RunOptions {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RunOptions")
+            .field("chunk", &self.chunk)
+            .field("progress", &self.progress.as_ref().map(|_| "callback"))
+            .field("cancel", &self.cancel)
+            .finish()
+    }
+}
@@ -0,0 +1,338 @@
+// Copyright (C) 2021 Cartesi Pte. Ltd.
+//
+// Licensed under the Apache License, Version 2.0 (the "License"); you may not use
+// this file except in compliance with the License. You may obtain a copy of the
+// License at http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software distributed
+// under the License is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR
+// CONDITIONS OF ANY KIND, either express or implied. See the License for the
+// specific language governing permissions and limitations under the License.
+
+//! Hex representations for serde, used with `#[serde(with = "...")]`
+//!
+//! Register values routinely exceed the range of TOML integers, so they are
+//! written as 0x-prefixed hex strings. Plain integers are still accepted when
+//! reading. The interfaces types embedded in configs are mirrored here too, so
+//! that config files reject unknown keys like the client types do.
+
+use std::convert::TryFrom;
+use std::fmt;
+
+use serde::de::{self, Visitor};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::interfaces;
+
+/// Word written as a 0x-prefixed hex string
+struct HexWord(u64);
+
+impl Serialize for HexWord {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&format!("{:#x}", self.0))
+    }
+}
+
+impl<'de> Deserialize<'de> for HexWord {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct HexWordVisitor;
+
+        impl<'de> Visitor<'de> for HexWordVisitor {
+            type Value = HexWord;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("an unsigned integer or a 0x-prefixed hex string")
+            }
+
+            fn visit_u64<E: de::Error>(self, value: u64) -> Result<HexWord, E> {
+                Ok(HexWord(value))
+            }
+
+            fn visit_i64<E: de::Error>(self, value: i64) -> Result<HexWord, E> {
+                u64::try_from(value)
+                    .map(HexWord)
+                    .map_err(|_| E::invalid_value(de::Unexpected::Signed(value), &self))
+            }
+
+            fn visit_str<E: de::Error>(self, value: &str) -> Result<HexWord, E> {
+                let parsed = match value.strip_prefix("0x") {
+                    Some(hex) => u64::from_str_radix(hex, 16),
+                    None => value.parse(),
+                };
+                parsed
+                    .map(HexWord)
+                    .map_err(|_| E::invalid_value(de::Unexpected::Str(value), &self))
+            }
+        }
+
+        deserializer.deserialize_any(HexWordVisitor)
+    }
+}
+
+/// Single word
+pub(crate) mod word {
+    use super::*;
+
+    pub(crate) fn serialize<S: Serializer>(value: &u64, serializer: S) -> Result<S::Ok, S::Error> {
+        HexWord(*value).serialize(serializer)
+    }
+
+    pub(crate) fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<u64, D::Error> {
+        Ok(HexWord::deserialize(deserializer)?.0)
+    }
+}
+
+/// Optional word
+pub(crate) mod optional_word {
+    use super::*;
+
+    pub(crate) fn serialize<S: Serializer>(
+        value: &Option<u64>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        value.map(HexWord).serialize(serializer)
+    }
+
+    pub(crate) fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<u64>, D::Error> {
+        Ok(Option::<HexWord>::deserialize(deserializer)?.map(|value| value.0))
+    }
+}
+
+/// Optional list of words
+pub(crate) mod optional_words {
+    use super::*;
+
+    pub(crate) fn serialize<S: Serializer>(
+        values: &Option<Vec<u64>>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        values
+            .as_ref()
+            .map(|values| {
+                values
+                    .iter()
+                    .map(|value| HexWord(*value))
+                    .collect::<Vec<_>>()
+            })
+            .serialize(serializer)
+    }
+
+    pub(crate) fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<Vec<u64>>, D::Error> {
+        Ok(Option::<Vec<HexWord>>::deserialize(deserializer)?
+            .map(|values| values.into_iter().map(|value| value.0).collect()))
+    }
+}
+
+/// Register file of 32 words
+pub(crate) mod registers {
+    use super::*;
+
+    pub(crate) fn serialize<S: Serializer>(
+        values: &[u64; 32],
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(values.iter().map(|value| HexWord(*value)))
+    }
+
+    pub(crate) fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<[u64; 32], D::Error> {
+        let values = Vec::<HexWord>::deserialize(deserializer)?;
+        let length = values.len();
+        let mut registers = [0u64; 32];
+        if length != registers.len() {
+            return Err(de::Error::invalid_length(length, &"32 registers"));
+        }
+        for (register, value) in registers.iter_mut().zip(values) {
+            *register = value.0;
+        }
+        Ok(registers)
+    }
+}
+
+/// Binary data written as a 0x-prefixed hex string
+pub(crate) mod bytes {
+    use super::*;
+
+    pub(crate) fn serialize<S: Serializer>(data: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+        let hex: String = data.iter().map(|byte| format!("{:02x}", byte)).collect();
+        serializer.serialize_str(&format!("0x{}", hex))
+    }
+
+    pub(crate) fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Vec<u8>, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        let hex = s.strip_prefix("0x").unwrap_or(&s);
+        if hex.len() % 2 != 0 || !hex.is_ascii() {
+            return Err(de::Error::custom(format!("invalid hex data {:?}", s)));
+        }
+        (0..hex.len())
+            .step_by(2)
+            .map(|i| {
+                u8::from_str_radix(&hex[i..i + 2], 16)
+                    .map_err(|_| de::Error::custom(format!("invalid hex data {:?}", s)))
+            })
+            .collect()
+    }
+}
+
+/// CLINT configuration, with mtimecmp as a word
+pub(crate) mod clint {
+    use super::*;
+
+    #[derive(Serialize, Deserialize, Default)]
+    #[serde(default, deny_unknown_fields)]
+    struct Clint {
+        #[serde(with = "optional_word", skip_serializing_if = "Option::is_none")]
+        mtimecmp: Option<u64>,
+    }
+
+    pub(crate) fn serialize<S: Serializer>(
+        config: &interfaces::CLINTConfig,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        Clint {
+            mtimecmp: config.mtimecmp,
+        }
+        .serialize(serializer)
+    }
+
+    pub(crate) fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<interfaces::CLINTConfig, D::Error> {
+        let clint = Clint::deserialize(deserializer)?;
+        Ok(interfaces::CLINTConfig {
+            mtimecmp: clint.mtimecmp,
+        })
+    }
+}
+
+/// HTIF configuration, with fromhost and tohost as words
+pub(crate) mod htif {
+    use super::*;
+
+    #[derive(Serialize, Deserialize, Default)]
+    #[serde(default, deny_unknown_fields)]
+    struct Htif {
+        #[serde(skip_serializing_if = "Option::is_none")]
+        console_getchar: Option<bool>,
+        #[serde(with = "optional_word", skip_serializing_if = "Option::is_none")]
+        fromhost: Option<u64>,
+        #[serde(with = "optional_word", skip_serializing_if = "Option::is_none")]
+        tohost: Option<u64>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        yield_automatic: Option<bool>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        yield_manual: Option<bool>,
+    }
+
+    pub(crate) fn serialize<S: Serializer>(
+        config: &interfaces::HTIFConfig,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        Htif {
+            console_getchar: config.console_getchar,
+            fromhost: config.fromhost,
+            tohost: config.tohost,
+            yield_automatic: config.yield_automatic,
+            yield_manual: config.yield_manual,
+        }
+        .serialize(serializer)
+    }
+
+    pub(crate) fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<interfaces::HTIFConfig, D::Error> {
+        let htif = Htif::deserialize(deserializer)?;
+        Ok(interfaces::HTIFConfig {
+            console_getchar: htif.console_getchar,
+            fromhost: htif.fromhost,
+            tohost: htif.tohost,
+            yield_automatic: htif.yield_automatic,
+            yield_manual: htif.yield_manual,
+        })
+    }
+}
+
+/// Optional uarch processor configuration, with registers, pc and cycle as words
+pub(crate) mod uarch_processor {
+    use super::*;
+
+    #[derive(Serialize, Deserialize, Default)]
+    #[serde(default, deny_unknown_fields)]
+    struct UarchProcessor {
+        #[serde(with = "optional_word", skip_serializing_if = "Option::is_none")]
+        cycle: Option<u64>,
+        #[serde(with = "optional_word", skip_serializing_if = "Option::is_none")]
+        pc: Option<u64>,
+        #[serde(with = "optional_words", skip_serializing_if = "Option::is_none")]
+        x: Option<Vec<u64>>,
+    }
+
+    pub(crate) fn serialize<S: Serializer>(
+        config: &Option<interfaces::UarchProcessorConfig>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        config
+            .as_ref()
+            .map(|config| UarchProcessor {
+                cycle: config.cycle,
+                pc: config.pc,
+                x: config.x.clone(),
+            })
+            .serialize(serializer)
+    }
+
+    pub(crate) fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<interfaces::UarchProcessorConfig>, D::Error> {
+        let processor = Option::<UarchProcessor>::deserialize(deserializer)?;
+        Ok(processor.map(|processor| interfaces::UarchProcessorConfig {
+            cycle: processor.cycle,
+            pc: processor.pc,
+            x: processor.x,
+        }))
+    }
+}
+
+/// Optional uarch RAM configuration
+pub(crate) mod uarch_ram {
+    use super::*;
+
+    #[derive(Serialize, Deserialize, Default)]
+    #[serde(default, deny_unknown_fields)]
+    struct UarchRam {
+        #[serde(skip_serializing_if = "Option::is_none")]
+        image_filename: Option<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        length: Option<u64>,
+    }
+
+    pub(crate) fn serialize<S: Serializer>(
+        config: &Option<interfaces::UarchRAMConfig>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        config
+            .as_ref()
+            .map(|config| UarchRam {
+                image_filename: config.image_filename.clone(),
+                length: config.length,
+            })
+            .serialize(serializer)
+    }
+
+    pub(crate) fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<interfaces::UarchRAMConfig>, D::Error> {
+        let ram = Option::<UarchRam>::deserialize(deserializer)?;
+        Ok(ram.map(|ram| interfaces::UarchRAMConfig {
+            image_filename: ram.image_filename,
+            length: ram.length,
+        }))
+    }
+}
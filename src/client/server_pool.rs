@@ -0,0 +1,203 @@
+// Copyright (C) 2021 Cartesi Pte. Ltd.
+//
+// Licensed under the Apache License, Version 2.0 (the "License"); you may not use
+// this file except in compliance with the License. You may obtain a copy of the
+// License at http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software distributed
+// under the License is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR
+// CONDITIONS OF ANY KIND, either express or implied. See the License for the
+// specific language governing permissions and limitations under the License.
+
+//! Bounded pool of local Cartesi machine server processes
+
+use std::ops::Deref;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+use crate::client::{Error, JsonRpcCartesiMachineClient, ServerProcess, ServerProcessConfig};
+
+/// Time a server has to answer the calls that check it is healthy
+const HEALTH_CHECK_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[doc = " Options for a server pool"]
+#[derive(Debug, Clone)]
+pub struct ServerPoolConfig {
+    #[doc = "< Maximum number of servers running at once"]
+    pub size: usize,
+    #[doc = "< How each server is launched"]
+    pub server: ServerProcessConfig,
+}
+
+impl Default for ServerPoolConfig {
+    fn default() -> Self {
+        ServerPoolConfig {
+            size: 4,
+            server: ServerProcessConfig::default(),
+        }
+    }
+}
+
+impl ServerPoolConfig {
+    pub fn new() -> Self {
+        Default::default()
+    }
+}
+
+#[doc = " Bounded pool of server processes, each leased to one user at a time"]
+#[doc = " \\details"]
+#[doc = " Servers are started on demand, up to the pool size. A returned server has"]
+#[doc = " its machine destroyed and is checked before it is leased again; servers"]
+#[doc = " that fail the check are killed and replaced by new ones."]
+#[derive(Clone)]
+pub struct ServerPool {
+    shared: Arc<PoolShared>,
+}
+
+struct PoolShared {
+    config: ServerPoolConfig,
+    idle: Mutex<Vec<ServerProcess>>,
+    permits: Arc<Semaphore>,
+}
+
+impl ServerPool {
+    /// Create an empty pool; fails with Error::InvalidArgument for a size of 0,
+    /// since no server could ever be leased from it
+    pub fn new(config: ServerPoolConfig) -> Result<Self, Error> {
+        if config.size == 0 {
+            return Err(Error::InvalidArgument(
+                "server pool size must be at least 1".to_string(),
+            ));
+        }
+        Ok(ServerPool {
+            shared: Arc::new(PoolShared {
+                permits: Arc::new(Semaphore::new(config.size)),
+                idle: Mutex::new(Vec::new()),
+                config,
+            }),
+        })
+    }
+
+    /// Lease a server, waiting while all of them are in use
+    pub async fn acquire(&self) -> Result<ServerLease, Error> {
+        let permit = self
+            .shared
+            .permits
+            .clone()
+            .acquire_owned()
+            .await
+            .expect("pool semaphore is never closed");
+        let server = loop {
+            let idle = self.shared.idle.lock().unwrap().pop();
+            match idle {
+                // Idle servers may have died since they were returned
+                Some(server) if is_healthy(&server).await => break server,
+                Some(_) => continue,
+                None => break ServerProcess::start_with(&self.shared.config.server).await?,
+            }
+        };
+        Ok(ServerLease {
+            server: Some(server),
+            shared: self.shared.clone(),
+            permit: Some(permit),
+        })
+    }
+
+    /// Maximum number of servers running at once
+    pub fn size(&self) -> usize {
+        self.shared.config.size
+    }
+
+    /// Number of servers running and waiting to be leased
+    pub fn idle(&self) -> usize {
+        self.shared.idle.lock().unwrap().len()
+    }
+}
+
+impl PoolShared {
+    /// Clean a returned server and keep it if it is still healthy
+    async fn recycle(&self, server: ServerProcess) {
+        let client = server.client().with_timeout(HEALTH_CHECK_TIMEOUT);
+        // A server whose machine may have survived is dropped, which kills it
+        match client.destroy().await {
+            Ok(_) | Err(Error::NoMachine) => {}
+            Err(_) => return,
+        }
+        if is_healthy(&server).await {
+            self.idle.lock().unwrap().push(server);
+        }
+    }
+}
+
+/// Whether the server answers calls
+async fn is_healthy(server: &ServerProcess) -> bool {
+    server
+        .client()
+        .with_timeout(HEALTH_CHECK_TIMEOUT)
+        .check_connection()
+        .await
+        .is_ok()
+}
+
+#[doc = " Server leased from a pool"]
+#[doc = " \\details"]
+#[doc = " Dereferences to the client connected to the server. The server goes back"]
+#[doc = " to the pool on release or drop, once it has been cleaned and checked."]
+pub struct ServerLease {
+    server: Option<ServerProcess>,
+    shared: Arc<PoolShared>,
+    permit: Option<OwnedSemaphorePermit>,
+}
+
+impl ServerLease {
+    /// Client connected to the leased server
+    pub fn client(&self) -> &JsonRpcCartesiMachineClient {
+        self.server().client()
+    }
+
+    /// Address the leased server listens on, as host:port
+    pub fn address(&self) -> &str {
+        self.server().address()
+    }
+
+    /// Return the server to the pool, waiting until it has been cleaned and checked
+    pub async fn release(mut self) {
+        if let Some(server) = self.server.take() {
+            self.shared.recycle(server).await;
+        }
+    }
+
+    fn server(&self) -> &ServerProcess {
+        self.server
+            .as_ref()
+            .expect("lease holds a server until dropped")
+    }
+}
+
+impl Deref for ServerLease {
+    type Target = JsonRpcCartesiMachineClient;
+
+    fn deref(&self) -> &Self::Target {
+        self.client()
+    }
+}
+
+impl Drop for ServerLease {
+    fn drop(&mut self) {
+        let server = match self.server.take() {
+            Some(server) => server,
+            None => return,
+        };
+        // Without a runtime the server cannot be cleaned, so it is killed instead
+        if let Ok(runtime) = tokio::runtime::Handle::try_current() {
+            let shared = self.shared.clone();
+            let permit = self.permit.take();
+            runtime.spawn(async move {
+                shared.recycle(server).await;
+                drop(permit);
+            });
+        }
+    }
+}
@@ -0,0 +1,258 @@
+// Copyright (C) 2021 Cartesi Pte. Ltd.
+//
+// Licensed under the Apache License, Version 2.0 (the "License"); you may not use
+// this file except in compliance with the License. You may obtain a copy of the
+// License at http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software distributed
+// under the License is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR
+// CONDITIONS OF ANY KIND, either express or implied. See the License for the
+// specific language governing permissions and limitations under the License.
+
+//! Launching and supervision of local Cartesi machine server processes
+
+use std::env;
+use std::io::{self, Read};
+use std::net::TcpListener;
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, ExitStatus, Stdio};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use crate::client::{Error, JsonRpcCartesiMachineClient};
+
+/// Name of the Cartesi machine server executable
+const SERVER_BINARY: &str = "jsonrpc-remote-cartesi-machine";
+
+/// Where the server is looked for when it is not in PATH
+const FALLBACK_DIRECTORIES: &[&str] = &["/opt/cartesi/bin", "/usr/local/bin", "/usr/bin"];
+
+/// Wait between checks of whether a server that was asked to shut down has exited
+const SHUTDOWN_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Launches of a server that lost its port to another process before giving up
+const START_ATTEMPTS: u32 = 3;
+
+/// Time a server gets to exit after being asked to shut down, before it is killed
+const SHUTDOWN_GRACE_PERIOD: Duration = Duration::from_secs(2);
+
+#[doc = " Options for launching a server process"]
+#[derive(Debug, Clone)]
+pub struct ServerProcessConfig {
+    #[doc = "< Server executable, None searches PATH and then /opt/cartesi/bin"]
+    pub binary: Option<PathBuf>,
+    #[doc = "< Interface the server listens on, on a free port"]
+    pub host: String,
+    #[doc = "< URL scheme the client reaches the server through, http or ws"]
+    pub scheme: String,
+    #[doc = "< Extra command line arguments"]
+    pub args: Vec<String>,
+    #[doc = "< Time the server has to start answering calls"]
+    pub startup_timeout: Duration,
+}
+
+impl Default for ServerProcessConfig {
+    fn default() -> Self {
+        ServerProcessConfig {
+            binary: None,
+            host: "127.0.0.1".to_string(),
+            scheme: "http".to_string(),
+            args: Vec::new(),
+            startup_timeout: Duration::from_secs(10),
+        }
+    }
+}
+
+impl ServerProcessConfig {
+    pub fn new() -> Self {
+        Default::default()
+    }
+}
+
+#[doc = " Cartesi machine server running as a child process"]
+#[doc = " \\details"]
+#[doc = " The process is killed when dropped, or asked to exit by shutdown."]
+#[doc = " Its standard error is captured for diagnostics. The port is chosen free"]
+#[doc = " before launch, so a server that fails because another process took it"]
+#[doc = " first is launched again on a new port."]
+pub struct ServerProcess {
+    child: Option<Child>,
+    address: String,
+    client: JsonRpcCartesiMachineClient,
+    stderr: Arc<Mutex<String>>,
+}
+
+impl ServerProcess {
+    /// Start a server with default options and connect to it
+    pub async fn start() -> Result<Self, Error> {
+        ServerProcess::start_with(&ServerProcessConfig::default()).await
+    }
+
+    /// Start a server and connect to it once it answers calls
+    pub async fn start_with(config: &ServerProcessConfig) -> Result<Self, Error> {
+        let binary = match &config.binary {
+            Some(binary) => binary.clone(),
+            None => find_binary()?,
+        };
+        let mut attempt = 1;
+        loop {
+            match ServerProcess::launch(&binary, config).await {
+                Err(Error::ServerExited { ref stderr, .. })
+                    if attempt < START_ATTEMPTS && is_port_taken(stderr) =>
+                {
+                    attempt += 1
+                }
+                result => return result,
+            }
+        }
+    }
+
+    /// Launch `binary` on a free port once, and connect to it
+    async fn launch(binary: &Path, config: &ServerProcessConfig) -> Result<Self, Error> {
+        let address = format!("{}:{}", config.host, free_port(&config.host)?);
+        let mut child = Command::new(binary)
+            .arg(format!("--server-address={}", address))
+            .args(&config.args)
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::piped())
+            .spawn()?;
+        let stderr = Arc::new(Mutex::new(String::new()));
+        let mut reader = capture(&mut child, stderr.clone());
+        // Checked after connecting too, in case another process answers on the port
+        let connected = JsonRpcCartesiMachineClient::connect_while(
+            format!("{}://{}", config.scheme, address),
+            config.startup_timeout,
+            "start",
+            || check_running(&mut child, &mut reader, &stderr),
+        )
+        .await;
+        match connected {
+            Ok(client) => Ok(ServerProcess {
+                child: Some(child),
+                address,
+                client,
+                stderr,
+            }),
+            Err(err) => {
+                let _ = child.kill();
+                let _ = child.wait();
+                Err(err)
+            }
+        }
+    }
+
+    /// Client connected to the server
+    pub fn client(&self) -> &JsonRpcCartesiMachineClient {
+        &self.client
+    }
+
+    /// Address the server listens on, as host:port
+    pub fn address(&self) -> &str {
+        &self.address
+    }
+
+    /// Operating system id of the server process
+    pub fn pid(&self) -> Option<u32> {
+        self.child.as_ref().map(Child::id)
+    }
+
+    /// Standard error written by the server so far
+    pub fn stderr(&self) -> String {
+        self.stderr.lock().unwrap().clone()
+    }
+
+    /// Ask the server to exit, killing it if it does not within a grace period
+    pub async fn shutdown(mut self) -> Result<ExitStatus, Error> {
+        let mut child = match self.child.take() {
+            Some(child) => child,
+            None => return Err(Error::InvalidArgument("server already stopped".to_string())),
+        };
+        let _ = self.client.shutdown().await;
+        let deadline = Instant::now() + SHUTDOWN_GRACE_PERIOD;
+        while Instant::now() < deadline {
+            if let Some(status) = child.try_wait()? {
+                return Ok(status);
+            }
+            tokio::time::sleep(SHUTDOWN_POLL_INTERVAL).await;
+        }
+        child.kill()?;
+        Ok(child.wait()?)
+    }
+}
+
+impl Drop for ServerProcess {
+    fn drop(&mut self) {
+        if let Some(mut child) = self.child.take() {
+            let _ = child.kill();
+            let _ = child.wait();
+        }
+    }
+}
+
+/// Find the server executable in PATH or in the directories it is usually installed to
+fn find_binary() -> Result<PathBuf, Error> {
+    let path = env::var_os("PATH").unwrap_or_default();
+    env::split_paths(&path)
+        .chain(FALLBACK_DIRECTORIES.iter().map(PathBuf::from))
+        .map(|directory| directory.join(SERVER_BINARY))
+        .find(|candidate| candidate.is_file())
+        .ok_or_else(|| {
+            Error::Io(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!(
+                    "{} not found in PATH or {:?}",
+                    SERVER_BINARY, FALLBACK_DIRECTORIES
+                ),
+            ))
+        })
+}
+
+/// Port that is free on `host` at the time of the call
+fn free_port(host: &str) -> Result<u16, Error> {
+    Ok(TcpListener::bind((host, 0))?.local_addr()?.port())
+}
+
+/// Copy the standard error of `child` into `stderr` as it is written
+fn capture(child: &mut Child, stderr: Arc<Mutex<String>>) -> Option<JoinHandle<()>> {
+    let mut pipe = child.stderr.take()?;
+    Some(thread::spawn(move || {
+        let mut buffer = [0u8; 4096];
+        while let Ok(length) = pipe.read(&mut buffer) {
+            if length == 0 {
+                break;
+            }
+            let text = String::from_utf8_lossy(&buffer[..length]);
+            stderr.lock().unwrap().push_str(&text);
+        }
+    }))
+}
+
+/// Fail with the exit status and standard error of `child` if it has exited
+fn check_running(
+    child: &mut Child,
+    reader: &mut Option<JoinHandle<()>>,
+    stderr: &Mutex<String>,
+) -> Result<(), Error> {
+    match child.try_wait()? {
+        Some(status) => {
+            // The pipe is closed once the process exits, so the reader finishes
+            if let Some(reader) = reader.take() {
+                let _ = reader.join();
+            }
+            Err(Error::ServerExited {
+                status,
+                stderr: stderr.lock().unwrap().clone(),
+            })
+        }
+        None => Ok(()),
+    }
+}
+
+/// Whether a server exited because the port it was given was no longer free
+fn is_port_taken(stderr: &str) -> bool {
+    stderr
+        .to_ascii_lowercase()
+        .contains("address already in use")
+}
@@ -0,0 +1,93 @@
+// Copyright (C) 2021 Cartesi Pte. Ltd.
+//
+// Licensed under the Apache License, Version 2.0 (the "License"); you may not use
+// this file except in compliance with the License. You may obtain a copy of the
+// License at http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software distributed
+// under the License is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR
+// CONDITIONS OF ANY KIND, either express or implied. See the License for the
+// specific language governing permissions and limitations under the License.
+
+//! Machine states saved in forked servers, to roll back to
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::SystemTime;
+
+use jsonrpsee::core::client::ClientT;
+
+use crate::client::{JsonRpcCartesiMachineClient, Transport};
+
+/// Snapshots alive for a client and the clients that replaced it through rollback
+#[derive(Default)]
+pub(crate) struct SnapshotTracker {
+    live: AtomicUsize,
+}
+
+impl SnapshotTracker {
+    pub(crate) fn live(&self) -> usize {
+        self.live.load(Ordering::SeqCst)
+    }
+}
+
+#[doc = " Machine state saved in a forked server"]
+#[doc = " \\details"]
+#[doc = " Taken with snapshot and consumed by rollback or commit of the same client."]
+#[doc = " A snapshot that is dropped instead shuts its server down, like any forked"]
+#[doc = " client. That shutdown is best effort: a server that cannot be reached, or a"]
+#[doc = " runtime that is shutting down, may leave it running."]
+pub struct Snapshot<T = Transport> {
+    client: Option<JsonRpcCartesiMachineClient<T>>,
+    tracker: Arc<SnapshotTracker>,
+}
+
+impl<T: ClientT + Send + Sync + 'static> Snapshot<T> {
+    pub(crate) fn new(
+        client: JsonRpcCartesiMachineClient<T>,
+        tracker: Arc<SnapshotTracker>,
+    ) -> Self {
+        tracker.live.fetch_add(1, Ordering::SeqCst);
+        Snapshot {
+            client: Some(client),
+            tracker,
+        }
+    }
+
+    /// Address of the server holding the saved state
+    pub fn address(&self) -> &str {
+        self.client().get_address()
+    }
+
+    /// Time at which the state was saved
+    pub fn taken_at(&self) -> SystemTime {
+        self.client()
+            .parent()
+            .map(|parent| parent.forked_at)
+            .expect("snapshot servers are forked")
+    }
+
+    /// Whether the snapshot was taken by a client sharing `tracker`
+    pub(crate) fn is_tracked_by(&self, tracker: &Arc<SnapshotTracker>) -> bool {
+        Arc::ptr_eq(&self.tracker, tracker)
+    }
+
+    /// Client connected to the server holding the saved state
+    pub(crate) fn into_client(mut self) -> JsonRpcCartesiMachineClient<T> {
+        self.client
+            .take()
+            .expect("snapshot holds a client until dropped")
+    }
+
+    fn client(&self) -> &JsonRpcCartesiMachineClient<T> {
+        self.client
+            .as_ref()
+            .expect("snapshot holds a client until dropped")
+    }
+}
+
+impl<T> Drop for Snapshot<T> {
+    fn drop(&mut self) {
+        self.tracker.live.fetch_sub(1, Ordering::SeqCst);
+    }
+}
@@ -0,0 +1,245 @@
+// Copyright (C) 2021 Cartesi Pte. Ltd.
+//
+// Licensed under the Apache License, Version 2.0 (the "License"); you may not use
+// this file except in compliance with the License. You may obtain a copy of the
+// License at http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software distributed
+// under the License is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR
+// CONDITIONS OF ANY KIND, either express or implied. See the License for the
+// specific language governing permissions and limitations under the License.
+
+//! Transports the client can reach a Cartesi machine server through
+
+use std::fmt;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+use async_trait::async_trait;
+use jsonrpsee::core::client::{BatchResponse, ClientT};
+use jsonrpsee::core::params::BatchRequestBuilder;
+use jsonrpsee::core::traits::ToRpcParams;
+use jsonrpsee::http_client::{HttpClient, HttpClientBuilder};
+use jsonrpsee::ws_client::{WsClient, WsClientBuilder};
+use serde::de::DeserializeOwned;
+
+use crate::client::Error;
+
+/// Request timeout long enough to never trigger. Duration::MAX overflows the
+/// deadline the WebSocket client computes for every request
+const UNLIMITED_TIMEOUT: Duration = Duration::from_secs(u32::MAX as u64);
+
+#[doc = " Connection to a Cartesi machine server"]
+#[doc = " \\details"]
+#[doc = " HTTP issues a separate round-trip per call. WebSocket keeps one"]
+#[doc = " persistent connection open, and reopens it on the next call if it drops."]
+#[derive(Clone)]
+pub enum Transport {
+    #[doc = "< One HTTP request per call, shared by clones"]
+    Http(Arc<HttpConnection>),
+    #[doc = "< Persistent WebSocket connection, shared by clones"]
+    WebSocket(Arc<WebSocketConnection>),
+}
+
+impl Transport {
+    /// Connect to `address`, choosing WebSocket for ws:// and wss:// and HTTP otherwise
+    pub async fn connect(address: &str) -> Result<Self, Error> {
+        if is_web_socket_address(address) {
+            Transport::web_socket(address).await
+        } else {
+            Transport::http(address)
+        }
+    }
+
+    /// Create an HTTP transport. No connection is made until the first call
+    pub fn http(address: &str) -> Result<Self, Error> {
+        let client = build_http(address)?;
+        Ok(Transport::Http(Arc::new(HttpConnection {
+            address: address.to_string(),
+            client,
+        })))
+    }
+
+    /// Open a WebSocket connection
+    pub async fn web_socket(address: &str) -> Result<Self, Error> {
+        let client = build_web_socket(address).await?;
+        Ok(Transport::WebSocket(Arc::new(WebSocketConnection {
+            address: address.to_string(),
+            client: RwLock::new(Arc::new(client)),
+        })))
+    }
+}
+
+#[doc = " HTTP client together with the address it was built for"]
+#[doc = " \\details"]
+#[doc = " The address keeps the scheme, so that forks are reached over https"]
+#[doc = " when the original server was."]
+pub struct HttpConnection {
+    address: String,
+    client: HttpClient,
+}
+
+#[doc = " WebSocket connection that is reopened when it drops"]
+#[doc = " \\details"]
+#[doc = " The call that finds the connection closed fails; the next call opens a"]
+#[doc = " new connection to the same address."]
+pub struct WebSocketConnection {
+    address: String,
+    client: RwLock<Arc<WsClient>>,
+}
+
+impl HttpConnection {
+    /// Address the client was built for, including its scheme
+    pub fn address(&self) -> &str {
+        &self.address
+    }
+}
+
+impl WebSocketConnection {
+    /// Address the connection is opened to, including its scheme
+    pub fn address(&self) -> &str {
+        &self.address
+    }
+
+    /// Live client, reconnecting first if the connection was closed
+    async fn client(&self) -> Result<Arc<WsClient>, jsonrpsee::core::Error> {
+        let client = self.client.read().unwrap().clone();
+        if client.is_connected() {
+            return Ok(client);
+        }
+        let client = Arc::new(build_web_socket(&self.address).await?);
+        *self.client.write().unwrap() = client.clone();
+        Ok(client)
+    }
+}
+
+fn build_http(address: &str) -> Result<HttpClient, jsonrpsee::core::Error> {
+    HttpClientBuilder::default()
+        .request_timeout(UNLIMITED_TIMEOUT)
+        .build(address)
+}
+
+async fn build_web_socket(address: &str) -> Result<WsClient, jsonrpsee::core::Error> {
+    WsClientBuilder::default()
+        .request_timeout(UNLIMITED_TIMEOUT)
+        .build(address)
+        .await
+}
+
+#[doc = " Transport that can open a new connection of its own kind"]
+#[doc = " \\details"]
+#[doc = " Forked servers listen on a new address, so forking a client needs a"]
+#[doc = " transport to the fork that works the same way as the original."]
+#[async_trait]
+pub trait Reconnect: ClientT + Clone + Send + Sync + Sized + 'static {
+    /// URL scheme the transport reaches servers through, such as "https" or "ws"
+    fn scheme(&self) -> &str;
+
+    /// Open a connection of the same kind to `address`
+    async fn reconnect(&self, address: &str) -> Result<Self, Error>;
+}
+
+#[async_trait]
+impl Reconnect for Transport {
+    fn scheme(&self) -> &str {
+        match self {
+            Transport::Http(connection) => scheme(&connection.address).unwrap_or("http"),
+            Transport::WebSocket(connection) => scheme(&connection.address).unwrap_or("ws"),
+        }
+    }
+
+    async fn reconnect(&self, address: &str) -> Result<Self, Error> {
+        match self {
+            Transport::Http(_) => Transport::http(address),
+            Transport::WebSocket(_) => Transport::web_socket(address).await,
+        }
+    }
+}
+
+/// An HttpClient does not expose the address it was built for, so forks are
+/// always reached over plain http. Use [`Transport::http`] to keep https
+#[async_trait]
+impl Reconnect for HttpClient {
+    fn scheme(&self) -> &str {
+        "http"
+    }
+
+    async fn reconnect(&self, address: &str) -> Result<Self, Error> {
+        Ok(build_http(address)?)
+    }
+}
+
+/// Scheme of `address`, if it has one
+fn scheme(address: &str) -> Option<&str> {
+    address.split_once("://").map(|(scheme, _)| scheme)
+}
+
+/// Whether `address` names a WebSocket endpoint
+fn is_web_socket_address(address: &str) -> bool {
+    let address = address.to_ascii_lowercase();
+    address.starts_with("ws://") || address.starts_with("wss://")
+}
+
+impl fmt::Debug for Transport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Transport::Http(_) => f.write_str("Transport::Http"),
+            Transport::WebSocket(_) => f.write_str("Transport::WebSocket"),
+        }
+    }
+}
+
+#[async_trait]
+impl ClientT for Transport {
+    async fn notification<Params>(
+        &self,
+        method: &str,
+        params: Params,
+    ) -> Result<(), jsonrpsee::core::Error>
+    where
+        Params: ToRpcParams + Send,
+    {
+        match self {
+            Transport::Http(connection) => connection.client.notification(method, params).await,
+            Transport::WebSocket(connection) => {
+                connection
+                    .client()
+                    .await?
+                    .notification(method, params)
+                    .await
+            }
+        }
+    }
+
+    async fn request<R, Params>(
+        &self,
+        method: &str,
+        params: Params,
+    ) -> Result<R, jsonrpsee::core::Error>
+    where
+        R: DeserializeOwned,
+        Params: ToRpcParams + Send,
+    {
+        match self {
+            Transport::Http(connection) => connection.client.request(method, params).await,
+            Transport::WebSocket(connection) => {
+                connection.client().await?.request(method, params).await
+            }
+        }
+    }
+
+    async fn batch_request<'a, R>(
+        &self,
+        batch: BatchRequestBuilder<'a>,
+    ) -> Result<BatchResponse<'a, R>, jsonrpsee::core::Error>
+    where
+        R: DeserializeOwned + fmt::Debug + 'a,
+    {
+        match self {
+            Transport::Http(connection) => connection.client.batch_request(batch).await,
+            Transport::WebSocket(connection) => {
+                connection.client().await?.batch_request(batch).await
+            }
+        }
+    }
+}
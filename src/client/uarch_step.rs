@@ -0,0 +1,559 @@
+// Copyright (C) 2021 Cartesi Pte. Ltd.
+//
+// Licensed under the Apache License, Version 2.0 (the "License"); you may not use
+// this file except in compliance with the License. You may obtain a copy of the
+// License at http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software distributed
+// under the License is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR
+// CONDITIONS OF ANY KIND, either express or implied. See the License for the
+// specific language governing permissions and limitations under the License.
+
+//! Single-step semantics of the microarchitecture interpreter
+//!
+//! Mirrors `uarch-step.h` from the emulator: every step reads the cycle, the
+//! halt flag, the pc and the instruction word, executes one RV64I instruction
+//! and writes the incremented cycle. The state accesses happen in exactly the
+//! order the emulator logs them, so replaying a step against an access log
+//! checks that the log is what one uarch instruction produces.
+
+use crate::client::merkle::{MerkleTree, LOG2_WORD_SIZE};
+use crate::client::{Access, AccessLog, AccessType, Error, Hash};
+
+#[doc = " Address of the uarch halt flag in the machine state"]
+pub const UARCH_HALT_FLAG_ADDRESS: u64 = 0x400008000;
+#[doc = " Address of the uarch cycle counter in the machine state"]
+pub const UARCH_CYCLE_ADDRESS: u64 = 0x400008008;
+#[doc = " Address of the uarch pc in the machine state"]
+pub const UARCH_PC_ADDRESS: u64 = 0x400008010;
+#[doc = " Address of uarch register x0 in the machine state"]
+pub const UARCH_X0_ADDRESS: u64 = 0x400008018;
+
+#[doc = " ECALL function (in a7) that halts the microarchitecture"]
+pub const UARCH_ECALL_FN_HALT: u64 = 1;
+#[doc = " ECALL function (in a7) that prints the character in a6"]
+pub const UARCH_ECALL_FN_PUTCHAR: u64 = 2;
+
+#[doc = " Outcome of a single uarch step"]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UarchStepStatus {
+    #[doc = "< One instruction was executed"]
+    Success,
+    #[doc = "< Cycle counter is saturated, nothing was executed"]
+    CycleOverflow,
+    #[doc = "< Microarchitecture is halted, nothing was executed"]
+    UarchHalted,
+}
+
+#[doc = " Machine state as seen by the uarch interpreter"]
+pub trait UarchState {
+    /// Read the word at an aligned address
+    fn read_word(&mut self, address: u64) -> Result<u64, Error>;
+
+    /// Write the word at an aligned address
+    fn write_word(&mut self, address: u64, value: u64) -> Result<(), Error>;
+
+    /// Output a character requested by the uarch program
+    fn putchar(&mut self, _character: u8) {}
+}
+
+impl UarchState for MerkleTree {
+    fn read_word(&mut self, address: u64) -> Result<u64, Error> {
+        Ok(MerkleTree::read_word(self, address))
+    }
+
+    fn write_word(&mut self, address: u64, value: u64) -> Result<(), Error> {
+        MerkleTree::write_word(self, address, value);
+        Ok(())
+    }
+}
+
+/// Execute one uarch instruction against `state`
+pub fn uarch_step<S: UarchState + ?Sized>(state: &mut S) -> Result<UarchStepStatus, Error> {
+    let cycle = state.read_word(UARCH_CYCLE_ADDRESS)?;
+    if cycle == u64::MAX {
+        return Ok(UarchStepStatus::CycleOverflow);
+    }
+    if state.read_word(UARCH_HALT_FLAG_ADDRESS)? != 0 {
+        return Ok(UarchStepStatus::UarchHalted);
+    }
+    let pc = state.read_word(UARCH_PC_ADDRESS)?;
+    let insn = read_uint(state, pc, 4)? as u32;
+    execute_insn(state, insn, pc)?;
+    state.write_word(UARCH_CYCLE_ADDRESS, cycle + 1)?;
+    Ok(UarchStepStatus::Success)
+}
+
+#[doc = " Machine state that records every access with proofs, as the emulator does"]
+#[derive(Debug)]
+pub struct RecordingUarchState<'a> {
+    tree: &'a mut MerkleTree,
+    accesses: Vec<Access>,
+}
+
+impl<'a> RecordingUarchState<'a> {
+    pub fn new(tree: &'a mut MerkleTree) -> Self {
+        RecordingUarchState {
+            tree,
+            accesses: Vec::new(),
+        }
+    }
+
+    /// Log with the accesses recorded so far
+    pub fn into_log(self) -> AccessLog {
+        AccessLog {
+            accesses: self.accesses,
+            brackets: Vec::new(),
+            notes: Vec::new(),
+            log_type: crate::client::AccessLogType {
+                proofs: true,
+                annotations: false,
+            },
+        }
+    }
+
+    fn record(
+        &mut self,
+        r#type: AccessType,
+        address: u64,
+        written_data: Vec<u8>,
+    ) -> Result<(), Error> {
+        let proof = self.tree.proof(address, LOG2_WORD_SIZE)?;
+        self.accesses.push(Access {
+            r#type,
+            address,
+            log2_size: LOG2_WORD_SIZE as i32,
+            read_data: self.tree.read(address, 1 << LOG2_WORD_SIZE),
+            written_data,
+            proof,
+        });
+        Ok(())
+    }
+}
+
+impl UarchState for RecordingUarchState<'_> {
+    fn read_word(&mut self, address: u64) -> Result<u64, Error> {
+        self.record(AccessType::Read, address, Vec::new())?;
+        Ok(MerkleTree::read_word(self.tree, address))
+    }
+
+    fn write_word(&mut self, address: u64, value: u64) -> Result<(), Error> {
+        self.record(AccessType::Write, address, value.to_le_bytes().to_vec())?;
+        MerkleTree::write_word(self.tree, address, value);
+        Ok(())
+    }
+}
+
+/// Machine state backed by an access log, checking each access as it is consumed
+struct ReplayUarchState<'a> {
+    accesses: &'a [Access],
+    next: usize,
+    root_hash: Hash,
+}
+
+impl ReplayUarchState<'_> {
+    fn next_access(&mut self, r#type: AccessType, address: u64) -> Result<&Access, Error> {
+        let index = self.next;
+        let access = self
+            .accesses
+            .get(index)
+            .ok_or_else(|| Error::InconsistentAccess {
+                index,
+                reason: "log ended before the step did".to_string(),
+            })?;
+        if access.r#type != r#type
+            || access.address != address
+            || access.log2_size != LOG2_WORD_SIZE as i32
+        {
+            return Err(Error::InconsistentAccess {
+                index,
+                reason: format!(
+                    "expected {:?} of word at {:#x}, log has {:?} of 2^{} bytes at {:#x}",
+                    r#type, address, access.r#type, access.log2_size, access.address
+                ),
+            });
+        }
+        self.root_hash = access
+            .replay(&self.root_hash)
+            .map_err(|reason| Error::InconsistentAccess { index, reason })?;
+        self.next += 1;
+        Ok(access)
+    }
+}
+
+fn word_from_le_bytes(data: &[u8]) -> u64 {
+    let mut word = [0u8; 1 << LOG2_WORD_SIZE];
+    word.copy_from_slice(data);
+    u64::from_le_bytes(word)
+}
+
+impl UarchState for ReplayUarchState<'_> {
+    fn read_word(&mut self, address: u64) -> Result<u64, Error> {
+        let access = self.next_access(AccessType::Read, address)?;
+        Ok(word_from_le_bytes(&access.read_data))
+    }
+
+    fn write_word(&mut self, address: u64, value: u64) -> Result<(), Error> {
+        let index = self.next;
+        let access = self.next_access(AccessType::Write, address)?;
+        let written = word_from_le_bytes(&access.written_data);
+        if written != value {
+            return Err(Error::InconsistentAccess {
+                index,
+                reason: format!("expected write of {:#x}, log has {:#x}", value, written),
+            });
+        }
+        Ok(())
+    }
+}
+
+impl AccessLog {
+    /// Replay one uarch step from `root_hash_before` and return the resulting root hash
+    ///
+    /// Fails with [`Error::InconsistentAccess`] at the first access that is not what the
+    /// uarch interpreter would do, or whose proof does not match the state before it.
+    pub fn verify_uarch_step(&self, root_hash_before: &Hash) -> Result<Hash, Error> {
+        if !self.log_type.proofs {
+            return Err(Error::InvalidArgument(
+                "access log has no proofs".to_string(),
+            ));
+        }
+        let mut state = ReplayUarchState {
+            accesses: &self.accesses,
+            next: 0,
+            root_hash: *root_hash_before,
+        };
+        uarch_step(&mut state)?;
+        if state.next != self.accesses.len() {
+            return Err(Error::InconsistentAccess {
+                index: state.next,
+                reason: "access past the end of the step".to_string(),
+            });
+        }
+        Ok(state.root_hash)
+    }
+
+    /// Check that the log is one uarch step taking the machine from `root_hash_before`
+    /// to `root_hash_after`
+    pub fn verify_uarch_step_transition(
+        &self,
+        root_hash_before: &Hash,
+        root_hash_after: &Hash,
+    ) -> Result<(), Error> {
+        let computed = self.verify_uarch_step(root_hash_before)?;
+        if computed != *root_hash_after {
+            return Err(Error::RootHashMismatch {
+                expected: *root_hash_after,
+                computed,
+            });
+        }
+        Ok(())
+    }
+}
+
+fn illegal_instruction(insn: u32, pc: u64) -> Error {
+    Error::UarchException(format!(
+        "illegal instruction {:#010x} at pc {:#x}",
+        insn, pc
+    ))
+}
+
+/// Read `size` bytes at a naturally aligned address from the word containing it
+fn read_uint<S: UarchState + ?Sized>(state: &mut S, address: u64, size: u64) -> Result<u64, Error> {
+    check_alignment(address, size)?;
+    let word = state.read_word(address & !7)?;
+    let value = word >> ((address & 7) * 8);
+    Ok(if size == 8 {
+        value
+    } else {
+        value & ((1u64 << (size * 8)) - 1)
+    })
+}
+
+/// Write `size` bytes at a naturally aligned address into the word containing it
+fn write_uint<S: UarchState + ?Sized>(
+    state: &mut S,
+    address: u64,
+    size: u64,
+    value: u64,
+) -> Result<(), Error> {
+    check_alignment(address, size)?;
+    if size == 8 {
+        return state.write_word(address, value);
+    }
+    let shift = (address & 7) * 8;
+    let mask = ((1u64 << (size * 8)) - 1) << shift;
+    let old = state.read_word(address & !7)?;
+    state.write_word(address & !7, (old & !mask) | ((value << shift) & mask))
+}
+
+fn check_alignment(address: u64, size: u64) -> Result<(), Error> {
+    if address & (size - 1) != 0 {
+        return Err(Error::UarchException(format!(
+            "misaligned {}-byte access at {:#x}",
+            size, address
+        )));
+    }
+    Ok(())
+}
+
+fn read_register<S: UarchState + ?Sized>(state: &mut S, reg: u32) -> Result<u64, Error> {
+    state.read_word(UARCH_X0_ADDRESS + 8 * reg as u64)
+}
+
+fn write_register<S: UarchState + ?Sized>(
+    state: &mut S,
+    reg: u32,
+    value: u64,
+) -> Result<(), Error> {
+    state.write_word(UARCH_X0_ADDRESS + 8 * reg as u64, value)
+}
+
+fn operand_rd(insn: u32) -> u32 {
+    (insn >> 7) & 0x1f
+}
+
+fn operand_rs1(insn: u32) -> u32 {
+    (insn >> 15) & 0x1f
+}
+
+fn operand_rs2(insn: u32) -> u32 {
+    (insn >> 20) & 0x1f
+}
+
+fn operand_funct3(insn: u32) -> u32 {
+    (insn >> 12) & 0x7
+}
+
+fn operand_funct7(insn: u32) -> u32 {
+    insn >> 25
+}
+
+fn operand_imm_i(insn: u32) -> i64 {
+    (insn as i32 >> 20) as i64
+}
+
+fn operand_imm_s(insn: u32) -> i64 {
+    (((insn & 0xfe000000) as i32 >> 20) | ((insn >> 7) & 0x1f) as i32) as i64
+}
+
+fn operand_imm_b(insn: u32) -> i64 {
+    (((insn & 0x80000000) as i32 >> 19)
+        | ((insn << 4) & 0x800) as i32
+        | ((insn >> 20) & 0x7e0) as i32
+        | ((insn >> 7) & 0x1e) as i32) as i64
+}
+
+fn operand_imm_u(insn: u32) -> i64 {
+    (insn & 0xfffff000) as i32 as i64
+}
+
+fn operand_imm_j(insn: u32) -> i64 {
+    (((insn & 0x80000000) as i32 >> 11)
+        | (insn & 0xff000) as i32
+        | ((insn >> 9) & 0x800) as i32
+        | ((insn >> 20) & 0x7fe) as i32) as i64
+}
+
+fn advance_pc<S: UarchState + ?Sized>(state: &mut S, pc: u64) -> Result<(), Error> {
+    state.write_word(UARCH_PC_ADDRESS, pc.wrapping_add(4))
+}
+
+fn branch<S: UarchState + ?Sized>(state: &mut S, target: u64) -> Result<(), Error> {
+    state.write_word(UARCH_PC_ADDRESS, target)
+}
+
+/// Write `value()` to rd, reading operands only when rd is not x0, then advance the pc
+fn execute_op<S, F>(state: &mut S, insn: u32, pc: u64, value: F) -> Result<(), Error>
+where
+    S: UarchState + ?Sized,
+    F: FnOnce(&mut S) -> Result<u64, Error>,
+{
+    let rd = operand_rd(insn);
+    if rd != 0 {
+        let value = value(state)?;
+        write_register(state, rd, value)?;
+    }
+    advance_pc(state, pc)
+}
+
+fn sext32(value: u64) -> u64 {
+    value as i32 as i64 as u64
+}
+
+fn execute_insn<S: UarchState + ?Sized>(state: &mut S, insn: u32, pc: u64) -> Result<(), Error> {
+    let funct3 = operand_funct3(insn);
+    let funct7 = operand_funct7(insn);
+    let rs1 = operand_rs1(insn);
+    let rs2 = operand_rs2(insn);
+    match insn & 0x7f {
+        // LUI
+        0x37 => execute_op(state, insn, pc, |_| Ok(operand_imm_u(insn) as u64)),
+        // AUIPC
+        0x17 => execute_op(state, insn, pc, |_| {
+            Ok(pc.wrapping_add(operand_imm_u(insn) as u64))
+        }),
+        // JAL
+        0x6f => {
+            let rd = operand_rd(insn);
+            if rd != 0 {
+                write_register(state, rd, pc.wrapping_add(4))?;
+            }
+            branch(state, pc.wrapping_add(operand_imm_j(insn) as u64))
+        }
+        // JALR
+        0x67 if funct3 == 0 => {
+            let rs1val = read_register(state, rs1)?;
+            let rd = operand_rd(insn);
+            if rd != 0 {
+                write_register(state, rd, pc.wrapping_add(4))?;
+            }
+            branch(state, rs1val.wrapping_add(operand_imm_i(insn) as u64) & !1)
+        }
+        // BEQ, BNE, BLT, BGE, BLTU, BGEU
+        0x63 => {
+            let rs1val = read_register(state, rs1)?;
+            let rs2val = read_register(state, rs2)?;
+            let taken = match funct3 {
+                0 => rs1val == rs2val,
+                1 => rs1val != rs2val,
+                4 => (rs1val as i64) < (rs2val as i64),
+                5 => (rs1val as i64) >= (rs2val as i64),
+                6 => rs1val < rs2val,
+                7 => rs1val >= rs2val,
+                _ => return Err(illegal_instruction(insn, pc)),
+            };
+            if taken {
+                branch(state, pc.wrapping_add(operand_imm_b(insn) as u64))
+            } else {
+                advance_pc(state, pc)
+            }
+        }
+        // LB, LH, LW, LD, LBU, LHU, LWU
+        0x03 => {
+            let (size, signed) = match funct3 {
+                0 => (1, true),
+                1 => (2, true),
+                2 => (4, true),
+                3 => (8, false),
+                4 => (1, false),
+                5 => (2, false),
+                6 => (4, false),
+                _ => return Err(illegal_instruction(insn, pc)),
+            };
+            let rs1val = read_register(state, rs1)?;
+            let mut value =
+                read_uint(state, rs1val.wrapping_add(operand_imm_i(insn) as u64), size)?;
+            if signed {
+                let shift = 64 - size * 8;
+                value = (((value << shift) as i64) >> shift) as u64;
+            }
+            let rd = operand_rd(insn);
+            if rd != 0 {
+                write_register(state, rd, value)?;
+            }
+            advance_pc(state, pc)
+        }
+        // SB, SH, SW, SD
+        0x23 if funct3 <= 3 => {
+            let rs1val = read_register(state, rs1)?;
+            let rs2val = read_register(state, rs2)?;
+            let address = rs1val.wrapping_add(operand_imm_s(insn) as u64);
+            write_uint(state, address, 1 << funct3, rs2val)?;
+            advance_pc(state, pc)
+        }
+        // ADDI, SLTI, SLTIU, XORI, ORI, ANDI, SLLI, SRLI, SRAI
+        0x13 => {
+            let imm = operand_imm_i(insn);
+            let shamt = (insn >> 20) & 0x3f;
+            let op: fn(u64, i64, u32) -> u64 = match (funct3, funct7 >> 1) {
+                (0, _) => |a, imm, _| a.wrapping_add(imm as u64),
+                (2, _) => |a, imm, _| ((a as i64) < imm) as u64,
+                (3, _) => |a, imm, _| (a < imm as u64) as u64,
+                (4, _) => |a, imm, _| a ^ imm as u64,
+                (6, _) => |a, imm, _| a | imm as u64,
+                (7, _) => |a, imm, _| a & imm as u64,
+                (1, 0) => |a, _, shamt| a << shamt,
+                (5, 0) => |a, _, shamt| a >> shamt,
+                (5, 0x10) => |a, _, shamt| ((a as i64) >> shamt) as u64,
+                _ => return Err(illegal_instruction(insn, pc)),
+            };
+            execute_op(state, insn, pc, |state| {
+                Ok(op(read_register(state, rs1)?, imm, shamt))
+            })
+        }
+        // ADDIW, SLLIW, SRLIW, SRAIW
+        0x1b => {
+            let imm = operand_imm_i(insn);
+            let shamt = (insn >> 20) & 0x1f;
+            let op: fn(u64, i64, u32) -> u64 = match (funct3, funct7) {
+                (0, _) => |a, imm, _| sext32(a.wrapping_add(imm as u64)),
+                (1, 0) => |a, _, shamt| sext32(a << shamt),
+                (5, 0) => |a, _, shamt| sext32((a as u32 >> shamt) as u64),
+                (5, 0x20) => |a, _, shamt| (a as i32 >> shamt) as i64 as u64,
+                _ => return Err(illegal_instruction(insn, pc)),
+            };
+            execute_op(state, insn, pc, |state| {
+                Ok(op(read_register(state, rs1)?, imm, shamt))
+            })
+        }
+        // ADD, SUB, SLL, SLT, SLTU, XOR, SRL, SRA, OR, AND
+        0x33 => {
+            let op: fn(u64, u64) -> u64 = match (funct3, funct7) {
+                (0, 0) => |a, b| a.wrapping_add(b),
+                (0, 0x20) => |a, b| a.wrapping_sub(b),
+                (1, 0) => |a, b| a << (b & 0x3f),
+                (2, 0) => |a, b| ((a as i64) < (b as i64)) as u64,
+                (3, 0) => |a, b| (a < b) as u64,
+                (4, 0) => |a, b| a ^ b,
+                (5, 0) => |a, b| a >> (b & 0x3f),
+                (5, 0x20) => |a, b| ((a as i64) >> (b & 0x3f)) as u64,
+                (6, 0) => |a, b| a | b,
+                (7, 0) => |a, b| a & b,
+                _ => return Err(illegal_instruction(insn, pc)),
+            };
+            execute_op(state, insn, pc, |state| {
+                let rs1val = read_register(state, rs1)?;
+                let rs2val = read_register(state, rs2)?;
+                Ok(op(rs1val, rs2val))
+            })
+        }
+        // ADDW, SUBW, SLLW, SRLW, SRAW
+        0x3b => {
+            let op: fn(u64, u64) -> u64 = match (funct3, funct7) {
+                (0, 0) => |a, b| sext32(a.wrapping_add(b)),
+                (0, 0x20) => |a, b| sext32(a.wrapping_sub(b)),
+                (1, 0) => |a, b| sext32(a << (b & 0x1f)),
+                (5, 0) => |a, b| sext32((a as u32 >> (b & 0x1f)) as u64),
+                (5, 0x20) => |a, b| (a as i32 >> (b & 0x1f)) as i64 as u64,
+                _ => return Err(illegal_instruction(insn, pc)),
+            };
+            execute_op(state, insn, pc, |state| {
+                let rs1val = read_register(state, rs1)?;
+                let rs2val = read_register(state, rs2)?;
+                Ok(op(rs1val, rs2val))
+            })
+        }
+        // FENCE
+        0x0f if funct3 == 0 => advance_pc(state, pc),
+        // ECALL
+        0x73 if insn == 0x73 => {
+            match read_register(state, 17)? {
+                // As in the reference executeECALL, halting still advances the pc
+                UARCH_ECALL_FN_HALT => state.write_word(UARCH_HALT_FLAG_ADDRESS, 1)?,
+                UARCH_ECALL_FN_PUTCHAR => {
+                    let character = read_register(state, 16)?;
+                    state.putchar(character as u8);
+                }
+                function => {
+                    return Err(Error::UarchException(format!(
+                        "unsupported ecall function {}",
+                        function
+                    )))
+                }
+            }
+            advance_pc(state, pc)
+        }
+        _ => Err(illegal_instruction(insn, pc)),
+    }
+}
@@ -0,0 +1,479 @@
+// Copyright (C) 2021 Cartesi Pte. Ltd.
+//
+// Licensed under the Apache License, Version 2.0 (the "License"); you may not use
+// this file except in compliance with the License. You may obtain a copy of the
+// License at http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software distributed
+// under the License is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR
+// CONDITIONS OF ANY KIND, either express or implied. See the License for the
+// specific language governing permissions and limitations under the License.
+
+//! Machine state kept by the fake server
+//!
+//! Registers and CSRs live at their shadow addresses inside the same sparse
+//! Merkle tree as memory, so root hashes, proofs and uarch step logs are
+//! consistent with each other.
+
+use crate::client::merkle::{MerkleTree, LOG2_WORD_SIZE};
+use crate::client::rollup::{HTIF_DEVICE_YIELD, HTIF_YIELD_AUTOMATIC, HTIF_YIELD_MANUAL};
+use crate::client::uarch_step::{
+    uarch_step, RecordingUarchState, UarchStepStatus, UARCH_CYCLE_ADDRESS, UARCH_HALT_FLAG_ADDRESS,
+    UARCH_PC_ADDRESS, UARCH_X0_ADDRESS,
+};
+use crate::client::{AccessLog, Csr, Error, MerkleTreeProof, MARCHID, MIMPID, MVENDORID};
+use crate::interfaces::{self, InterpreterBreakReason, UarchInterpreterBreakReason};
+
+pub(crate) const X_ADDRESS: u64 = 0x0;
+pub(crate) const F_ADDRESS: u64 = 0x100;
+const PROCESSOR_CSR_ADDRESS: u64 = 0x200;
+const ROM_START: u64 = 0x1000;
+const RAM_START: u64 = 0x80000000;
+const UARCH_RAM_START: u64 = 0x70000000;
+
+pub(crate) const IFLAGS_H: u64 = 1 << 0;
+pub(crate) const IFLAGS_Y: u64 = 1 << 1;
+pub(crate) const IFLAGS_X: u64 = 1 << 2;
+const IFLAGS_PRV_SHIFT: u64 = 3;
+
+/// Configuration returned by `machine.get_default_config`
+pub(crate) fn default_config() -> interfaces::MachineConfig {
+    interfaces::MachineConfig {
+        processor: Some(interfaces::ProcessorConfig {
+            x: Some(vec![0; 32]),
+            f: Some(vec![0; 32]),
+            pc: Some(0x1000),
+            mvendorid: Some(MVENDORID),
+            marchid: Some(MARCHID),
+            mimpid: Some(MIMPID),
+            misa: Some(0x800000000014112d),
+            mstatus: Some(0xa00000000),
+            iflags: Some(0x18),
+            ..Default::default()
+        }),
+        ram: Some(interfaces::RAMConfig {
+            length: 0,
+            image_filename: Some(String::new()),
+        }),
+        rom: Some(interfaces::ROMConfig {
+            bootargs: Some(String::new()),
+            image_filename: Some(String::new()),
+        }),
+        tlb: Some(interfaces::TLBConfig {
+            image_filename: Some(String::new()),
+        }),
+        uarch: Some(interfaces::UarchConfig {
+            processor: Some(interfaces::UarchProcessorConfig {
+                x: Some(vec![0; 32]),
+                pc: Some(UARCH_RAM_START),
+                cycle: Some(0),
+            }),
+            ram: Some(interfaces::UarchRAMConfig {
+                length: Some(0),
+                image_filename: Some(String::new()),
+            }),
+        }),
+        flash_drive: Some(Vec::new()),
+        clint: Some(interfaces::CLINTConfig { mtimecmp: Some(0) }),
+        htif: Some(interfaces::HTIFConfig {
+            console_getchar: Some(false),
+            yield_manual: Some(false),
+            yield_automatic: Some(false),
+            fromhost: Some(0),
+            tohost: Some(0),
+        }),
+        rollup: Some(interfaces::RollupConfig::default()),
+    }
+}
+
+fn csr_config_value(config: &interfaces::MachineConfig, csr: Csr) -> u64 {
+    let default = interfaces::ProcessorConfig::default();
+    let p = config.processor.as_ref().unwrap_or(&default);
+    let htif = config.htif.clone().unwrap_or_default();
+    let flag = |value: Option<bool>, bit: u64| if value.unwrap_or(false) { bit } else { 0 };
+    let value = match csr {
+        Csr::Pc => p.pc,
+        Csr::Fcsr => p.fcsr,
+        Csr::Mvendorid => p.mvendorid,
+        Csr::Marchid => p.marchid,
+        Csr::Mimpid => p.mimpid,
+        Csr::Mcycle => p.mcycle,
+        Csr::Icycleinstret => p.icycleinstret,
+        Csr::Mstatus => p.mstatus,
+        Csr::Mtvec => p.mtvec,
+        Csr::Mscratch => p.mscratch,
+        Csr::Mepc => p.mepc,
+        Csr::Mcause => p.mcause,
+        Csr::Mtval => p.mtval,
+        Csr::Misa => p.misa,
+        Csr::Mie => p.mie,
+        Csr::Mip => p.mip,
+        Csr::Medeleg => p.medeleg,
+        Csr::Mideleg => p.mideleg,
+        Csr::Mcounteren => p.mcounteren,
+        Csr::Menvcfg => p.menvcfg,
+        Csr::Stvec => p.stvec,
+        Csr::Sscratch => p.sscratch,
+        Csr::Sepc => p.sepc,
+        Csr::Scause => p.scause,
+        Csr::Stval => p.stval,
+        Csr::Satp => p.satp,
+        Csr::Scounteren => p.scounteren,
+        Csr::Senvcfg => p.senvcfg,
+        Csr::Ilrsc => p.ilrsc,
+        Csr::Iflags => p.iflags,
+        Csr::ClintMtimecmp => config.clint.as_ref().and_then(|clint| clint.mtimecmp),
+        Csr::HtifTohost => htif.tohost,
+        Csr::HtifFromhost => htif.fromhost,
+        Csr::HtifIhalt => Some(1),
+        Csr::HtifIconsole => Some(1 | flag(htif.console_getchar, 1 << 1)),
+        Csr::HtifIyield => Some(flag(htif.yield_manual, 1) | flag(htif.yield_automatic, 1 << 1)),
+        Csr::UarchPc => config
+            .uarch
+            .as_ref()
+            .and_then(|uarch| uarch.processor.as_ref())
+            .and_then(|processor| processor.pc),
+        Csr::UarchCycle => config
+            .uarch
+            .as_ref()
+            .and_then(|uarch| uarch.processor.as_ref())
+            .and_then(|processor| processor.cycle),
+        Csr::UarchHaltFlag => None,
+        Csr::UarchRamLength => uarch_ram(config).and_then(|ram| ram.length),
+    };
+    value.unwrap_or_default()
+}
+
+fn uarch_ram(config: &interfaces::MachineConfig) -> Option<&interfaces::UarchRAMConfig> {
+    config.uarch.as_ref().and_then(|uarch| uarch.ram.as_ref())
+}
+
+/// Memory ranges of the configuration that may be backed by image files
+fn memory_ranges(config: &interfaces::MachineConfig) -> Vec<(u64, u64, Option<String>)> {
+    let mut ranges = Vec::new();
+    if let Some(rom) = &config.rom {
+        ranges.push((ROM_START, 0xf000, rom.image_filename.clone()));
+    }
+    if let Some(ram) = &config.ram {
+        ranges.push((RAM_START, ram.length, ram.image_filename.clone()));
+    }
+    if let Some(ram) = uarch_ram(config) {
+        ranges.push((
+            UARCH_RAM_START,
+            ram.length.unwrap_or_default(),
+            ram.image_filename.clone(),
+        ));
+    }
+    let rollup = config.rollup.clone().unwrap_or_default();
+    let drives = config.flash_drive.iter().flatten().cloned().chain(
+        vec![
+            rollup.rx_buffer,
+            rollup.tx_buffer,
+            rollup.input_metadata,
+            rollup.voucher_hashes,
+            rollup.notice_hashes,
+        ]
+        .into_iter()
+        .flatten(),
+    );
+    for drive in drives {
+        ranges.push((
+            drive.start.unwrap_or_default(),
+            drive.length.unwrap_or_default(),
+            drive.image_filename.clone(),
+        ));
+    }
+    ranges
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct FakeMachine {
+    config: interfaces::MachineConfig,
+    state: MerkleTree,
+    /// Whether tohost was written since the last run, standing in for the
+    /// guest code that writes it while running
+    tohost_written: bool,
+}
+
+impl FakeMachine {
+    pub(crate) fn new(config: interfaces::MachineConfig) -> Result<Self, Error> {
+        let mut state = MerkleTree::new();
+        let processor = config.processor.clone().unwrap_or_default();
+        for (i, value) in processor.x.iter().flatten().enumerate().take(32) {
+            state.write_word(X_ADDRESS + 8 * i as u64, *value);
+        }
+        for (i, value) in processor.f.iter().flatten().enumerate().take(32) {
+            state.write_word(F_ADDRESS + 8 * i as u64, *value);
+        }
+        for csr in Csr::ALL {
+            if let Some(address) = csr_address(*csr) {
+                state.write_word(address, csr_config_value(&config, *csr));
+            }
+        }
+        let uarch_x = config
+            .uarch
+            .as_ref()
+            .and_then(|uarch| uarch.processor.as_ref())
+            .and_then(|processor| processor.x.clone())
+            .unwrap_or_default();
+        for (i, value) in uarch_x.iter().enumerate().take(32) {
+            state.write_word(UARCH_X0_ADDRESS + 8 * i as u64, *value);
+        }
+        let mut machine = FakeMachine {
+            config,
+            state,
+            tohost_written: false,
+        };
+        for (start, length, image_filename) in memory_ranges(&machine.config) {
+            machine.load_image(start, length, image_filename.as_deref())?;
+        }
+        Ok(machine)
+    }
+
+    fn load_image(
+        &mut self,
+        start: u64,
+        length: u64,
+        image_filename: Option<&str>,
+    ) -> Result<(), Error> {
+        self.state.clear(start, length);
+        let image_filename = match image_filename {
+            Some(name) if !name.is_empty() => name,
+            _ => return Ok(()),
+        };
+        let image = std::fs::read(image_filename).map_err(|err| {
+            Error::InvalidArgument(format!("unable to read image {}: {}", image_filename, err))
+        })?;
+        if image.len() as u64 > length {
+            return Err(Error::InvalidArgument(format!(
+                "image {} is larger than its range",
+                image_filename
+            )));
+        }
+        for (i, chunk) in image.chunks(1 << LOG2_WORD_SIZE).enumerate() {
+            if chunk.iter().any(|byte| *byte != 0) {
+                self.state
+                    .write(start + (i << LOG2_WORD_SIZE) as u64, chunk);
+            }
+        }
+        Ok(())
+    }
+
+    pub(crate) fn initial_config(&self) -> &interfaces::MachineConfig {
+        &self.config
+    }
+
+    pub(crate) fn root_hash(&self) -> crate::client::Hash {
+        self.state.root_hash()
+    }
+
+    pub(crate) fn proof(&self, address: u64, log2_size: u64) -> Result<MerkleTreeProof, Error> {
+        self.state.proof(address, log2_size as usize)
+    }
+
+    pub(crate) fn read_word(&self, address: u64) -> Result<u64, Error> {
+        if address & ((1 << LOG2_WORD_SIZE) - 1) != 0 {
+            return Err(Error::InvalidArgument(format!(
+                "address {:#x} is not aligned to a word",
+                address
+            )));
+        }
+        Ok(self.state.read_word(address))
+    }
+
+    /// Read `length` bytes at `address`, which must lie within a single memory range,
+    /// so the length requested is bounded by the machine configuration
+    pub(crate) fn read_memory(&self, address: u64, length: u64) -> Result<Vec<u8>, Error> {
+        let mapped = memory_ranges(&self.config).iter().any(|(start, size, _)| {
+            address >= *start
+                && address
+                    .checked_add(length)
+                    .is_some_and(|end| end <= start.saturating_add(*size))
+        });
+        if !mapped {
+            return Err(Error::InvalidArgument(
+                "address range not entirely in memory PMA".to_string(),
+            ));
+        }
+        Ok(self.state.read(address, length as usize))
+    }
+
+    pub(crate) fn write_memory(&mut self, address: u64, data: &[u8]) {
+        self.state.write(address, data);
+    }
+
+    pub(crate) fn replace_memory_range(
+        &mut self,
+        range: &interfaces::MemoryRangeConfig,
+    ) -> Result<(), Error> {
+        let start = range.start.unwrap_or_default();
+        let length = range.length.unwrap_or_default();
+        let exists = memory_ranges(&self.config)
+            .iter()
+            .any(|(s, l, _)| *s == start && *l == length);
+        if !exists {
+            return Err(Error::InvalidArgument(
+                "attempt to replace inexistent memory range".to_string(),
+            ));
+        }
+        self.load_image(start, length, range.image_filename.as_deref())
+    }
+
+    pub(crate) fn read_csr(&self, csr: Csr) -> u64 {
+        match csr_address(csr) {
+            Some(address) => self.state.read_word(address),
+            None => csr_config_value(&self.config, csr),
+        }
+    }
+
+    pub(crate) fn write_csr(&mut self, csr: Csr, value: u64) -> Result<(), Error> {
+        match csr_address(csr) {
+            Some(address) => {
+                self.state.write_word(address, value);
+                self.tohost_written |= csr == Csr::HtifTohost;
+                Ok(())
+            }
+            None => Err(Error::InvalidArgument(format!("CSR {} is read-only", csr))),
+        }
+    }
+
+    pub(crate) fn read_register(&self, address: u64) -> u64 {
+        self.state.read_word(address)
+    }
+
+    pub(crate) fn write_register(&mut self, address: u64, value: u64) {
+        self.state.write_word(address, value);
+    }
+
+    fn iflags(&self) -> u64 {
+        self.read_csr(Csr::Iflags)
+    }
+
+    pub(crate) fn read_iflags(&self, bit: u64) -> bool {
+        self.iflags() & bit != 0
+    }
+
+    pub(crate) fn read_iflags_prv(&self) -> u64 {
+        (self.iflags() >> IFLAGS_PRV_SHIFT) & 3
+    }
+
+    pub(crate) fn set_iflags(&mut self, bit: u64, value: bool) {
+        let iflags = if value {
+            self.iflags() | bit
+        } else {
+            self.iflags() & !bit
+        };
+        self.state
+            .write_word(csr_address(Csr::Iflags).unwrap(), iflags);
+    }
+
+    /// Advance mcycle to `mcycle_end`, stopping early on halt or yield. Like the
+    /// emulator, resuming from an automatic yield resets iflags_X, and a yield
+    /// command written to tohost since the last run yields the machine
+    pub(crate) fn run(&mut self, mcycle_end: u64) -> Result<InterpreterBreakReason, Error> {
+        let mcycle = self.read_csr(Csr::Mcycle);
+        if mcycle_end < mcycle {
+            return Err(Error::InvalidArgument("mcycle is past".to_string()));
+        }
+        if self.read_iflags(IFLAGS_H) {
+            return Ok(InterpreterBreakReason::Halted);
+        }
+        if self.read_iflags(IFLAGS_Y) {
+            return Ok(InterpreterBreakReason::YieldedManually);
+        }
+        if mcycle == mcycle_end {
+            return Ok(InterpreterBreakReason::ReachedTargetMcycle);
+        }
+        self.set_iflags(IFLAGS_X, false);
+        if let Some(reason) = self.take_yield() {
+            return Ok(reason);
+        }
+        self.state
+            .write_word(csr_address(Csr::Mcycle).unwrap(), mcycle_end);
+        Ok(InterpreterBreakReason::ReachedTargetMcycle)
+    }
+
+    /// Serve a yield command written to tohost since the last run, as the HTIF
+    /// device does: acknowledge it in fromhost and set iflags_Y or iflags_X
+    fn take_yield(&mut self) -> Option<InterpreterBreakReason> {
+        if !std::mem::take(&mut self.tohost_written) {
+            return None;
+        }
+        let tohost = self.read_csr(Csr::HtifTohost);
+        let (device, cmd) = (tohost >> 56, (tohost >> 48) & 0xff);
+        let (flag, reason) = match (device, cmd) {
+            (HTIF_DEVICE_YIELD, HTIF_YIELD_MANUAL) => {
+                (IFLAGS_Y, InterpreterBreakReason::YieldedManually)
+            }
+            (HTIF_DEVICE_YIELD, HTIF_YIELD_AUTOMATIC) => {
+                (IFLAGS_X, InterpreterBreakReason::YieldedAutomatically)
+            }
+            _ => return None,
+        };
+        self.state.write_word(
+            csr_address(Csr::HtifFromhost).unwrap(),
+            (device << 56) | (cmd << 48),
+        );
+        self.set_iflags(flag, true);
+        Some(reason)
+    }
+
+    /// Run the uarch interpreter on the machine state until `uarch_cycle_end`
+    pub(crate) fn run_uarch(
+        &mut self,
+        uarch_cycle_end: u64,
+    ) -> Result<UarchInterpreterBreakReason, Error> {
+        while self.state.read_word(UARCH_CYCLE_ADDRESS) < uarch_cycle_end {
+            if uarch_step(&mut self.state)? == UarchStepStatus::UarchHalted {
+                return Ok(UarchInterpreterBreakReason::UarchHalted);
+            }
+        }
+        if self.state.read_word(UARCH_HALT_FLAG_ADDRESS) != 0 {
+            return Ok(UarchInterpreterBreakReason::UarchHalted);
+        }
+        Ok(UarchInterpreterBreakReason::ReachedTargetCycle)
+    }
+
+    /// Execute one uarch instruction, logging every state access
+    pub(crate) fn step_uarch(&mut self) -> Result<AccessLog, Error> {
+        let mut recorder = RecordingUarchState::new(&mut self.state);
+        uarch_step(&mut recorder)?;
+        Ok(recorder.into_log())
+    }
+
+    pub(crate) fn reset_uarch_state(&mut self) -> Result<(), Error> {
+        let config = self.config.clone();
+        self.state.write_word(UARCH_HALT_FLAG_ADDRESS, 0);
+        self.state.write_word(
+            UARCH_CYCLE_ADDRESS,
+            csr_config_value(&config, Csr::UarchCycle),
+        );
+        self.state
+            .write_word(UARCH_PC_ADDRESS, csr_config_value(&config, Csr::UarchPc));
+        for i in 0..32 {
+            self.state.write_word(UARCH_X0_ADDRESS + 8 * i, 0);
+        }
+        if let Some(ram) = uarch_ram(&config) {
+            self.load_image(
+                UARCH_RAM_START,
+                ram.length.unwrap_or_default(),
+                ram.image_filename.as_deref(),
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Shadow address of a CSR, if it has one
+pub(crate) fn csr_address(csr: Csr) -> Option<u64> {
+    match csr {
+        Csr::UarchPc => Some(UARCH_PC_ADDRESS),
+        Csr::UarchCycle => Some(UARCH_CYCLE_ADDRESS),
+        Csr::UarchHaltFlag => Some(UARCH_HALT_FLAG_ADDRESS),
+        Csr::UarchRamLength => None,
+        csr => Csr::ALL
+            .iter()
+            .position(|c| *c == csr)
+            .map(|index| PROCESSOR_CSR_ADDRESS + 8 * index as u64),
+    }
+}
@@ -0,0 +1,438 @@
+// Copyright (C) 2021 Cartesi Pte. Ltd.
+//
+// Licensed under the Apache License, Version 2.0 (the "License"); you may not use
+// this file except in compliance with the License. You may obtain a copy of the
+// License at http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software distributed
+// under the License is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR
+// CONDITIONS OF ANY KIND, either express or implied. See the License for the
+// specific language governing permissions and limitations under the License.
+
+//! In-process fake of the Cartesi machine JSON-RPC server
+//!
+//! Answers the methods of [`RemoteCartesiMachine`](crate::interfaces::RemoteCartesiMachine)
+//! over HTTP on a local port, so drivers built on
+//! [`JsonRpcCartesiMachineClient`](crate::client::JsonRpcCartesiMachineClient)
+//! can be tested without the emulator.
+//!
+//! The fake does not interpret RISC-V code: `machine.run` only advances
+//! `mcycle`, stopping early when the machine is halted or yielded. The
+//! microarchitecture is fully interpreted, so `machine.step_uarch` produces
+//! logs that verify against the fake root hashes. Stored machines are kept in
+//! memory and shared by a server and all its forks.
+
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::fmt;
+use std::net::SocketAddr;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+
+use jsonrpsee::server::{RpcModule, ServerBuilder, ServerHandle};
+use jsonrpsee::types::error::{CALL_EXECUTION_FAILED_CODE, INVALID_REQUEST_CODE};
+use jsonrpsee::types::{ErrorObject, ErrorObjectOwned};
+
+use crate::client::conversions::{decode_wire_base64, encode_wire_base64};
+use crate::client::uarch_step::{UARCH_HALT_FLAG_ADDRESS, UARCH_X0_ADDRESS};
+use crate::client::{AccessLog, Csr, Error, Hash};
+use crate::interfaces;
+
+mod machine;
+use machine::*;
+
+type RpcResult<T> = Result<T, ErrorObjectOwned>;
+
+fn server_error(err: impl fmt::Display) -> ErrorObjectOwned {
+    ErrorObject::owned(CALL_EXECUTION_FAILED_CODE, err.to_string(), None::<()>)
+}
+
+fn register_address(kind: &str, base: u64, index: u64) -> Result<u64, Error> {
+    if index >= 32 {
+        return Err(Error::InvalidArgument(format!(
+            "invalid {} register index {}",
+            kind, index
+        )));
+    }
+    Ok(base + 8 * index)
+}
+
+/// State shared by the RPC methods of one fake server
+struct ServerContext {
+    machine: Mutex<Option<FakeMachine>>,
+    stored: Arc<Mutex<HashMap<String, FakeMachine>>>,
+    handle: Mutex<Option<ServerHandle>>,
+}
+
+impl ServerContext {
+    fn with_machine<T>(
+        &self,
+        f: impl FnOnce(&mut FakeMachine) -> Result<T, Error>,
+    ) -> RpcResult<T> {
+        match self.machine.lock().unwrap().as_mut() {
+            Some(machine) => f(machine).map_err(server_error),
+            None => Err(ErrorObject::owned(
+                INVALID_REQUEST_CODE,
+                "no machine",
+                None::<()>,
+            )),
+        }
+    }
+}
+
+#[doc = " Fake Cartesi machine server listening on a local port"]
+#[doc = " \\details"]
+#[doc = " The server stops when dropped. Servers created through `fork` keep"]
+#[doc = " running until they receive `shutdown`."]
+pub struct FakeServer {
+    address: SocketAddr,
+    handle: ServerHandle,
+}
+
+impl FakeServer {
+    /// Start a fake server without a machine on an ephemeral local port
+    pub async fn start() -> Result<FakeServer, Error> {
+        FakeServer::start_at("127.0.0.1:0".parse().unwrap()).await
+    }
+
+    /// Start a fake server without a machine on `address`, for instance to restart
+    /// a stopped server in place
+    pub async fn start_at(address: SocketAddr) -> Result<FakeServer, Error> {
+        let (address, handle) = spawn(address, None, Default::default()).await?;
+        Ok(FakeServer { address, handle })
+    }
+
+    /// Address of the server, in the form returned by `fork`
+    pub fn address(&self) -> String {
+        self.address.to_string()
+    }
+
+    /// URI clients should connect to
+    pub fn uri(&self) -> String {
+        format!("http://{}", self.address)
+    }
+
+    /// Stop the server
+    pub fn stop(&self) {
+        let _ = self.handle.stop();
+    }
+
+    /// Wait until the server has stopped and released its port
+    pub async fn stopped(&self) {
+        self.handle.clone().stopped().await
+    }
+}
+
+impl Drop for FakeServer {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+async fn spawn(
+    address: SocketAddr,
+    machine: Option<FakeMachine>,
+    stored: Arc<Mutex<HashMap<String, FakeMachine>>>,
+) -> Result<(SocketAddr, ServerHandle), Error> {
+    let context = Arc::new(ServerContext {
+        machine: Mutex::new(machine),
+        stored,
+        handle: Mutex::new(None),
+    });
+    let server = ServerBuilder::default().build(address).await?;
+    let address = server.local_addr()?;
+    let handle = server.start(rpc_module(context.clone())?)?;
+    *context.handle.lock().unwrap() = Some(handle.clone());
+    Ok((address, handle))
+}
+
+fn rpc_module(context: Arc<ServerContext>) -> Result<RpcModule<Arc<ServerContext>>, Error> {
+    let mut module = RpcModule::new(context);
+
+    module.register_async_method("fork", |_, context| async move {
+        let machine = context.machine.lock().unwrap().clone();
+        let (address, _) = spawn(
+            "127.0.0.1:0".parse().unwrap(),
+            machine,
+            context.stored.clone(),
+        )
+        .await
+        .map_err(server_error)?;
+        RpcResult::Ok(address.to_string())
+    })?;
+    module.register_async_method("shutdown", |_, context| async move {
+        if let Some(handle) = context.handle.lock().unwrap().take() {
+            // Let the reply go out before the server stops listening
+            tokio::spawn(async move {
+                tokio::task::yield_now().await;
+                let _ = handle.stop();
+            });
+        }
+        RpcResult::Ok(true)
+    })?;
+    module.register_method("get_version", |_, _| {
+        RpcResult::Ok(interfaces::SemanticVersion {
+            major: 0,
+            minor: 1,
+            patch: 1,
+            pre_release: Some(String::new()),
+            build: Some(String::new()),
+        })
+    })?;
+
+    module.register_method("machine.machine.config", |params, context| {
+        let (config, _): (interfaces::MachineConfig, interfaces::MachineRuntimeConfig) =
+            params.parse()?;
+        let mut machine = context.machine.lock().unwrap();
+        if machine.is_some() {
+            return Err(server_error("machine exists"));
+        }
+        *machine = Some(FakeMachine::new(config).map_err(server_error)?);
+        Ok(true)
+    })?;
+    module.register_method("machine.machine.directory", |params, context| {
+        let (directory, _): (String, interfaces::MachineRuntimeConfig) = params.parse()?;
+        let mut machine = context.machine.lock().unwrap();
+        if machine.is_some() {
+            return Err(server_error("machine exists"));
+        }
+        let stored = context.stored.lock().unwrap().get(&directory).cloned();
+        *machine = Some(
+            stored
+                .ok_or_else(|| server_error(format!("unable to open directory {}", directory)))?,
+        );
+        Ok(true)
+    })?;
+    module.register_method("machine.destroy", |_, context| {
+        *context.machine.lock().unwrap() = None;
+        RpcResult::Ok(true)
+    })?;
+    module.register_method("machine.store", |params, context| {
+        let (directory,): (String,) = params.parse()?;
+        context.with_machine(|machine| {
+            let mut stored = context.stored.lock().unwrap();
+            if stored.contains_key(&directory) {
+                return Err(Error::InvalidArgument(format!(
+                    "directory {} already exists",
+                    directory
+                )));
+            }
+            stored.insert(directory, machine.clone());
+            Ok(true)
+        })
+    })?;
+    module.register_method("machine.run", |params, context| {
+        let (mcycle_end,): (u64,) = params.parse()?;
+        context.with_machine(|machine| machine.run(mcycle_end))
+    })?;
+    module.register_method("machine.run_uarch", |params, context| {
+        let (uarch_cycle_end,): (u64,) = params.parse()?;
+        context.with_machine(|machine| machine.run_uarch(uarch_cycle_end))
+    })?;
+    module.register_method("machine.step_uarch", |params, context| {
+        let (log_type, _): (interfaces::AccessLogType, bool) = params.parse()?;
+        context.with_machine(|machine| {
+            let mut log = interfaces::AccessLog::from(&machine.step_uarch()?);
+            if !log_type.has_proofs {
+                log.accesses
+                    .iter_mut()
+                    .for_each(|access| access.proof = None);
+            }
+            log.log_type = log_type;
+            Ok(log)
+        })
+    })?;
+    module.register_method("machine.verify_access_log", |params, _| {
+        let (log, _, _): (
+            interfaces::AccessLog,
+            interfaces::MachineRuntimeConfig,
+            bool,
+        ) = params.parse()?;
+        let log = AccessLog::try_from(&log).map_err(server_error)?;
+        let root_hash_before = log
+            .accesses
+            .first()
+            .map(|access| access.proof.root_hash)
+            .ok_or_else(|| server_error("access log is empty"))?;
+        log.verify_uarch_step(&root_hash_before)
+            .map_err(server_error)?;
+        RpcResult::Ok(true)
+    })?;
+    module.register_method("machine.verify_state_transition", |params, _| {
+        let (root_hash_before, log, root_hash_after, _, _): (
+            String,
+            interfaces::AccessLog,
+            String,
+            interfaces::MachineRuntimeConfig,
+            bool,
+        ) = params.parse()?;
+        let root_hash_before = Hash::from_wire(&root_hash_before).map_err(server_error)?;
+        let root_hash_after = Hash::from_wire(&root_hash_after).map_err(server_error)?;
+        AccessLog::try_from(&log)
+            .and_then(|log| log.verify_uarch_step_transition(&root_hash_before, &root_hash_after))
+            .map_err(server_error)?;
+        RpcResult::Ok(true)
+    })?;
+    module.register_method("machine.get_proof", |params, context| {
+        let (address, log2_size): (u64, u64) = params.parse()?;
+        context.with_machine(|machine| {
+            Ok(interfaces::Proof::from(&machine.proof(address, log2_size)?))
+        })
+    })?;
+    module.register_method("machine.get_root_hash", |_, context| {
+        context.with_machine(|machine| Ok(machine.root_hash().to_wire()))
+    })?;
+    module.register_method("machine.read_word", |params, context| {
+        let (address,): (u64,) = params.parse()?;
+        context.with_machine(|machine| machine.read_word(address))
+    })?;
+    for method in ["machine.read_memory", "machine.read_virtual_memory"] {
+        module.register_method(method, |params, context| {
+            let (address, length): (u64, u64) = params.parse()?;
+            context.with_machine(|machine| {
+                Ok(encode_wire_base64(&machine.read_memory(address, length)?))
+            })
+        })?;
+    }
+    for method in ["machine.write_memory", "machine.write_virtual_memory"] {
+        module.register_method(method, |params, context| {
+            let (address, data): (u64, String) = params.parse()?;
+            context.with_machine(|machine| {
+                machine.write_memory(address, &decode_wire_base64(&data)?);
+                Ok(true)
+            })
+        })?;
+    }
+    module.register_method("machine.replace_memory_range", |params, context| {
+        let (range,): (interfaces::MemoryRangeConfig,) = params.parse()?;
+        context.with_machine(|machine| {
+            machine.replace_memory_range(&range)?;
+            Ok(true)
+        })
+    })?;
+
+    module.register_method("machine.read_csr", |params, context| {
+        let (csr,): (String,) = params.parse()?;
+        context.with_machine(|machine| Ok(machine.read_csr(Csr::from_str(&csr)?)))
+    })?;
+    module.register_method("machine.write_csr", |params, context| {
+        let (csr, value): (String, u64) = params.parse()?;
+        context.with_machine(|machine| {
+            machine.write_csr(Csr::from_str(&csr)?, value)?;
+            Ok(true)
+        })
+    })?;
+    module.register_method("machine.get_csr_address", |params, context| {
+        let (csr,): (String,) = params.parse()?;
+        context.with_machine(|_| {
+            let csr = Csr::from_str(&csr)?;
+            csr_address(csr)
+                .ok_or_else(|| Error::InvalidArgument(format!("CSR {} has no address", csr)))
+        })
+    })?;
+
+    let registers = [
+        (
+            "x",
+            X_ADDRESS,
+            ["machine.read_x", "machine.write_x", "machine.get_x_address"],
+        ),
+        (
+            "f",
+            F_ADDRESS,
+            ["machine.read_f", "machine.write_f", "machine.get_f_address"],
+        ),
+        (
+            "uarch x",
+            UARCH_X0_ADDRESS,
+            [
+                "machine.read_uarch_x",
+                "machine.write_uarch_x",
+                "machine.get_uarch_x_address",
+            ],
+        ),
+    ];
+    for (kind, base, [read, write, get_address]) in registers {
+        module.register_method(read, move |params, context| {
+            let (index,): (u64,) = params.parse()?;
+            context.with_machine(|machine| {
+                Ok(machine.read_register(register_address(kind, base, index)?))
+            })
+        })?;
+        module.register_method(write, move |params, context| {
+            let (index, value): (u64, u64) = params.parse()?;
+            context.with_machine(|machine| {
+                machine.write_register(register_address(kind, base, index)?, value);
+                Ok(true)
+            })
+        })?;
+        module.register_method(get_address, move |params, context| {
+            let (index,): (u64,) = params.parse()?;
+            context.with_machine(|_| register_address(kind, base, index))
+        })?;
+    }
+
+    let iflags = [
+        (IFLAGS_Y, "machine.set_iflags_Y", "machine.read_iflags_Y"),
+        (IFLAGS_X, "machine.set_iflags_X", "machine.read_iflags_X"),
+        (IFLAGS_H, "machine.set_iflags_H", "machine.read_iflags_H"),
+    ];
+    for (bit, set, read) in iflags {
+        module.register_method(set, move |_, context| {
+            context.with_machine(|machine| {
+                machine.set_iflags(bit, true);
+                Ok(true)
+            })
+        })?;
+        module.register_method(read, move |_, context| {
+            context.with_machine(|machine| Ok(machine.read_iflags(bit)))
+        })?;
+    }
+    // Only the yield flags can be cleared
+    let resets = [
+        (IFLAGS_Y, "machine.reset_iflags_Y"),
+        (IFLAGS_X, "machine.reset_iflags_X"),
+    ];
+    for (bit, reset) in resets {
+        module.register_method(reset, move |_, context| {
+            context.with_machine(|machine| {
+                machine.set_iflags(bit, false);
+                Ok(true)
+            })
+        })?;
+    }
+    module.register_method("machine.read_iflags_PRV", |_, context| {
+        context.with_machine(|machine| Ok(machine.read_iflags_prv()))
+    })?;
+    module.register_method("machine.set_uarch_halt_flag", |_, context| {
+        context.with_machine(|machine| {
+            machine.write_register(UARCH_HALT_FLAG_ADDRESS, 1);
+            Ok(true)
+        })
+    })?;
+    module.register_method("machine.read_uarch_halt_flag", |_, context| {
+        context.with_machine(|machine| Ok(machine.read_register(UARCH_HALT_FLAG_ADDRESS) != 0))
+    })?;
+    module.register_method("machine.reset_uarch_state", |_, context| {
+        context.with_machine(|machine| {
+            machine.reset_uarch_state()?;
+            Ok(true)
+        })
+    })?;
+
+    module.register_method("machine.get_initial_config", |_, context| {
+        context.with_machine(|machine| Ok(machine.initial_config().clone()))
+    })?;
+    module.register_method("machine.get_default_config", |_, _| {
+        RpcResult::Ok(default_config())
+    })?;
+    for method in [
+        "machine.verify_merkle_tree",
+        "machine.verify_dirty_page_maps",
+        "machine.dump_pmas",
+    ] {
+        module.register_method(method, |_, context| context.with_machine(|_| Ok(true)))?;
+    }
+
+    Ok(module)
+}
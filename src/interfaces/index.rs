@@ -313,8 +313,26 @@ pub struct SemanticVersion {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub pre_release: Option<StringDoaGddGA>,
 }
-pub type InterpreterBreakReason = serde_json::Value;
-pub type UarchInterpreterBreakReason = serde_json::Value;
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum InterpreterBreakReason {
+    Failed,
+    Halted,
+    YieldedManually,
+    YieldedAutomatically,
+    ReachedTargetMcycle,
+    #[serde(other)]
+    Unknown,
+}
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum UarchInterpreterBreakReason {
+    ReachedTargetCycle,
+    #[serde(alias = "halted")]
+    UarchHalted,
+    #[serde(other)]
+    Unknown,
+}
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
 #[serde(untagged)]
 pub enum AnyOfMachineConfigMachineRuntimeConfigStringDoaGddGAMachineRuntimeConfigStringDoaGddGAUnsignedIntegerUnsignedIntegerAccessLogTypeBooleanVyG3AEThAccessLogMachineRuntimeConfigBooleanVyG3AEThAccessLogAccessLogAccessLogMachineRuntimeConfigBooleanVyG3AEThUnsignedIntegerUnsignedIntegerUnsignedIntegerUnsignedIntegerUnsignedIntegerUnsignedIntegerUnsignedIntegerUnsignedIntegerBase64StringUnsignedIntegerUnsignedIntegerUnsignedIntegerBase64StringMemoryRangeConfigCSRCSRUnsignedIntegerCSRUnsignedIntegerUnsignedIntegerUnsignedIntegerUnsignedIntegerUnsignedIntegerUnsignedIntegerUnsignedIntegerUnsignedIntegerUnsignedIntegerUnsignedIntegerUnsignedIntegerUnsignedIntegerStringDoaGddGABooleanVyG3AEThSemanticVersionBooleanVyG3AEThBooleanVyG3AEThBooleanVyG3AEThBooleanVyG3AEThInterpreterBreakReasonUarchInterpreterBreakReasonAccessLogBooleanVyG3AEThBooleanVyG3AEThProofBase64HashProofUnsignedIntegerBase64StringBooleanVyG3AEThBase64StringBooleanVyG3AEThBooleanVyG3AEThUnsignedIntegerBooleanVyG3AEThUnsignedIntegerUnsignedIntegerUnsignedIntegerUnsignedIntegerBooleanVyG3AEThBooleanVyG3AEThBooleanVyG3AEThUnsignedIntegerUnsignedIntegerUnsignedIntegerBooleanVyG3AEThBooleanVyG3AEThBooleanVyG3AEThBooleanVyG3AEThBooleanVyG3AEThBooleanVyG3AEThBooleanVyG3AEThBooleanVyG3AEThUnsignedIntegerBooleanVyG3AEThBooleanVyG3AEThBooleanVyG3AEThMachineConfigMachineConfigBooleanVyG3AEThBooleanVyG3AEThBooleanVyG3AETh
@@ -351,10 +369,13 @@ where
         }
     }
 
+    pub fn transport(&self) -> &T {
+        &self.transport
+    }
+
     pub async fn CheckConnection<'a>(&'a self) -> Result<(), Error> {
-        let method = "";
-        let params = ArrayParams::new();
-        self.transport.request(method, params).await
+        // The server has no dedicated health method, any get_version reply proves it is serving
+        self.GetVersion().await.map(|_| ())
     }
 
     pub async fn Fork<'a>(&'a self) -> Result<String, Error> {
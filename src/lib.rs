@@ -1,2 +1,4 @@
 pub mod client;
+#[cfg(feature = "fake-server")]
+pub mod fake;
 pub mod interfaces;
@@ -0,0 +1,144 @@
+// Copyright (C) 2021 Cartesi Pte. Ltd.
+//
+// Licensed under the Apache License, Version 2.0 (the "License"); you may not use
+// this file except in compliance with the License. You may obtain a copy of the
+// License at http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software distributed
+// under the License is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR
+// CONDITIONS OF ANY KIND, either express or implied. See the License for the
+// specific language governing permissions and limitations under the License.
+
+use cartesi_machine_json_rpc::client::merkle::*;
+use cartesi_machine_json_rpc::client::*;
+use rstest::*;
+
+/// Record a word access against `tree`, applying it when writing
+fn access(tree: &mut MerkleTree, address: u64, written: Option<u64>) -> Access {
+    let proof = tree.proof(address, LOG2_WORD_SIZE).unwrap();
+    let read_data = tree.read(address, 8);
+    let (r#type, written_data) = match written {
+        Some(value) => {
+            tree.write_word(address, value);
+            (AccessType::Write, value.to_le_bytes().to_vec())
+        }
+        None => (AccessType::Read, Vec::new()),
+    };
+    Access {
+        r#type,
+        address,
+        log2_size: LOG2_WORD_SIZE as i32,
+        read_data,
+        written_data,
+        proof,
+    }
+}
+
+/// Log of a few reads and writes, with the root hashes before and after it
+#[fixture]
+fn transition() -> (Hash, AccessLog, Hash) {
+    let mut tree = MerkleTree::new();
+    tree.write_word(0x1000, 7);
+    tree.write_word(0x80000000, 0xdeadbeef);
+    let root_hash_before = tree.root_hash();
+    let accesses = vec![
+        access(&mut tree, 0x1000, None),
+        access(&mut tree, 0x1008, Some(42)),
+        access(&mut tree, 0x80000000, Some(0)),
+        access(&mut tree, 0x1008, None),
+    ];
+    let log = AccessLog {
+        accesses,
+        brackets: Vec::new(),
+        notes: Vec::new(),
+        log_type: AccessLogType {
+            proofs: true,
+            annotations: false,
+        },
+    };
+    (root_hash_before, log, tree.root_hash())
+}
+
+#[test]
+fn test_merkle_tree_pristine_root() {
+    let tree = MerkleTree::new();
+    assert_eq!(tree.root_hash(), tree.pristine_hash(LOG2_ROOT_SIZE));
+    assert_eq!(tree.node_hash(0, 5), merkle_root_hash(&[0u8; 32]).unwrap());
+}
+
+#[test]
+fn test_merkle_tree_proof() {
+    let mut tree = MerkleTree::new();
+    tree.write_word(0x18, 3);
+    tree.write(0x20, &[1, 2, 3]);
+    assert_eq!(tree.read_word(0x18), 3);
+    assert_eq!(tree.read(0x1f, 3), vec![0, 1, 2]);
+    let proof = tree.proof(0x18, LOG2_WORD_SIZE).unwrap();
+    assert_eq!(proof.root_hash, tree.root_hash());
+    assert!(proof.verify());
+    assert!(matches!(
+        tree.proof(0x1c, LOG2_WORD_SIZE),
+        Err(Error::InvalidArgument(_))
+    ));
+}
+
+#[rstest]
+fn test_verify_local(transition: (Hash, AccessLog, Hash)) {
+    let (root_hash_before, log, root_hash_after) = transition;
+    assert_eq!(
+        log.verify_local(&root_hash_before).unwrap(),
+        root_hash_after
+    );
+    log.verify_state_transition_local(&root_hash_before, &root_hash_after)
+        .unwrap();
+}
+
+#[rstest]
+fn test_verify_local_wrong_root_hash_before(transition: (Hash, AccessLog, Hash)) {
+    let (_, log, root_hash_after) = transition;
+    assert!(matches!(
+        log.verify_local(&root_hash_after),
+        Err(Error::InconsistentAccess { index: 0, .. })
+    ));
+}
+
+#[rstest]
+fn test_verify_local_tampered_read(transition: (Hash, AccessLog, Hash)) {
+    let (root_hash_before, mut log, _) = transition;
+    log.accesses[3].read_data = 43u64.to_le_bytes().to_vec();
+    assert!(matches!(
+        log.verify_local(&root_hash_before),
+        Err(Error::InconsistentAccess { index: 3, .. })
+    ));
+}
+
+#[rstest]
+fn test_verify_local_tampered_write(transition: (Hash, AccessLog, Hash)) {
+    let (root_hash_before, mut log, _) = transition;
+    log.accesses[1].written_data = 43u64.to_le_bytes().to_vec();
+    assert!(matches!(
+        log.verify_local(&root_hash_before),
+        Err(Error::InconsistentAccess { index: 2, .. })
+    ));
+}
+
+#[rstest]
+fn test_verify_state_transition_local_mismatch(transition: (Hash, AccessLog, Hash)) {
+    let (root_hash_before, mut log, root_hash_after) = transition;
+    log.accesses.pop();
+    log.accesses.pop();
+    match log.verify_state_transition_local(&root_hash_before, &root_hash_after) {
+        Err(Error::RootHashMismatch { expected, .. }) => assert_eq!(expected, root_hash_after),
+        other => panic!("unexpected result {:?}", other),
+    }
+}
+
+#[rstest]
+fn test_verify_local_without_proofs(transition: (Hash, AccessLog, Hash)) {
+    let (root_hash_before, mut log, _) = transition;
+    log.log_type.proofs = false;
+    assert!(matches!(
+        log.verify_local(&root_hash_before),
+        Err(Error::InvalidArgument(_))
+    ));
+}
@@ -0,0 +1,61 @@
+// Copyright (C) 2021 Cartesi Pte. Ltd.
+//
+// Licensed under the Apache License, Version 2.0 (the "License"); you may not use
+// this file except in compliance with the License. You may obtain a copy of the
+// License at http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software distributed
+// under the License is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR
+// CONDITIONS OF ANY KIND, either express or implied. See the License for the
+// specific language governing permissions and limitations under the License.
+
+use cartesi_machine_json_rpc::client::*;
+use rstest::*;
+
+#[rstest]
+#[case("\"failed\"", InterpreterBreakReason::Failed)]
+#[case("\"halted\"", InterpreterBreakReason::Halted)]
+#[case("\"yielded_manually\"", InterpreterBreakReason::YieldedManually)]
+#[case(
+    "\"yielded_automatically\"",
+    InterpreterBreakReason::YieldedAutomatically
+)]
+#[case(
+    "\"reached_target_mcycle\"",
+    InterpreterBreakReason::ReachedTargetMcycle
+)]
+#[case("\"something_new\"", InterpreterBreakReason::Unknown)]
+fn test_interpreter_break_reason_from_wire(
+    #[case] wire: &str,
+    #[case] expected: InterpreterBreakReason,
+) {
+    let reason: InterpreterBreakReason = serde_json::from_str(wire).unwrap();
+    assert_eq!(reason, expected);
+    if expected != InterpreterBreakReason::Unknown {
+        assert_eq!(format!("\"{}\"", reason), wire);
+    }
+}
+
+#[rstest]
+#[case(
+    "\"reached_target_cycle\"",
+    UarchInterpreterBreakReason::ReachedTargetCycle
+)]
+#[case("\"uarch_halted\"", UarchInterpreterBreakReason::UarchHalted)]
+#[case("\"halted\"", UarchInterpreterBreakReason::UarchHalted)]
+#[case("\"something_new\"", UarchInterpreterBreakReason::Unknown)]
+fn test_uarch_interpreter_break_reason_from_wire(
+    #[case] wire: &str,
+    #[case] expected: UarchInterpreterBreakReason,
+) {
+    let reason: UarchInterpreterBreakReason = serde_json::from_str(wire).unwrap();
+    assert_eq!(reason, expected);
+}
+
+#[test]
+fn test_break_reason_is_yield() {
+    assert!(InterpreterBreakReason::YieldedManually.is_yield());
+    assert!(InterpreterBreakReason::YieldedAutomatically.is_yield());
+    assert!(!InterpreterBreakReason::Halted.is_yield());
+    assert!(!InterpreterBreakReason::ReachedTargetMcycle.is_yield());
+}
@@ -27,14 +27,6 @@ static SECOND_STEP_HASH: [u8; 32] = [
     134, 195, 184, 149, 206, 26, 29, 81, 127, 11, 29, 192,
 ];
 
-#[allow(dead_code)]
-struct Context {
-    cartesi_machine_server: JsonRpcCartesiMachineClient,
-    server_ip: String,
-    port: u32,
-    container_name: String,
-}
-
 fn generate_random_name() -> String {
     thread_rng()
         .sample_iter(&Alphanumeric)
@@ -43,55 +35,26 @@ fn generate_random_name() -> String {
         .collect()
 }
 
-fn instantiate_external_server_instance(
-    name: &str,
-    port: u32,
-) -> Result<(), Box<dyn std::error::Error>> {
-    let address = format!("127.0.0.1:{0}", port);
-    let server_address = format!("--server-address=127.0.0.1:{0}", port);
-
-    println!(
-        "Starting Cartesi jsonrpc remote machine on address {}",
-        address
-    );
-    match std::process::Command::new("/bin/bash")
-        .stdout(std::process::Stdio::null())
-        .stderr(std::process::Stdio::null())
-        .arg("-c")
-        .arg(format!(
-            "exec -a {} /usr/bin/jsonrpc-remote-cartesi-machine {}",
-            name, server_address
-        ))
-        .spawn()
-    {
-        Ok(_child) => {}
-        Err(error) => panic!("{}", error.to_string()),
-    };
-    std::thread::sleep(std::time::Duration::from_secs(2));
-    Ok(())
-}
-
-fn try_stop_container(name: &str) {
-    let result = std::process::Command::new("pkill")
-        .arg("-f")
-        .arg(format!("{}", name))
-        .status()
-        .unwrap();
-    if !result.success() {
-        eprint!("Error stopping container");
-    }
+struct Context {
+    server: ServerProcess,
 }
 
 impl Context {
     pub fn get_server(&self) -> &JsonRpcCartesiMachineClient {
-        &self.cartesi_machine_server
+        self.server.client()
     }
 }
 
-impl Drop for Context {
-    fn drop(&mut self) {
-        println!("Destroying container {}", &self.container_name);
-        try_stop_container(&self.container_name);
+async fn start_server() -> Context {
+    match ServerProcess::start().await {
+        Ok(server) => {
+            println!(
+                "Started jsonrpc cartesi machine server at {}",
+                server.address()
+            );
+            Context { server }
+        }
+        Err(err) => panic!("Unable to start machine server: {}", err.to_string()),
     }
 }
 
@@ -101,67 +64,12 @@ mod local_server {
 
     #[fixture]
     async fn context_future() -> Context {
-        let server_ip = "127.0.0.1".to_string();
-        let port: u32 = rand::thread_rng().gen_range(49152..65535);
-        let uri = format!("http://{}:{}", server_ip, port);
-        let container_name = generate_random_name();
-
-        match instantiate_external_server_instance(&container_name, port) {
-            Ok(_) => (),
-            Err(ex) => eprint!(
-                "Error instantiating cartesi machine server {}",
-                ex.to_string()
-            ),
-        }
-        println!(
-            "Starting jsonrpc machine server: {} server_ip:{}:{} ",
-            container_name, server_ip, port
-        );
-
-        Context {
-            cartesi_machine_server: match JsonRpcCartesiMachineClient::new(uri).await {
-                Ok(machine) => machine,
-                Err(err) => {
-                    panic!("Unable to create machine server: {}", err.to_string())
-                }
-            },
-            port,
-            server_ip,
-            container_name,
-        }
+        start_server().await
     }
 
     #[fixture]
     async fn context_with_machine_future() -> Context {
-        let server_ip = "127.0.0.1".to_string();
-        let port: u32 = rand::thread_rng().gen_range(49152..65535);
-        let uri = format!("http://{}:{}", server_ip, port);
-        let container_name = generate_random_name();
-        match instantiate_external_server_instance(&container_name, port) {
-            Ok(_) => (),
-            Err(err) => eprint!(
-                "Error instantiating jsonrpc cartesi machine server {}",
-                err.to_string()
-            ),
-        }
-        println!(
-            "Starting jsonrpc cartesi server: {} server_ip:{}:{} ",
-            container_name, server_ip, port
-        );
-        let context = Context {
-            cartesi_machine_server: match JsonRpcCartesiMachineClient::new(uri).await {
-                Ok(machine) => machine,
-                Err(err) => {
-                    panic!(
-                        "Unable to create jsonrpc machine server: {}",
-                        err.to_string()
-                    )
-                }
-            },
-            port,
-            server_ip,
-            container_name,
-        };
+        let context = start_server().await;
         //Modify default configuration
         let mut default_config = match context.get_server().get_default_config().await {
             Ok(config) => config,
@@ -244,32 +152,7 @@ mod local_server {
 
     #[fixture]
     async fn context_with_machine_with_flash_future() -> Context {
-        let server_ip = "127.0.0.1".to_string();
-        let port: u32 = rand::thread_rng().gen_range(49152..65535);
-        let uri = format!("http://{}:{}", server_ip, port);
-        let container_name = generate_random_name();
-        match instantiate_external_server_instance(&container_name, port) {
-            Ok(_) => (),
-            Err(err) => eprint!(
-                "Error instantiating jsonrpc cartesi machine server {}",
-                err.to_string()
-            ),
-        }
-        println!(
-            "Starting jsonrpc cartesi server: {} server_ip:{}:{} ",
-            container_name, server_ip, port
-        );
-        let context = Context {
-            cartesi_machine_server: match JsonRpcCartesiMachineClient::new(uri).await {
-                Ok(machine) => machine,
-                Err(err) => {
-                    panic!("Unable to create machine server: {}", err.to_string())
-                }
-            },
-            port,
-            server_ip,
-            container_name,
-        };
+        let context = start_server().await;
         //Modify default configuration
         let mut default_config = match context.get_server().get_default_config().await {
             Ok(config) => config,
@@ -363,22 +246,10 @@ mod local_server {
     #[tokio::test]
     #[should_panic]
     async fn test_invalid_server_address() -> () {
-        let server_ip = "127.0.0.1".to_string();
-        let port: u32 = 12345;
-        let uri = format!("http://{}:{}", server_ip, port);
-        let container_name = generate_random_name();
-        let _context = Context {
-            cartesi_machine_server: match JsonRpcCartesiMachineClient::new(uri).await {
-                Ok(machine) => machine,
-                Err(err) => {
-                    panic!("Unable to create machine server: {}", err.to_string())
-                }
-            },
-            port,
-            server_ip,
-            container_name,
-        };
-        ()
+        let uri = "http://127.0.0.1:12345".to_string();
+        if let Err(err) = JsonRpcCartesiMachineClient::new(uri).await {
+            panic!("Unable to create machine server: {}", err.to_string())
+        }
     }
 
     #[rstest]
@@ -388,8 +259,8 @@ mod local_server {
     ) -> Result<(), Box<dyn std::error::Error>> {
         let context = context_future.await;
         println!(
-            "Sleeping in the test... context container name: {}",
-            context.container_name
+            "Sleeping in the test... server address: {}",
+            context.server.address()
         );
         std::thread::sleep(std::time::Duration::from_secs(5));
         println!("End sleeping");
@@ -587,7 +458,7 @@ mod local_server {
     ) -> Result<(), Box<dyn std::error::Error>> {
         let context = context_with_machine_future.await;
         let run_response = context.get_server().run(1000).await?;
-        assert_eq!(run_response, "reached_target_mcycle");
+        assert_eq!(run_response, InterpreterBreakReason::ReachedTargetMcycle);
 
         Ok(())
     }
@@ -638,6 +509,47 @@ mod local_server {
         Ok(())
     }
 
+    #[rstest]
+    #[tokio::test]
+    async fn test_fork_client(
+        context_with_machine_future: impl Future<Output = Context>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let context = context_with_machine_future.await;
+        let child = context.get_server().fork_client().await?;
+        assert_eq!(
+            &child.parent().unwrap().address,
+            context.get_server().get_address()
+        );
+        assert_eq!(
+            child.get_root_hash().await?,
+            context.get_server().get_root_hash().await?
+        );
+        let child_address = child.get_address().clone();
+        drop(child);
+        tokio::time::sleep(std::time::Duration::from_secs(1)).await;
+        assert!(JsonRpcCartesiMachineClient::new(child_address)
+            .await
+            .is_err());
+        Ok(())
+    }
+
+    #[rstest]
+    #[tokio::test]
+    async fn test_fork_client_keep_alive(
+        context_with_machine_future: impl Future<Output = Context>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let context = context_with_machine_future.await;
+        let child = context.get_server().fork_client().await?;
+        child.set_shutdown_on_drop(false);
+        let child_address = child.get_address().clone();
+        drop(child);
+        tokio::time::sleep(std::time::Duration::from_secs(1)).await;
+        let child = JsonRpcCartesiMachineClient::new(child_address).await?;
+        assert!(child.parent().is_none());
+        child.shutdown().await?;
+        Ok(())
+    }
+
     #[rstest]
     #[tokio::test]
     async fn test_step(
@@ -716,6 +628,40 @@ mod local_server {
         Ok(())
     }
 
+    #[rstest]
+    #[tokio::test]
+    async fn test_read_virtual_memory(
+        context_with_machine_future: impl Future<Output = Context>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let context = context_with_machine_future.await;
+        let ret = context.get_server().read_virtual_memory(0x1000, 16).await?;
+        assert_eq!(
+            ret,
+            vec![151, 2, 0, 0, 147, 130, 162, 4, 115, 144, 82, 48, 65, 101, 189, 101]
+        );
+        Ok(())
+    }
+
+    #[rstest]
+    #[tokio::test]
+    async fn test_write_virtual_memory(
+        context_with_machine_future: impl Future<Output = Context>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let context = context_with_machine_future.await;
+        context
+            .get_server()
+            .write_virtual_memory(0x8000000F, &[1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12])
+            .await?;
+        let ret = context
+            .get_server()
+            .read_virtual_memory(0x8000000F, 12)
+            .await?;
+        assert_eq!(ret, vec![1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12]);
+        let ret = context.get_server().read_memory(0x8000000F, 12).await?;
+        assert_eq!(ret, vec![1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12]);
+        Ok(())
+    }
+
     #[rstest]
     #[tokio::test]
     async fn test_read_word(
@@ -769,18 +715,16 @@ mod local_server {
         let context = context_with_machine_future.await;
         let proof = context.get_server().get_proof(0x0, 10).await?;
         assert_eq!(proof.log2_target_size, 10);
-        let mut target_hash_string = proof.target_hash.clone();
-        if target_hash_string.ends_with('\n') {
-            target_hash_string.pop();
-        }
         assert_eq!(
-            STANDARD.decode(target_hash_string).unwrap(),
+            proof.target_hash,
             [
                 35, 254, 2, 79, 37, 221, 81, 35, 248, 37, 161, 169, 94, 207, 252, 232, 112, 88,
                 158, 205, 25, 69, 157, 16, 32, 131, 238, 33, 140, 15, 0, 174
             ]
         );
         assert_eq!(proof.sibling_hashes.len(), 54);
+        assert!(proof.verify());
+        assert_eq!(proof.root_hash, context.get_server().get_root_hash().await?);
         Ok(())
     }
 
@@ -842,6 +786,71 @@ mod local_server {
         Ok(())
     }
 
+    #[rstest]
+    #[tokio::test]
+    async fn test_register_index_out_of_range(
+        context_with_machine_future: impl Future<Output = Context>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let context = context_with_machine_future.await;
+        let ret = context.get_server().read_x(32).await;
+        assert!(matches!(ret, Err(Error::InvalidArgument(_))));
+        let ret = context.get_server().write_f(32, 0x1234).await;
+        assert!(matches!(ret, Err(Error::InvalidArgument(_))));
+        let ret = context.get_server().get_uarch_x_address(32).await;
+        assert!(matches!(ret, Err(Error::InvalidArgument(_))));
+        Ok(())
+    }
+
+    #[rstest]
+    #[tokio::test]
+    async fn test_get_f_address(
+        context_future: impl Future<Output = Context>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let context = context_future.await;
+        let f_address = context.get_server().get_f_address(2).await?;
+        assert_eq!(f_address, 0x110);
+        Ok(())
+    }
+
+    #[rstest]
+    #[tokio::test]
+    async fn test_read_write_f(
+        context_with_machine_future: impl Future<Output = Context>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let context = context_with_machine_future.await;
+        let f_value = context.get_server().read_f(2).await?;
+        assert_eq!(f_value, 0x0);
+        context.get_server().write_f(2, 0x1234).await?;
+        let f_value = context.get_server().read_f(2).await?;
+        assert_eq!(f_value, 0x1234);
+        Ok(())
+    }
+
+    #[rstest]
+    #[tokio::test]
+    async fn test_get_uarch_x_address(
+        context_future: impl Future<Output = Context>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let context = context_future.await;
+        let x_address = context.get_server().get_uarch_x_address(2).await?;
+        assert_eq!(x_address, 0x400008028);
+        Ok(())
+    }
+
+    #[rstest]
+    #[tokio::test]
+    async fn test_read_write_uarch_x(
+        context_with_machine_future: impl Future<Output = Context>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let context = context_with_machine_future.await;
+        let x_value = context.get_server().read_uarch_x(2).await?;
+        assert_eq!(x_value, 0x0);
+        context.get_server().write_uarch_x(2, 0x1234).await?;
+        let x_value = context.get_server().read_uarch_x(2).await?;
+        assert_eq!(x_value, 0x1234);
+        Ok(())
+    }
+
     #[rstest]
     #[tokio::test]
     async fn test_reset_i_flags_y(
@@ -852,16 +861,59 @@ mod local_server {
         Ok(())
     }
 
+    #[rstest]
+    #[tokio::test]
+    async fn test_set_reset_iflags(
+        context_with_machine_future: impl Future<Output = Context>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let context = context_with_machine_future.await;
+        let server = context.get_server();
+        assert!(!server.read_iflags_x().await?);
+        server.set_iflags_x().await?;
+        assert!(server.read_iflags_x().await?);
+        server.reset_iflags_x().await?;
+        assert!(!server.read_iflags_x().await?);
+        server.set_iflags_y().await?;
+        assert!(server.read_iflags_y().await?);
+        server.set_iflags_h().await?;
+        assert!(server.read_iflags_h().await?);
+        let iflags = server.read_iflags().await?;
+        assert!(iflags.h && iflags.y && !iflags.x);
+        Ok(())
+    }
+
+    #[rstest]
+    #[tokio::test]
+    async fn test_read_iflags_prv(
+        context_with_machine_future: impl Future<Output = Context>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let context = context_with_machine_future.await;
+        let prv = context.get_server().read_iflags_prv().await?;
+        assert_eq!(prv, PrivilegeLevel::Machine);
+        let iflags = context.get_server().read_iflags().await?;
+        assert_eq!(iflags.prv, PrivilegeLevel::Machine);
+        Ok(())
+    }
+
+    #[rstest]
+    #[tokio::test]
+    async fn test_set_uarch_halt_flag(
+        context_with_machine_future: impl Future<Output = Context>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let context = context_with_machine_future.await;
+        assert!(!context.get_server().read_uarch_halt_flag().await?);
+        context.get_server().set_uarch_halt_flag().await?;
+        assert!(context.get_server().read_uarch_halt_flag().await?);
+        Ok(())
+    }
+
     #[rstest]
     #[tokio::test]
     async fn test_get_csr_address(
         context_future: impl Future<Output = Context>,
     ) -> Result<(), Box<dyn std::error::Error>> {
         let context = context_future.await;
-        let address = context
-            .get_server()
-            .get_csr_address("pc".to_string())
-            .await?;
+        let address = context.get_server().get_csr_address(Csr::Pc).await?;
         println!("Got address: {}", address);
         Ok(())
     }
@@ -872,23 +924,30 @@ mod local_server {
         context_with_machine_future: impl Future<Output = Context>,
     ) -> Result<(), Box<dyn std::error::Error>> {
         let context = context_with_machine_future.await;
-        let x_value = context
-            .get_server()
-            .read_csr("sscratch".to_string())
-            .await?;
+        let x_value = context.get_server().read_csr(Csr::Sscratch).await?;
         assert_eq!(x_value, 0x0);
         context
             .get_server()
-            .write_csr("sscratch".to_string(), 0x12345)
-            .await?;
-        let x_value = context
-            .get_server()
-            .read_csr("sscratch".to_string())
+            .write_csr(Csr::Sscratch, 0x12345)
             .await?;
+        let x_value = context.get_server().read_csr(Csr::Sscratch).await?;
         assert_eq!(x_value, 0x12345);
         Ok(())
     }
 
+    #[rstest]
+    #[tokio::test]
+    async fn test_read_all_csrs(
+        context_with_machine_future: impl Future<Output = Context>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let context = context_with_machine_future.await;
+        let csrs = context.get_server().read_all_csrs().await?;
+        assert_eq!(csrs.len(), Csr::ALL.len());
+        assert_eq!(csrs[&Csr::Pc], 4096);
+        assert_eq!(csrs[&Csr::UarchPc], 0x70000000);
+        Ok(())
+    }
+
     #[rstest]
     #[tokio::test]
     async fn test_get_initial_config(
@@ -999,9 +1058,9 @@ mod local_server {
         context
             .get_server()
             .verify_state_transition(
-                root_hash_before.to_vec(),
+                &root_hash_before,
                 &log,
-                root_hash_after.to_vec(),
+                &root_hash_after,
                 false,
                 &MachineRuntimeConfig::default(),
             )
@@ -1009,6 +1068,78 @@ mod local_server {
         Ok(())
     }
 
+    #[rstest]
+    #[tokio::test]
+    async fn test_verify_state_transition_local(
+        context_with_machine_future: impl Future<Output = Context>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let context = context_with_machine_future.await;
+        let root_hash_before = context.get_server().get_root_hash().await?;
+        let log = context
+            .get_server()
+            .step(
+                &AccessLogType {
+                    annotations: false,
+                    proofs: true,
+                },
+                false,
+            )
+            .await?;
+        let root_hash_after = context.get_server().get_root_hash().await?;
+        log.verify_state_transition_local(&root_hash_before, &root_hash_after)?;
+        Ok(())
+    }
+
+    #[rstest]
+    #[tokio::test]
+    async fn test_verify_uarch_step(
+        context_with_machine_future: impl Future<Output = Context>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let context = context_with_machine_future.await;
+        let root_hash_before = context.get_server().get_root_hash().await?;
+        let log = context
+            .get_server()
+            .step(
+                &AccessLogType {
+                    annotations: false,
+                    proofs: true,
+                },
+                false,
+            )
+            .await?;
+        let root_hash_after = context.get_server().get_root_hash().await?;
+        log.verify_uarch_step_transition(&root_hash_before, &root_hash_after)?;
+        Ok(())
+    }
+
+    #[rstest]
+    #[tokio::test]
+    async fn test_replay_halting_uarch_steps(
+        context_with_machine_future: impl Future<Output = Context>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let context = context_with_machine_future.await;
+        let server = context.get_server();
+        // addi a7, x0, 1; ecall, which halts the microarchitecture
+        let program = [0x93, 0x08, 0x10, 0x00, 0x73, 0x00, 0x00, 0x00];
+        server
+            .write_memory(0x70000000, STANDARD.encode(program))
+            .await?;
+        let log_type = AccessLogType {
+            annotations: false,
+            proofs: true,
+        };
+        // Logs come from the emulator and are replayed by the Rust interpreter
+        for _ in 0..2 {
+            let root_hash_before = server.get_root_hash().await?;
+            let log = server.step(&log_type, false).await?;
+            let root_hash_after = server.get_root_hash().await?;
+            log.verify_uarch_step_transition(&root_hash_before, &root_hash_after)?;
+        }
+        assert!(server.read_uarch_halt_flag().await?);
+        assert_eq!(server.read_csr(Csr::UarchPc).await?, 0x70000008);
+        Ok(())
+    }
+
     #[rstest]
     #[tokio::test]
     async fn test_sequential_requests(
@@ -1089,7 +1220,7 @@ mod local_server {
             .create_machine(&default_config, &MachineRuntimeConfig::default())
             .await?;
 
-        let csr_addr = jsonrpc_machine.read_csr("mcycle".to_string()).await?;
+        let csr_addr = jsonrpc_machine.read_csr(Csr::Mcycle).await?;
         println!("I got csr address of mcycle reg: {}", csr_addr);
 
         let hash = jsonrpc_machine.get_root_hash().await?;
@@ -0,0 +1,87 @@
+// Copyright (C) 2021 Cartesi Pte. Ltd.
+//
+// Licensed under the Apache License, Version 2.0 (the "License"); you may not use
+// this file except in compliance with the License. You may obtain a copy of the
+// License at http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software distributed
+// under the License is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR
+// CONDITIONS OF ANY KIND, either express or implied. See the License for the
+// specific language governing permissions and limitations under the License.
+
+use cartesi_machine_json_rpc::client::*;
+use cartesi_machine_json_rpc::fake::FakeServer;
+use rstest::*;
+
+mod common;
+
+// Drivers only see the trait, never the concrete client
+async fn boot<M: CartesiMachine>(machine: &M) -> Result<Hash, Error> {
+    common::create_machine(machine).await?;
+    machine.get_root_hash().await
+}
+
+async fn verified_uarch_step<M: CartesiMachine>(machine: &M) -> Result<(), Error> {
+    // addi x1, x0, 1 at the uarch reset pc
+    machine
+        .write_virtual_memory(0x70000000, &0x00100093u64.to_le_bytes())
+        .await?;
+    let root_hash_before = machine.get_root_hash().await?;
+    let log = machine
+        .step(
+            &AccessLogType {
+                annotations: true,
+                proofs: true,
+            },
+            false,
+        )
+        .await?;
+    let root_hash_after = machine.get_root_hash().await?;
+    log.verify_uarch_step_transition(&root_hash_before, &root_hash_after)
+}
+
+#[rstest]
+#[tokio::test]
+async fn test_generic_driver() -> Result<(), Box<dyn std::error::Error>> {
+    let server = FakeServer::start().await?;
+    let client = JsonRpcCartesiMachineClient::new(server.uri()).await?;
+    let root_hash = boot(&client).await?;
+    assert!(CartesiMachine::write_x(&client, 5, 0x1234).await?);
+    assert_eq!(CartesiMachine::read_x(&client, 5).await?, 0x1234);
+    assert_ne!(CartesiMachine::get_root_hash(&client).await?, root_hash);
+    verified_uarch_step(&client).await?;
+    Ok(())
+}
+
+#[rstest]
+#[tokio::test]
+async fn test_read_all_csrs_default() -> Result<(), Box<dyn std::error::Error>> {
+    let server = FakeServer::start().await?;
+    let client = JsonRpcCartesiMachineClient::new(server.uri()).await?;
+    boot(&client).await?;
+    let machine: &dyn CartesiMachine = &client;
+    let csrs = machine.read_all_csrs().await?;
+    assert_eq!(csrs.len(), Csr::ALL.len());
+    assert_eq!(csrs[&Csr::Pc], 0x1000);
+    assert_eq!(csrs[&Csr::Marchid], 0xf);
+    Ok(())
+}
+
+// Forking is only required by drivers that fork
+async fn diverge<M: ForkableCartesiMachine>(machine: &M) -> Result<M, Error> {
+    let copy = machine.fork_client().await?;
+    copy.write_x(5, 1).await?;
+    Ok(copy)
+}
+
+#[rstest]
+#[tokio::test]
+async fn test_forkable_driver() -> Result<(), Box<dyn std::error::Error>> {
+    let server = FakeServer::start().await?;
+    let client = JsonRpcCartesiMachineClient::new(server.uri()).await?;
+    boot(&client).await?;
+    let copy = diverge(&client).await?;
+    assert_eq!(CartesiMachine::read_x(&copy, 5).await?, 1);
+    assert_eq!(CartesiMachine::read_x(&client, 5).await?, 0);
+    Ok(())
+}
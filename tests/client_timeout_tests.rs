@@ -0,0 +1,129 @@
+// Copyright (C) 2021 Cartesi Pte. Ltd.
+//
+// Licensed under the Apache License, Version 2.0 (the "License"); you may not use
+// this file except in compliance with the License. You may obtain a copy of the
+// License at http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software distributed
+// under the License is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR
+// CONDITIONS OF ANY KIND, either express or implied. See the License for the
+// specific language governing permissions and limitations under the License.
+
+use async_trait::async_trait;
+use cartesi_machine_json_rpc::client::*;
+use cartesi_machine_json_rpc::fake::FakeServer;
+use rstest::*;
+use std::future::Future;
+use std::time::{Duration, Instant};
+
+mod common;
+use common::*;
+
+/// Holds back run requests, standing in for a slow server
+#[derive(Clone)]
+struct SlowRun;
+
+type SlowRunTransport = Intercept<SlowRun>;
+
+const RUN_DELAY: Duration = Duration::from_millis(300);
+
+#[async_trait]
+impl Interceptor for SlowRun {
+    async fn intercept(&self, method: &str) -> Result<(), jsonrpsee::core::Error> {
+        if method == "machine.run" {
+            tokio::time::sleep(RUN_DELAY).await;
+        }
+        Ok(())
+    }
+}
+
+#[fixture]
+async fn context_future() -> Context<SlowRunTransport> {
+    let server = FakeServer::start().await.unwrap();
+    let transport = Intercept::new(SlowRun, Transport::http(&server.uri()).unwrap());
+    connect_with_machine(server, transport).await
+}
+
+#[test]
+fn test_method_timeout_overrides_default() {
+    let mut config = ClientConfig::new();
+    config.default_timeout = Some(Duration::from_secs(1));
+    config
+        .set_method_timeout("run", None)
+        .unwrap()
+        .set_method_timeout("read_x", Some(Duration::from_millis(10)))
+        .unwrap();
+    assert_eq!(config.timeout("run"), None);
+    assert_eq!(config.timeout("read_x"), Some(Duration::from_millis(10)));
+    assert_eq!(config.timeout("store"), Some(Duration::from_secs(1)));
+    assert_eq!(ClientConfig::default().timeout("run"), None);
+}
+
+#[rstest]
+#[case::misspelled("reed_x")]
+#[case::server_method("machine.read_x")]
+#[case::local_method("run_until")]
+fn test_unknown_method_timeout(#[case] method: &str) {
+    let mut config = ClientConfig::new();
+    assert!(matches!(
+        config.set_method_timeout(method, None),
+        Err(Error::InvalidArgument(_))
+    ));
+    assert_eq!(config.timeout(method), None);
+}
+
+#[rstest]
+#[tokio::test]
+async fn test_default_timeout(context_future: impl Future<Output = Context<SlowRunTransport>>) {
+    let mut context = context_future.await;
+    let mut config = ClientConfig::new();
+    config.default_timeout = Some(Duration::from_millis(50));
+    context.client.set_config(config);
+    match context.client.run(100).await {
+        Err(Error::Timeout { method, timeout }) => {
+            assert_eq!(method, "run");
+            assert_eq!(timeout, Duration::from_millis(50));
+        }
+        result => panic!("unexpected result: {:?}", result),
+    }
+    assert_eq!(context.client.read_x(0).await.unwrap(), 0);
+}
+
+#[rstest]
+#[tokio::test]
+async fn test_long_running_method_override(
+    context_future: impl Future<Output = Context<SlowRunTransport>>,
+) {
+    let mut context = context_future.await;
+    let mut config = ClientConfig::new();
+    config.default_timeout = Some(Duration::from_millis(50));
+    config
+        .set_method_timeout("run", Some(Duration::from_secs(10)))
+        .unwrap();
+    context.client.set_config(config);
+    assert_eq!(
+        context.client.run(100).await.unwrap(),
+        InterpreterBreakReason::ReachedTargetMcycle
+    );
+}
+
+#[rstest]
+#[tokio::test]
+async fn test_deadline(context_future: impl Future<Output = Context<SlowRunTransport>>) {
+    let context = context_future.await;
+    let hurried = context.client.with_timeout(Duration::from_millis(50));
+    assert!(matches!(
+        hurried.run(100).await,
+        Err(Error::Timeout { method: "run", .. })
+    ));
+    // The deadline only applies to the copy
+    assert!(context.client.run(100).await.is_ok());
+    let expired = context.client.with_deadline(Instant::now());
+    assert!(matches!(
+        expired.read_x(0).await,
+        Err(Error::Timeout {
+            method: "read_x",
+            ..
+        })
+    ));
+}
@@ -0,0 +1,169 @@
+// Copyright (C) 2021 Cartesi Pte. Ltd.
+//
+// Licensed under the Apache License, Version 2.0 (the "License"); you may not use
+// this file except in compliance with the License. You may obtain a copy of the
+// License at http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software distributed
+// under the License is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR
+// CONDITIONS OF ANY KIND, either express or implied. See the License for the
+// specific language governing permissions and limitations under the License.
+
+//! Fixtures shared by the integration tests
+
+// Every test crate uses only some of the fixtures
+#![allow(dead_code)]
+
+use async_trait::async_trait;
+use cartesi_machine_json_rpc::client::*;
+use cartesi_machine_json_rpc::fake::FakeServer;
+use jsonrpsee::core::client::{BatchResponse, ClientT};
+use jsonrpsee::core::params::BatchRequestBuilder;
+use jsonrpsee::core::traits::ToRpcParams;
+use rstest::*;
+use serde::de::DeserializeOwned;
+
+pub struct Context<T = Transport> {
+    // Keeps the server running for as long as the client is used
+    pub server: FakeServer,
+    pub client: JsonRpcCartesiMachineClient<T>,
+}
+
+/// Create a machine from the default configuration with 1 MiB of RAM, after `customize`
+pub async fn create_machine_with<M, F>(machine: &M, customize: F) -> Result<(), Error>
+where
+    M: CartesiMachine,
+    F: FnOnce(&mut MachineConfig),
+{
+    let mut config = machine.get_default_config().await?;
+    config.ram.length = 1 << 20;
+    customize(&mut config);
+    machine
+        .create_machine(&config, &MachineRuntimeConfig::default())
+        .await?;
+    Ok(())
+}
+
+/// Create a machine from the default configuration with 1 MiB of RAM
+pub async fn create_machine<M: CartesiMachine>(machine: &M) -> Result<(), Error> {
+    create_machine_with(machine, |_| {}).await
+}
+
+/// Connect to `server` through `transport`, without creating a machine
+pub async fn connect<T>(server: FakeServer, transport: T) -> Context<T>
+where
+    T: ClientT + Send + Sync + 'static,
+{
+    let client = JsonRpcCartesiMachineClient::from_transport(server.uri(), transport)
+        .await
+        .unwrap();
+    Context { server, client }
+}
+
+/// Connect to `server` through `transport` and create a machine
+pub async fn connect_with_machine<T>(server: FakeServer, transport: T) -> Context<T>
+where
+    T: ClientT + Send + Sync + 'static,
+{
+    let context = connect(server, transport).await;
+    create_machine(&context.client).await.unwrap();
+    context
+}
+
+/// Fake server with a connected client, without a machine
+#[fixture]
+pub async fn context_future() -> Context {
+    let server = FakeServer::start().await.unwrap();
+    let client = JsonRpcCartesiMachineClient::new(server.uri())
+        .await
+        .unwrap();
+    Context { server, client }
+}
+
+/// Fake server with a connected client and a machine
+#[fixture]
+pub async fn context_with_machine_future() -> Context {
+    let context = context_future().await;
+    create_machine(&context.client).await.unwrap();
+    context
+}
+
+/// Hook run before every request and notification sent through an [`Intercept`]
+#[async_trait]
+pub trait Interceptor: Clone + Send + Sync + 'static {
+    /// Called with the method name; an error fails the call without sending it
+    async fn intercept(&self, method: &str) -> Result<(), jsonrpsee::core::Error>;
+}
+
+/// Transport that runs `interceptor` before delegating every call to `inner`
+#[derive(Clone)]
+pub struct Intercept<I, T = Transport> {
+    pub interceptor: I,
+    pub inner: T,
+}
+
+impl<I, T> Intercept<I, T> {
+    pub fn new(interceptor: I, inner: T) -> Self {
+        Intercept { interceptor, inner }
+    }
+}
+
+#[async_trait]
+impl<I, T> ClientT for Intercept<I, T>
+where
+    I: Interceptor,
+    T: ClientT + Send + Sync,
+{
+    async fn notification<Params>(
+        &self,
+        method: &str,
+        params: Params,
+    ) -> Result<(), jsonrpsee::core::Error>
+    where
+        Params: ToRpcParams + Send,
+    {
+        self.interceptor.intercept(method).await?;
+        self.inner.notification(method, params).await
+    }
+
+    async fn request<R, Params>(
+        &self,
+        method: &str,
+        params: Params,
+    ) -> Result<R, jsonrpsee::core::Error>
+    where
+        R: DeserializeOwned,
+        Params: ToRpcParams + Send,
+    {
+        self.interceptor.intercept(method).await?;
+        self.inner.request(method, params).await
+    }
+
+    async fn batch_request<'a, R>(
+        &self,
+        batch: BatchRequestBuilder<'a>,
+    ) -> Result<BatchResponse<'a, R>, jsonrpsee::core::Error>
+    where
+        R: DeserializeOwned + std::fmt::Debug + 'a,
+    {
+        self.inner.batch_request(batch).await
+    }
+}
+
+#[async_trait]
+impl<I, T> Reconnect for Intercept<I, T>
+where
+    I: Interceptor,
+    T: Reconnect,
+{
+    fn scheme(&self) -> &str {
+        self.inner.scheme()
+    }
+
+    async fn reconnect(&self, address: &str) -> Result<Self, Error> {
+        Ok(Intercept {
+            interceptor: self.interceptor.clone(),
+            inner: self.inner.reconnect(address).await?,
+        })
+    }
+}
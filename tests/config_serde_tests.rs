@@ -0,0 +1,227 @@
+// Copyright (C) 2021 Cartesi Pte. Ltd.
+//
+// Licensed under the Apache License, Version 2.0 (the "License"); you may not use
+// this file except in compliance with the License. You may obtain a copy of the
+// License at http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software distributed
+// under the License is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR
+// CONDITIONS OF ANY KIND, either express or implied. See the License for the
+// specific language governing permissions and limitations under the License.
+
+use cartesi_machine_json_rpc::client::merkle::MerkleTree;
+use cartesi_machine_json_rpc::client::uarch_step::*;
+use cartesi_machine_json_rpc::client::*;
+use cartesi_machine_json_rpc::interfaces;
+use rstest::*;
+use std::path::PathBuf;
+
+fn temp_path(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!(
+        "config_serde_tests_{}_{}",
+        std::process::id(),
+        name
+    ))
+}
+
+#[fixture]
+fn config() -> MachineConfig {
+    let mut processor = ProcessorConfig::new();
+    processor.misa = 0x800000000014112d;
+    processor.f[1] = u64::MAX;
+    processor.x[2] = 0x80000000;
+    MachineConfigBuilder::default()
+        .processor(processor)
+        .ram(
+            RamConfigBuilder::default()
+                .length(64u64 << 20)
+                .build()
+                .unwrap(),
+        )
+        .rom(
+            RomConfigBuilder::default()
+                .image_filename("rom.bin")
+                .bootargs("console=hvc0")
+                .build()
+                .unwrap(),
+        )
+        .flash_drive(
+            MemoryRangeConfigBuilder::default()
+                .start(0x80000000000000u64)
+                .length(0x100000u64)
+                .image_filename("rootfs.ext2")
+                .build()
+                .unwrap(),
+        )
+        .rollup(
+            RollupConfigBuilder::default()
+                .rx_buffer(
+                    MemoryRangeConfigBuilder::default()
+                        .start(0x60000000u64)
+                        .length(0x200000u64)
+                        .build()
+                        .unwrap(),
+                )
+                .build()
+                .unwrap(),
+        )
+        .uarch(UarchConfig {
+            processor: Some(interfaces::UarchProcessorConfig {
+                pc: Some(0x70000000),
+                ..Default::default()
+            }),
+            ram: None,
+        })
+        .build()
+        .unwrap()
+}
+
+#[rstest]
+fn test_processor_registers_as_hex(config: MachineConfig) {
+    let value = serde_json::to_value(config.processor).unwrap();
+    assert_eq!(value["misa"], "0x800000000014112d");
+    assert_eq!(value["mvendorid"], "0x6361727465736920");
+    assert_eq!(value["f"][1], "0xffffffffffffffff");
+    assert_eq!(value["x"][2], "0x80000000");
+}
+
+#[rstest]
+#[case::json("config.json")]
+#[case::toml("config.toml")]
+fn test_save_and_load(config: MachineConfig, #[case] name: &str) {
+    let path = temp_path(name);
+    config.save(&path).unwrap();
+    let loaded = MachineConfig::load(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(
+        serde_json::to_value(&loaded).unwrap(),
+        serde_json::to_value(&config).unwrap()
+    );
+    assert_eq!(loaded.processor.misa, 0x800000000014112d);
+    assert_eq!(loaded.flash_drives[0].image_filename, "rootfs.ext2");
+    assert!(loaded.rollup.tx_buffer.is_none());
+    loaded.validate().unwrap();
+}
+
+#[rstest]
+fn test_device_words_round_trip_toml(mut config: MachineConfig) {
+    config.clint.mtimecmp = Some(u64::MAX);
+    config.htif.tohost = Some(0x8000000000000000);
+    config.htif.fromhost = Some(0x8000000000000001);
+    config.uarch.processor = Some(interfaces::UarchProcessorConfig {
+        cycle: Some(u64::MAX),
+        pc: Some(0x8000000000000000),
+        x: Some(vec![0, u64::MAX]),
+    });
+    let path = temp_path("device_words.toml");
+    config.save(&path).unwrap();
+    let loaded = MachineConfig::load(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(loaded.clint.mtimecmp, Some(u64::MAX));
+    assert_eq!(loaded.htif, config.htif);
+    assert_eq!(loaded.uarch.processor, config.uarch.processor);
+    let value = serde_json::to_value(&config).unwrap();
+    assert_eq!(value["htif"]["tohost"], "0x8000000000000000");
+    assert_eq!(value["uarch"]["processor"]["x"][1], "0xffffffffffffffff");
+}
+
+#[test]
+fn test_load_partial_toml() {
+    let config: MachineConfig = toml::from_str(
+        r#"
+        [processor]
+        pc = 4096
+        misa = "0x800000000014112d"
+
+        [ram]
+        length = 0x4000000
+
+        [rom]
+        image_filename = "rom.bin"
+        "#,
+    )
+    .unwrap();
+    assert_eq!(config.processor.pc, 0x1000);
+    assert_eq!(config.processor.misa, 0x800000000014112d);
+    assert_eq!(config.processor.x, [0; 32]);
+    assert_eq!(config.processor.mvendorid, 0x6361727465736920);
+    assert_eq!(config.processor.marchid, 0xf);
+    assert_eq!(config.processor.mimpid, 1);
+    assert_eq!(config.ram.length, 64 << 20);
+    assert!(config.flash_drives.is_empty());
+}
+
+#[rstest]
+#[case::json_machine("misspelled.json", r#"{"flash_drive": []}"#)]
+#[case::json_range(
+    "misspelled_range.json",
+    r#"{"flash_drives": [{"start": 0, "lenght": 4096}]}"#
+)]
+#[case::toml_processor("misspelled.toml", "[processor]\nmisaa = 0\n")]
+#[case::toml_htif("misspelled_htif.toml", "[htif]\ntohots = 0\n")]
+#[case::toml_uarch_ram("misspelled_uarch.toml", "[uarch.ram]\nlenght = 4096\n")]
+fn test_reject_misspelled_keys(#[case] name: &str, #[case] contents: &str) {
+    let path = temp_path(name);
+    std::fs::write(&path, contents).unwrap();
+    let result = MachineConfig::load(&path);
+    std::fs::remove_file(&path).unwrap();
+    match result {
+        Err(Error::Serialization(message)) => assert!(message.contains("unknown field")),
+        other => panic!("expected a serialization error, got {:?}", other),
+    }
+}
+
+#[rstest]
+#[case::bad_hex(r#"{"processor": {"pc": "0xzz"}}"#)]
+#[case::negative(r#"{"processor": {"pc": -1}}"#)]
+#[case::short_register_file(r#"{"processor": {"x": ["0x0"]}}"#)]
+fn test_reject_invalid_registers(#[case] json: &str) {
+    assert!(serde_json::from_str::<MachineConfig>(json).is_err());
+}
+
+#[rstest]
+fn test_unknown_extension(config: MachineConfig) {
+    let path = temp_path("config.yaml");
+    assert!(matches!(config.save(&path), Err(Error::InvalidArgument(_))));
+    assert!(matches!(
+        MachineConfig::load(&path),
+        Err(Error::InvalidArgument(_))
+    ));
+}
+
+#[test]
+fn test_load_errors() {
+    assert!(matches!(
+        MachineConfig::load(temp_path("missing.json")),
+        Err(Error::Io(_))
+    ));
+    let path = temp_path("broken.toml");
+    std::fs::write(&path, "[processor\n").unwrap();
+    let result = MachineConfig::load(&path);
+    std::fs::remove_file(&path).unwrap();
+    assert!(matches!(result, Err(Error::Serialization(_))));
+}
+
+#[test]
+fn test_access_log_round_trip() {
+    let mut tree = MerkleTree::new();
+    // addi x1, x0, 1
+    tree.write_word(0x70000000, 0x00100093);
+    tree.write_word(0x400008010, 0x70000000);
+    let root_hash_before = tree.root_hash();
+    let mut state = RecordingUarchState::new(&mut tree);
+    uarch_step(&mut state).unwrap();
+    let log = state.into_log();
+    let json = serde_json::to_string(&log).unwrap();
+    let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+    assert_eq!(value["accesses"][0]["type"], "read");
+    assert!(value["accesses"][0]["read_data"]
+        .as_str()
+        .unwrap()
+        .starts_with("0x"));
+    let decoded: AccessLog = serde_json::from_str(&json).unwrap();
+    assert_eq!(decoded.accesses.len(), log.accesses.len());
+    decoded
+        .verify_uarch_step_transition(&root_hash_before, &tree.root_hash())
+        .unwrap();
+}
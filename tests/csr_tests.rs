@@ -0,0 +1,39 @@
+// Copyright (C) 2021 Cartesi Pte. Ltd.
+//
+// Licensed under the Apache License, Version 2.0 (the "License"); you may not use
+// this file except in compliance with the License. You may obtain a copy of the
+// License at http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software distributed
+// under the License is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR
+// CONDITIONS OF ANY KIND, either express or implied. See the License for the
+// specific language governing permissions and limitations under the License.
+
+use cartesi_machine_json_rpc::client::*;
+use rstest::*;
+
+#[rstest]
+#[case(Csr::Pc, "pc")]
+#[case(Csr::Mcycle, "mcycle")]
+#[case(Csr::ClintMtimecmp, "clint_mtimecmp")]
+#[case(Csr::HtifTohost, "htif_tohost")]
+#[case(Csr::UarchHaltFlag, "uarch_halt_flag")]
+fn test_csr_wire_name(#[case] csr: Csr, #[case] name: &str) {
+    assert_eq!(csr.to_string(), name);
+    assert_eq!(name.parse::<Csr>().unwrap(), csr);
+}
+
+#[test]
+fn test_csr_names_round_trip() {
+    for csr in Csr::ALL {
+        assert_eq!(csr.as_str().parse::<Csr>().unwrap(), *csr);
+    }
+}
+
+#[test]
+fn test_unknown_csr() {
+    assert!(matches!(
+        "mcyle".parse::<Csr>(),
+        Err(Error::InvalidArgument(_))
+    ));
+}
@@ -0,0 +1,87 @@
+// Copyright (C) 2021 Cartesi Pte. Ltd.
+//
+// Licensed under the Apache License, Version 2.0 (the "License"); you may not use
+// this file except in compliance with the License. You may obtain a copy of the
+// License at http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software distributed
+// under the License is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR
+// CONDITIONS OF ANY KIND, either express or implied. See the License for the
+// specific language governing permissions and limitations under the License.
+
+use cartesi_machine_json_rpc::client::*;
+use cartesi_machine_json_rpc::interfaces;
+use std::convert::TryFrom;
+
+fn call_error(code: i32, message: &str) -> jsonrpsee::core::Error {
+    let object = serde_json::json!({ "code": code, "message": message });
+    jsonrpsee::core::Error::Call(serde_json::from_value(object).unwrap())
+}
+
+#[test]
+fn test_server_error_keeps_code_and_message() {
+    match Error::from(call_error(-32602, "invalid params")) {
+        Error::Server { code, message } => {
+            assert_eq!(code, -32602);
+            assert_eq!(message, "invalid params");
+        }
+        err => panic!("unexpected error: {:?}", err),
+    }
+}
+
+#[test]
+fn test_no_machine_error() {
+    let err = Error::from(call_error(-32600, "no machine"));
+    assert!(matches!(err, Error::NoMachine));
+}
+
+#[test]
+fn test_transport_error() {
+    let err = Error::from(jsonrpsee::core::Error::RequestTimeout);
+    assert!(matches!(err, Error::Transport(_)));
+}
+
+#[test]
+fn test_malformed_access_is_decode_error() {
+    let access = interfaces::Access {
+        address: 0x1000,
+        log_2_size: 3,
+        proof: None,
+        read: "not base64!".to_string(),
+        r#type: serde_json::json!("read"),
+        written: None,
+    };
+    assert!(matches!(Access::try_from(&access), Err(Error::Decode(_))));
+}
+
+#[test]
+fn test_malformed_access_log_is_decode_error() {
+    let log = interfaces::AccessLog {
+        accesses: vec![interfaces::Access {
+            address: 0x1000,
+            log_2_size: 3,
+            proof: None,
+            read: "AAAAAAAAAAA=\n".to_string(),
+            r#type: serde_json::json!("write"),
+            written: Some("###".to_string()),
+        }],
+        ..Default::default()
+    };
+    assert!(matches!(AccessLog::try_from(&log), Err(Error::Decode(_))));
+}
+
+#[test]
+fn test_access_decoding() {
+    let access = interfaces::Access {
+        address: 0x1000,
+        log_2_size: 3,
+        proof: None,
+        read: "AQIDBAUGBwg=\n".to_string(),
+        r#type: serde_json::json!("write"),
+        written: Some("CAcGBQQDAgE=\n".to_string()),
+    };
+    let access = Access::try_from(&access).unwrap();
+    assert_eq!(access.r#type, AccessType::Write);
+    assert_eq!(access.read_data, vec![1, 2, 3, 4, 5, 6, 7, 8]);
+    assert_eq!(access.written_data, vec![8, 7, 6, 5, 4, 3, 2, 1]);
+}
@@ -0,0 +1,275 @@
+// Copyright (C) 2021 Cartesi Pte. Ltd.
+//
+// Licensed under the Apache License, Version 2.0 (the "License"); you may not use
+// this file except in compliance with the License. You may obtain a copy of the
+// License at http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software distributed
+// under the License is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR
+// CONDITIONS OF ANY KIND, either express or implied. See the License for the
+// specific language governing permissions and limitations under the License.
+
+use cartesi_machine_json_rpc::client::*;
+use rstest::*;
+use std::future::Future;
+
+mod common;
+use common::*;
+
+#[rstest]
+#[tokio::test]
+async fn test_no_machine(context_future: impl Future<Output = Context>) {
+    let context = context_future.await;
+    assert!(matches!(
+        context.client.get_root_hash().await,
+        Err(Error::NoMachine)
+    ));
+    let default_config = context.client.get_default_config().await.unwrap();
+    assert_eq!(default_config.processor.pc, 4096);
+    assert_eq!(default_config.processor.marchid, 0xf);
+}
+
+#[rstest]
+#[tokio::test]
+async fn test_memory_and_proofs(
+    context_with_machine_future: impl Future<Output = Context>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let context = context_with_machine_future.await;
+    let client = &context.client;
+    let root_hash = client.get_root_hash().await?;
+    client
+        .write_virtual_memory(0x8000000f, &[1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12])
+        .await?;
+    assert_eq!(
+        client.read_memory(0x8000000f, 12).await?,
+        vec![1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12]
+    );
+    assert_eq!(client.read_word(0x80000010).await?, 0x0908070605040302);
+    let new_root_hash = client.get_root_hash().await?;
+    assert_ne!(root_hash, new_root_hash);
+    let proof = client.get_proof(0x80000010, 3).await?;
+    assert_eq!(proof.root_hash, new_root_hash);
+    assert!(proof.verify());
+    Ok(())
+}
+
+#[rstest]
+#[tokio::test]
+async fn test_read_outside_memory_ranges(
+    context_with_machine_future: impl Future<Output = Context>,
+) {
+    let context = context_with_machine_future.await;
+    let client = &context.client;
+    assert!(client.read_memory(0x80000000, 1 << 20).await.is_ok());
+    assert!(client.read_memory(0x80000000, (1 << 20) + 1).await.is_err());
+    assert!(client.read_memory(0x80000000, u64::MAX).await.is_err());
+    assert!(client.read_memory(0x40000000, 8).await.is_err());
+}
+
+#[rstest]
+#[tokio::test]
+async fn test_registers_and_csrs(
+    context_with_machine_future: impl Future<Output = Context>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let context = context_with_machine_future.await;
+    let client = &context.client;
+    assert_eq!(client.get_x_address(2).await?, 0x10);
+    client.write_x(2, 0x1234).await?;
+    assert_eq!(client.read_x(2).await?, 0x1234);
+    client.write_f(3, 0x5678).await?;
+    assert_eq!(client.read_f(3).await?, 0x5678);
+    assert_eq!(client.read_csr(Csr::Pc).await?, 0x1000);
+    client.write_csr(Csr::Mscratch, 42).await?;
+    assert_eq!(client.read_csr(Csr::Mscratch).await?, 42);
+    assert_eq!(client.read_csr(Csr::UarchPc).await?, 0x70000000);
+    assert_eq!(client.read_iflags_prv().await?, PrivilegeLevel::Machine);
+    client.set_iflags_y().await?;
+    assert!(client.read_iflags_y().await?);
+    client.reset_iflags_y().await?;
+    assert!(!client.read_iflags_y().await?);
+    Ok(())
+}
+
+#[rstest]
+#[tokio::test]
+async fn test_run(
+    context_with_machine_future: impl Future<Output = Context>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let context = context_with_machine_future.await;
+    let client = &context.client;
+    assert_eq!(
+        client.run(1000).await?,
+        InterpreterBreakReason::ReachedTargetMcycle
+    );
+    assert_eq!(client.read_csr(Csr::Mcycle).await?, 1000);
+    client.set_iflags_y().await?;
+    assert_eq!(
+        client.run(2000).await?,
+        InterpreterBreakReason::YieldedManually
+    );
+    client.set_iflags_h().await?;
+    assert_eq!(client.run(2000).await?, InterpreterBreakReason::Halted);
+    assert_eq!(client.read_csr(Csr::Mcycle).await?, 1000);
+    Ok(())
+}
+
+#[rstest]
+#[tokio::test]
+async fn test_run_resumes_automatic_yield(
+    context_with_machine_future: impl Future<Output = Context>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let context = context_with_machine_future.await;
+    let client = &context.client;
+    client.set_iflags_x().await?;
+    assert_eq!(
+        client.run(0).await?,
+        InterpreterBreakReason::ReachedTargetMcycle
+    );
+    assert!(client.read_iflags_x().await?);
+    assert_eq!(
+        client.run(1000).await?,
+        InterpreterBreakReason::ReachedTargetMcycle
+    );
+    assert!(!client.read_iflags_x().await?);
+    assert_eq!(client.read_csr(Csr::Mcycle).await?, 1000);
+    Ok(())
+}
+
+#[rstest]
+#[case::automatic(0, InterpreterBreakReason::YieldedAutomatically)]
+#[case::manual(1, InterpreterBreakReason::YieldedManually)]
+#[tokio::test]
+async fn test_run_yields_on_tohost(
+    context_with_machine_future: impl Future<Output = Context>,
+    #[case] cmd: u64,
+    #[case] reason: InterpreterBreakReason,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let context = context_with_machine_future.await;
+    let client = &context.client;
+    client
+        .write_csr(Csr::HtifTohost, (2 << 56) | (cmd << 48))
+        .await?;
+    assert_eq!(client.run(1000).await?, reason);
+    assert_eq!(client.read_csr(Csr::Mcycle).await?, 0);
+    assert_eq!(
+        client.read_csr(Csr::HtifFromhost).await?,
+        (2 << 56) | (cmd << 48)
+    );
+    Ok(())
+}
+
+#[rstest]
+#[tokio::test]
+async fn test_run_rejects_past_mcycle(
+    context_with_machine_future: impl Future<Output = Context>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let context = context_with_machine_future.await;
+    let client = &context.client;
+    client.run(1000).await?;
+    match client.run(999).await {
+        Err(Error::Server { message, .. }) => assert!(message.contains("mcycle is past")),
+        result => panic!("unexpected result: {:?}", result),
+    }
+    assert_eq!(client.read_csr(Csr::Mcycle).await?, 1000);
+    Ok(())
+}
+
+#[rstest]
+#[tokio::test]
+async fn test_step(
+    context_with_machine_future: impl Future<Output = Context>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let context = context_with_machine_future.await;
+    let client = &context.client;
+    // addi x1, x0, 5; ecall
+    client
+        .write_memory(0x70000000, "kwBQAHMAAAA=".to_string())
+        .await?;
+    client.write_uarch_x(17, 1).await?;
+    let root_hash_before = client.get_root_hash().await?;
+    let log = client
+        .step(
+            &AccessLogType {
+                proofs: true,
+                annotations: false,
+            },
+            false,
+        )
+        .await?;
+    let root_hash_after = client.get_root_hash().await?;
+    assert_eq!(client.read_uarch_x(1).await?, 5);
+    log.verify_uarch_step_transition(&root_hash_before, &root_hash_after)?;
+    client
+        .verify_state_transition(
+            &root_hash_before,
+            &log,
+            &root_hash_after,
+            false,
+            &MachineRuntimeConfig::default(),
+        )
+        .await?;
+    assert_eq!(
+        client.run_uarch(10).await?,
+        UarchInterpreterBreakReason::UarchHalted
+    );
+    assert!(client.read_uarch_halt_flag().await?);
+    client.reset_uarch_state().await?;
+    assert!(!client.read_uarch_halt_flag().await?);
+    assert_eq!(client.read_csr(Csr::UarchCycle).await?, 0);
+    Ok(())
+}
+
+#[rstest]
+#[tokio::test]
+async fn test_fork(
+    context_with_machine_future: impl Future<Output = Context>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let context = context_with_machine_future.await;
+    let client = &context.client;
+    client.write_x(1, 1).await?;
+    let child = client.fork_client().await?;
+    child.write_x(1, 2).await?;
+    assert_eq!(client.read_x(1).await?, 1);
+    assert_eq!(child.read_x(1).await?, 2);
+    child.shutdown().await?;
+    assert!(child.get_version().await.is_err());
+    Ok(())
+}
+
+#[rstest]
+#[tokio::test]
+async fn test_store_and_load(
+    context_with_machine_future: impl Future<Output = Context>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let context = context_with_machine_future.await;
+    let client = &context.client;
+    client.write_x(1, 7).await?;
+    let root_hash = client.get_root_hash().await?;
+    client.store("/tmp/fake-machine").await?;
+    assert!(client.store("/tmp/fake-machine").await.is_err());
+    client.destroy().await?;
+    assert!(matches!(client.read_x(1).await, Err(Error::NoMachine)));
+    client
+        .load_machine("/tmp/fake-machine", &MachineRuntimeConfig::default())
+        .await?;
+    assert_eq!(client.read_x(1).await?, 7);
+    assert_eq!(client.get_root_hash().await?, root_hash);
+    Ok(())
+}
+
+#[test]
+fn test_fork_dropped_outside_runtime() {
+    // Worker threads keep the server answering while no runtime is entered
+    let runtime = tokio::runtime::Runtime::new().unwrap();
+    let (context, child) = runtime.block_on(async {
+        let context = context_with_machine_future().await;
+        let child = context.client.fork_client().await.unwrap();
+        (context, child)
+    });
+    let address = child.get_address().clone();
+    drop(child);
+    runtime.block_on(async {
+        assert!(JsonRpcCartesiMachineClient::new(address).await.is_err());
+        context.client.get_version().await.unwrap();
+    });
+}
@@ -0,0 +1,67 @@
+// Copyright (C) 2021 Cartesi Pte. Ltd.
+//
+// Licensed under the Apache License, Version 2.0 (the "License"); you may not use
+// this file except in compliance with the License. You may obtain a copy of the
+// License at http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software distributed
+// under the License is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR
+// CONDITIONS OF ANY KIND, either express or implied. See the License for the
+// specific language governing permissions and limitations under the License.
+
+use cartesi_machine_json_rpc::client::*;
+use cartesi_machine_json_rpc::interfaces;
+use std::convert::TryFrom;
+
+static HASH_BYTES: [u8; 32] = [
+    35, 254, 2, 79, 37, 221, 81, 35, 248, 37, 161, 169, 94, 207, 252, 232, 112, 88, 158, 205, 25,
+    69, 157, 16, 32, 131, 238, 33, 140, 15, 0, 174,
+];
+static HASH_HEX: &str = "23fe024f25dd5123f825a1a95ecffce870589ecd19459d102083ee218c0f00ae";
+static HASH_BASE64: &str = "I/4CTyXdUSP4JaGpXs/86HBYns0ZRZ0QIIPuIYwPAK4=";
+
+#[test]
+fn test_hash_display_and_parse() {
+    let hash = Hash::new(HASH_BYTES);
+    assert_eq!(hash.to_string(), HASH_HEX);
+    assert_eq!(hash.to_base64(), HASH_BASE64);
+    assert_eq!(HASH_HEX.parse::<Hash>().unwrap(), hash);
+    assert_eq!(format!("0x{}", HASH_HEX).parse::<Hash>().unwrap(), hash);
+    assert_eq!(HASH_BASE64.parse::<Hash>().unwrap(), hash);
+}
+
+#[test]
+fn test_hash_parse_errors() {
+    assert!(matches!("0x1234".parse::<Hash>(), Err(Error::Decode(_))));
+    assert!(matches!("AAAA".parse::<Hash>(), Err(Error::Decode(_))));
+    assert!(matches!(
+        Hash::try_from(&HASH_BYTES[..31]),
+        Err(Error::Decode(_))
+    ));
+}
+
+#[test]
+fn test_hash_serde() {
+    let hash = Hash::new(HASH_BYTES);
+    let json = serde_json::to_string(&hash).unwrap();
+    assert_eq!(json, format!("\"{}\"", HASH_HEX));
+    assert_eq!(serde_json::from_str::<Hash>(&json).unwrap(), hash);
+}
+
+#[test]
+fn test_proof_hashes_from_wire() {
+    let wire_hash = format!("{}\n", HASH_BASE64);
+    let proof = interfaces::Proof {
+        log_2_root_size: 64,
+        log_2_target_size: 63,
+        root_hash: wire_hash.clone(),
+        sibling_hashes: vec![wire_hash.clone()],
+        target_address: 0,
+        target_hash: wire_hash.clone(),
+    };
+    let proof = MerkleTreeProof::try_from(&proof).unwrap();
+    assert_eq!(proof.root_hash, HASH_BYTES);
+    assert_eq!(proof.sibling_hashes, vec![Hash::new(HASH_BYTES)]);
+    let back = interfaces::Proof::from(&proof);
+    assert_eq!(back.target_hash, wire_hash);
+}
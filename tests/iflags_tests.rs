@@ -0,0 +1,38 @@
+// Copyright (C) 2021 Cartesi Pte. Ltd.
+//
+// Licensed under the Apache License, Version 2.0 (the "License"); you may not use
+// this file except in compliance with the License. You may obtain a copy of the
+// License at http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software distributed
+// under the License is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR
+// CONDITIONS OF ANY KIND, either express or implied. See the License for the
+// specific language governing permissions and limitations under the License.
+
+use cartesi_machine_json_rpc::client::*;
+use rstest::*;
+use std::convert::TryFrom;
+
+#[rstest]
+#[case(0x18, PrivilegeLevel::Machine, false, false, false)]
+#[case(0x19, PrivilegeLevel::Machine, false, false, true)]
+#[case(0x1a, PrivilegeLevel::Machine, false, true, false)]
+#[case(0x0c, PrivilegeLevel::Supervisor, true, false, false)]
+#[case(0x00, PrivilegeLevel::User, false, false, false)]
+fn test_decode_iflags(
+    #[case] packed: u64,
+    #[case] prv: PrivilegeLevel,
+    #[case] x: bool,
+    #[case] y: bool,
+    #[case] h: bool,
+) {
+    let iflags = IFlags::try_from(packed).unwrap();
+    assert_eq!(iflags, IFlags { prv, x, y, h });
+    assert_eq!(u64::from(iflags), packed);
+}
+
+#[test]
+fn test_reserved_privilege_level() {
+    assert!(matches!(PrivilegeLevel::try_from(2), Err(Error::Decode(_))));
+    assert!(matches!(IFlags::try_from(0x10), Err(Error::Decode(_))));
+}
@@ -0,0 +1,211 @@
+// Copyright (C) 2021 Cartesi Pte. Ltd.
+//
+// Licensed under the Apache License, Version 2.0 (the "License"); you may not use
+// this file except in compliance with the License. You may obtain a copy of the
+// License at http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software distributed
+// under the License is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR
+// CONDITIONS OF ANY KIND, either express or implied. See the License for the
+// specific language governing permissions and limitations under the License.
+
+use cartesi_machine_json_rpc::client::*;
+use cartesi_machine_json_rpc::interfaces;
+use rstest::*;
+
+fn range(start: u64, length: u64) -> MemoryRangeConfig {
+    MemoryRangeConfigBuilder::default()
+        .start(start)
+        .length(length)
+        .build()
+        .unwrap()
+}
+
+#[fixture]
+fn builder() -> MachineConfigBuilder {
+    let mut builder = MachineConfigBuilder::default();
+    builder
+        .processor(ProcessorConfig::new())
+        .ram(
+            RamConfigBuilder::default()
+                .length(64u64 << 20)
+                .image_filename("linux.bin")
+                .build()
+                .unwrap(),
+        )
+        .rom(
+            RomConfigBuilder::default()
+                .image_filename("rom.bin")
+                .bootargs("console=hvc0")
+                .build()
+                .unwrap(),
+        );
+    builder
+}
+
+fn problems(builder: &MachineConfigBuilder) -> Vec<String> {
+    match builder.build() {
+        Err(Error::InvalidConfig(problems)) => problems,
+        result => panic!("unexpected result: {:?}", result),
+    }
+}
+
+#[rstest]
+fn test_build_valid_config(mut builder: MachineConfigBuilder) {
+    let config = builder
+        .flash_drive(range(0x80000000000000, 0x100000))
+        .flash_drive(range(0x90000000000000, 0x1000))
+        .rollup(
+            RollupConfigBuilder::default()
+                .rx_buffer(range(0x60000000, 2 << 20))
+                .tx_buffer(range(0x60200000, 2 << 20))
+                .build()
+                .unwrap(),
+        )
+        .build()
+        .unwrap();
+    assert_eq!(config.ram.length, 64 << 20);
+    assert_eq!(config.rom.image_filename, "rom.bin");
+    assert_eq!(config.flash_drives.len(), 2);
+    assert_eq!(config.rollup.tx_buffer.unwrap().start, 0x60200000);
+    assert_eq!(config.processor.marchid, ProcessorConfig::new().marchid);
+}
+
+#[test]
+fn test_build_without_processor() {
+    let config = MachineConfigBuilder::default()
+        .ram(
+            RamConfigBuilder::default()
+                .length(1u64 << 20)
+                .build()
+                .unwrap(),
+        )
+        .rom(
+            RomConfigBuilder::default()
+                .image_filename("rom.bin")
+                .build()
+                .unwrap(),
+        )
+        .build()
+        .unwrap();
+    assert_eq!(config.processor.mvendorid, 0x6361727465736920);
+    assert_eq!(config.processor.marchid, 0xf);
+    assert_eq!(config.processor.mimpid, 1);
+}
+
+#[rstest]
+fn test_wrong_processor_ids(mut builder: MachineConfigBuilder) {
+    let mut processor = ProcessorConfig::new();
+    processor.marchid = 0;
+    processor.mimpid = 0;
+    builder.processor(processor);
+    assert_eq!(
+        problems(&builder),
+        vec![
+            "processor: marchid 0x0 is not the emulator's 0xf",
+            "processor: mimpid 0x0 is not the emulator's 0x1",
+        ]
+    );
+}
+
+#[rstest]
+fn test_missing_rom_image(mut builder: MachineConfigBuilder) {
+    builder.rom(RomConfig::new());
+    assert_eq!(problems(&builder), vec!["rom: image filename is missing"]);
+}
+
+#[rstest]
+#[case(0)]
+#[case(4097)]
+fn test_unaligned_ram_length(mut builder: MachineConfigBuilder, #[case] length: u64) {
+    builder.ram(RamConfigBuilder::default().length(length).build().unwrap());
+    let problems = problems(&builder);
+    assert_eq!(problems.len(), 1);
+    assert!(problems[0].starts_with("ram: length"));
+}
+
+#[rstest]
+fn test_overlapping_ranges(mut builder: MachineConfigBuilder) {
+    builder
+        .flash_drive(range(0x80000000000000, 0x100000))
+        .flash_drive(range(0x80000000080000, 0x100000))
+        .rollup(
+            RollupConfigBuilder::default()
+                .rx_buffer(range(0x80000000000000, 0x1000))
+                .build()
+                .unwrap(),
+        );
+    assert_eq!(
+        problems(&builder),
+        vec![
+            "flash drive 0 overlaps flash drive 1",
+            "flash drive 0 overlaps rollup rx buffer",
+        ]
+    );
+}
+
+#[rstest]
+fn test_flash_drive_overlapping_ram(mut builder: MachineConfigBuilder) {
+    builder.flash_drive(range(0x80000000, 0x1000));
+    assert_eq!(problems(&builder), vec!["ram overlaps flash drive 0"]);
+}
+
+#[rstest]
+#[case::shadow_state(0x0, "shadow state")]
+#[case::rom(0x8000, "rom")]
+#[case::clint(0x2000000, "clint")]
+#[case::htif(0x40008000, "htif")]
+fn test_range_overlapping_fixed_region(
+    mut builder: MachineConfigBuilder,
+    #[case] start: u64,
+    #[case] region: &str,
+) {
+    builder.rollup(
+        RollupConfigBuilder::default()
+            .rx_buffer(range(start, 0x1000))
+            .build()
+            .unwrap(),
+    );
+    assert_eq!(
+        problems(&builder),
+        vec![format!("rollup rx buffer overlaps {}", region)]
+    );
+}
+
+#[rstest]
+fn test_flash_drive_overlapping_uarch_ram(mut builder: MachineConfigBuilder) {
+    builder.flash_drive(range(0x70000000, 0x100000));
+    builder.build().unwrap();
+    builder.uarch(UarchConfig {
+        processor: None,
+        ram: Some(interfaces::UarchRAMConfig {
+            image_filename: None,
+            length: Some(0x100000),
+        }),
+    });
+    assert_eq!(problems(&builder), vec!["flash drive 0 overlaps uarch ram"]);
+}
+
+#[rstest]
+fn test_reports_every_problem(mut builder: MachineConfigBuilder) {
+    builder
+        .rom(RomConfig::new())
+        .flash_drive(range(0x80000000000800, 0x3000))
+        .rollup(
+            RollupConfigBuilder::default()
+                .notice_hashes(range(0x60000000, 0))
+                .build()
+                .unwrap(),
+        );
+    let err = builder.build().unwrap_err();
+    assert_eq!(
+        problems(&builder),
+        vec![
+            "rom: image filename is missing",
+            "flash drive 0: length 0x3000 is not a power of two",
+            "flash drive 0: start 0x80000000000800 is not aligned to 0x1000",
+            "rollup notice hashes: length 0x0 is not a power of two",
+        ]
+    );
+    assert!(err.to_string().starts_with("invalid machine config: rom:"));
+}
@@ -0,0 +1,108 @@
+// Copyright (C) 2021 Cartesi Pte. Ltd.
+//
+// Licensed under the Apache License, Version 2.0 (the "License"); you may not use
+// this file except in compliance with the License. You may obtain a copy of the
+// License at http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software distributed
+// under the License is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR
+// CONDITIONS OF ANY KIND, either express or implied. See the License for the
+// specific language governing permissions and limitations under the License.
+
+use cartesi_machine_json_rpc::client::merkle::*;
+use cartesi_machine_json_rpc::client::*;
+use rstest::*;
+
+fn words(values: &[u64]) -> Vec<u8> {
+    values.iter().flat_map(|v| v.to_le_bytes()).collect()
+}
+
+#[fixture]
+fn data() -> Vec<u8> {
+    words(&[1, 2, 3, 4])
+}
+
+/// Proof for the third word of a 4-word tree
+#[fixture]
+fn proof(data: Vec<u8>) -> MerkleTreeProof {
+    MerkleTreeProof {
+        target_address: 16,
+        log2_target_size: 3,
+        target_hash: keccak256(&data[16..24]),
+        log2_root_size: 5,
+        root_hash: merkle_root_hash(&data).unwrap(),
+        sibling_hashes: vec![
+            merkle_root_hash(&data[0..16]).unwrap(),
+            keccak256(&data[24..32]),
+        ],
+    }
+}
+
+#[test]
+fn test_keccak256() {
+    assert_eq!(
+        keccak256(b"").to_string(),
+        "c5d2460186f7233c927e7db2dcc703c0e500b653ca82273b7bfad8045d85a470"
+    );
+}
+
+#[rstest]
+fn test_merkle_root_hash(data: Vec<u8>) {
+    let left = hash_children(&keccak256(&data[0..8]), &keccak256(&data[8..16]));
+    let right = hash_children(&keccak256(&data[16..24]), &keccak256(&data[24..32]));
+    assert_eq!(
+        merkle_root_hash(&data).unwrap(),
+        hash_children(&left, &right)
+    );
+    assert!(matches!(
+        merkle_root_hash(&data[0..24]),
+        Err(Error::InvalidArgument(_))
+    ));
+}
+
+#[rstest]
+fn test_verify_proof(proof: MerkleTreeProof) {
+    assert!(proof.verify());
+}
+
+#[rstest]
+fn test_verify_tampered_proof(mut proof: MerkleTreeProof) {
+    proof.target_hash = keccak256(&words(&[42]));
+    assert!(!proof.verify());
+}
+
+#[rstest]
+fn test_verify_wrong_address(mut proof: MerkleTreeProof) {
+    proof.target_address = 24;
+    assert!(!proof.verify());
+}
+
+#[rstest]
+fn test_root_hash_for_replacement(proof: MerkleTreeProof) {
+    let replaced = words(&[1, 2, 42, 4]);
+    let root_hash = proof.root_hash_for(&keccak256(&words(&[42]))).unwrap();
+    assert_eq!(root_hash, merkle_root_hash(&replaced).unwrap());
+}
+
+#[rstest]
+fn test_malformed_proof(mut proof: MerkleTreeProof) {
+    proof.sibling_hashes.pop();
+    assert!(matches!(
+        proof.root_hash_for(&proof.target_hash),
+        Err(Error::InvalidProof(_))
+    ));
+    assert!(!proof.verify());
+}
+
+#[rstest]
+fn test_subtree_target(data: Vec<u8>) {
+    let proof = MerkleTreeProof {
+        target_address: 0,
+        log2_target_size: 4,
+        target_hash: merkle_root_hash(&data[0..16]).unwrap(),
+        log2_root_size: 5,
+        root_hash: merkle_root_hash(&data).unwrap(),
+        sibling_hashes: vec![merkle_root_hash(&data[16..32]).unwrap()],
+    };
+    assert!(proof.verify());
+}
@@ -0,0 +1,212 @@
+// Copyright (C) 2021 Cartesi Pte. Ltd.
+//
+// Licensed under the Apache License, Version 2.0 (the "License"); you may not use
+// this file except in compliance with the License. You may obtain a copy of the
+// License at http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software distributed
+// under the License is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR
+// CONDITIONS OF ANY KIND, either express or implied. See the License for the
+// specific language governing permissions and limitations under the License.
+
+use async_trait::async_trait;
+use cartesi_machine_json_rpc::client::*;
+use cartesi_machine_json_rpc::fake::FakeServer;
+use rstest::*;
+use std::future::Future;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+mod common;
+
+/// Drops the next `failures` requests and counts every attempt
+#[derive(Clone)]
+struct Flaky {
+    failures: Arc<AtomicUsize>,
+    attempts: Arc<AtomicUsize>,
+}
+
+type FlakyTransport = common::Intercept<Flaky>;
+
+#[async_trait]
+impl common::Interceptor for Flaky {
+    async fn intercept(&self, _method: &str) -> Result<(), jsonrpsee::core::Error> {
+        self.attempts.fetch_add(1, Ordering::SeqCst);
+        let failures = self.failures.load(Ordering::SeqCst);
+        if failures > 0 {
+            self.failures.store(failures - 1, Ordering::SeqCst);
+            return Err(jsonrpsee::core::Error::RequestTimeout);
+        }
+        Ok(())
+    }
+}
+
+struct Context {
+    _server: FakeServer,
+    client: JsonRpcCartesiMachineClient<FlakyTransport>,
+    failures: Arc<AtomicUsize>,
+    attempts: Arc<AtomicUsize>,
+}
+
+impl Context {
+    /// Drop the next `failures` requests and return the attempts made by `f`
+    async fn attempts<R, Fut: Future<Output = R>>(&self, failures: usize, f: Fut) -> (R, usize) {
+        self.failures.store(failures, Ordering::SeqCst);
+        self.attempts.store(0, Ordering::SeqCst);
+        let result = f.await;
+        (result, self.attempts.load(Ordering::SeqCst))
+    }
+}
+
+fn fast_policy(max_attempts: u32) -> RetryPolicy {
+    RetryPolicy {
+        max_attempts,
+        initial_backoff: Duration::from_millis(1),
+        max_backoff: Duration::from_millis(5),
+        jitter: 0.0,
+    }
+}
+
+#[fixture]
+async fn context_future() -> Context {
+    let server = FakeServer::start().await.unwrap();
+    let failures = Arc::new(AtomicUsize::new(0));
+    let attempts = Arc::new(AtomicUsize::new(0));
+    let flaky = Flaky {
+        failures: failures.clone(),
+        attempts: attempts.clone(),
+    };
+    let transport = common::Intercept::new(flaky, Transport::http(&server.uri()).unwrap());
+    let common::Context { server, mut client } =
+        common::connect_with_machine(server, transport).await;
+    let mut config = ClientConfig::new();
+    config.retry = Some(fast_policy(3));
+    client.set_config(config);
+    Context {
+        _server: server,
+        client,
+        failures,
+        attempts,
+    }
+}
+
+#[test]
+fn test_backoff_doubles_up_to_limit() {
+    let policy = RetryPolicy {
+        max_attempts: 6,
+        initial_backoff: Duration::from_millis(100),
+        max_backoff: Duration::from_millis(500),
+        jitter: 0.0,
+    };
+    let backoffs: Vec<_> = (1..=6).map(|attempt| policy.backoff(attempt)).collect();
+    assert_eq!(
+        backoffs,
+        vec![
+            Some(Duration::from_millis(100)),
+            Some(Duration::from_millis(200)),
+            Some(Duration::from_millis(400)),
+            Some(Duration::from_millis(500)),
+            Some(Duration::from_millis(500)),
+            None,
+        ]
+    );
+    assert_eq!(RetryPolicy::default().backoff(100), None);
+}
+
+#[test]
+fn test_backoff_jitter_stays_in_range() {
+    let policy = RetryPolicy {
+        max_attempts: 2,
+        initial_backoff: Duration::from_millis(100),
+        max_backoff: Duration::from_secs(1),
+        jitter: 0.5,
+    };
+    for _ in 0..100 {
+        let backoff = policy.backoff(1).unwrap();
+        assert!(backoff > Duration::from_millis(50) && backoff <= Duration::from_millis(100));
+    }
+}
+
+#[rstest]
+#[tokio::test]
+async fn test_reads_are_retried(context_future: impl Future<Output = Context>) {
+    let context = context_future.await;
+    let client = &context.client;
+    let (result, attempts) = context.attempts(2, client.read_x(0)).await;
+    assert_eq!(result.unwrap(), 0);
+    assert_eq!(attempts, 3);
+    let (result, attempts) = context.attempts(2, client.get_root_hash()).await;
+    assert!(result.is_ok());
+    assert_eq!(attempts, 3);
+    let (result, attempts) = context.attempts(3, client.get_proof(0, 3)).await;
+    assert!(matches!(result, Err(Error::Transport(_))));
+    assert_eq!(attempts, 3);
+}
+
+#[rstest]
+#[tokio::test]
+async fn test_mutating_calls_are_not_retried(context_future: impl Future<Output = Context>) {
+    let context = context_future.await;
+    let client = &context.client;
+    let (result, attempts) = context.attempts(1, client.write_x(1, 1)).await;
+    assert!(matches!(result, Err(Error::Transport(_))));
+    assert_eq!(attempts, 1);
+    let (result, attempts) = context.attempts(1, client.run(10)).await;
+    assert!(result.is_err());
+    assert_eq!(attempts, 1);
+    let log_type = AccessLogType {
+        proofs: true,
+        annotations: false,
+    };
+    let (result, attempts) = context.attempts(1, client.step(&log_type, false)).await;
+    assert!(result.is_err());
+    assert_eq!(attempts, 1);
+    assert_eq!(client.read_x(1).await.unwrap(), 0);
+}
+
+#[rstest]
+#[tokio::test]
+async fn test_retry_is_opt_in(context_future: impl Future<Output = Context>) {
+    let mut context = context_future.await;
+    context.client.set_config(ClientConfig::new());
+    let (result, attempts) = context.attempts(1, context.client.read_x(0)).await;
+    assert!(matches!(result, Err(Error::Transport(_))));
+    assert_eq!(attempts, 1);
+}
+
+#[rstest]
+#[tokio::test]
+async fn test_server_errors_are_not_retried(context_future: impl Future<Output = Context>) {
+    let context = context_future.await;
+    context.client.destroy().await.unwrap();
+    let (result, attempts) = context.attempts(0, context.client.read_x(0)).await;
+    assert!(matches!(result, Err(Error::NoMachine)));
+    assert_eq!(attempts, 1);
+}
+
+#[rstest]
+#[tokio::test]
+async fn test_web_socket_reconnects_after_restart() -> Result<(), Box<dyn std::error::Error>> {
+    let server = FakeServer::start().await?;
+    let address = server.address().parse()?;
+    let mut client = JsonRpcCartesiMachineClient::new(format!("ws://{}", address)).await?;
+    let mut config = ClientConfig::new();
+    config.retry = Some(RetryPolicy {
+        max_attempts: 50,
+        initial_backoff: Duration::from_millis(20),
+        max_backoff: Duration::from_millis(20),
+        jitter: 0.0,
+    });
+    client.set_config(config);
+    server.stop();
+    server.stopped().await;
+    let restart = tokio::spawn(async move {
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        FakeServer::start_at(address).await.unwrap()
+    });
+    let config = client.get_default_config().await?;
+    assert_eq!(config.processor.marchid, 0xf);
+    let _server = restart.await?;
+    Ok(())
+}
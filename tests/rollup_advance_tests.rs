@@ -0,0 +1,427 @@
+// Copyright (C) 2021 Cartesi Pte. Ltd.
+//
+// Licensed under the Apache License, Version 2.0 (the "License"); you may not use
+// this file except in compliance with the License. You may obtain a copy of the
+// License at http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software distributed
+// under the License is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR
+// CONDITIONS OF ANY KIND, either express or implied. See the License for the
+// specific language governing permissions and limitations under the License.
+
+use async_trait::async_trait;
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use cartesi_machine_json_rpc::client::*;
+use cartesi_machine_json_rpc::fake::FakeServer;
+use rstest::*;
+use std::collections::VecDeque;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+
+mod common;
+
+const RX_BUFFER: u64 = 0x60000000;
+const TX_BUFFER: u64 = 0x60200000;
+const INPUT_METADATA: u64 = 0x60400000;
+const RANGE_LENGTH: u64 = 4096;
+
+const ACCEPTED: u64 = 1;
+const REJECTED: u64 = 2;
+const VOUCHER: u64 = 3;
+const NOTICE: u64 = 4;
+const REPORT: u64 = 5;
+const EXCEPTION: u64 = 6;
+
+/// What the dapp does when the machine next runs
+enum Step {
+    /// Write `output` to the tx buffer and yield with `reason`
+    Yield {
+        manual: bool,
+        reason: u64,
+        output: Vec<u8>,
+    },
+    Halt,
+}
+
+/// Plays a scripted dapp on the fake server, which does not run code
+#[derive(Clone)]
+struct ScriptedDapp {
+    control: JsonRpcCartesiMachineClient,
+    script: Arc<Mutex<VecDeque<Step>>>,
+    methods: Arc<Mutex<Vec<String>>>,
+}
+
+type ScriptedDappTransport = common::Intercept<ScriptedDapp>;
+
+impl ScriptedDapp {
+    async fn play(&self, step: Step) -> Result<(), Error> {
+        match step {
+            Step::Yield {
+                manual,
+                reason,
+                output,
+            } => {
+                if !output.is_empty() {
+                    self.control
+                        .write_memory(TX_BUFFER, STANDARD.encode(output))
+                        .await?;
+                }
+                // The fake server yields the run on a yield command in tohost
+                let tohost = (2 << 56) | (manual as u64) << 48 | reason << 32;
+                self.control.write_csr(Csr::HtifTohost, tohost).await?;
+            }
+            Step::Halt => {
+                self.control.set_iflags_h().await?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl common::Interceptor for ScriptedDapp {
+    async fn intercept(&self, method: &str) -> Result<(), jsonrpsee::core::Error> {
+        self.methods.lock().unwrap().push(method.to_string());
+        if method == "machine.run" {
+            let step = self.script.lock().unwrap().pop_front();
+            if let Some(step) = step {
+                self.play(step)
+                    .await
+                    .map_err(|err| jsonrpsee::core::Error::Custom(err.to_string()))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+struct Context {
+    _server: FakeServer,
+    client: JsonRpcCartesiMachineClient<ScriptedDappTransport>,
+    script: Arc<Mutex<VecDeque<Step>>>,
+    methods: Arc<Mutex<Vec<String>>>,
+}
+
+impl Context {
+    fn script(&self, steps: Vec<Step>) {
+        self.script.lock().unwrap().extend(steps);
+    }
+
+    /// Number of calls to the server `method` so far
+    fn calls(&self, method: &str) -> usize {
+        let methods = self.methods.lock().unwrap();
+        methods.iter().filter(|called| *called == method).count()
+    }
+}
+
+fn range(start: u64) -> MemoryRangeConfig {
+    MemoryRangeConfigBuilder::default()
+        .start(start)
+        .length(RANGE_LENGTH)
+        .build()
+        .unwrap()
+}
+
+async fn start(rollup: RollupConfig) -> Context {
+    let server = FakeServer::start().await.unwrap();
+    let script = Arc::new(Mutex::new(VecDeque::new()));
+    let methods = Arc::new(Mutex::new(Vec::new()));
+    let dapp = ScriptedDapp {
+        control: JsonRpcCartesiMachineClient::new(server.uri())
+            .await
+            .unwrap(),
+        script: script.clone(),
+        methods: methods.clone(),
+    };
+    let transport = common::Intercept::new(dapp, Transport::http(&server.uri()).unwrap());
+    let common::Context { server, client } = common::connect(server, transport).await;
+    common::create_machine_with(&client, |config| config.rollup = rollup)
+        .await
+        .unwrap();
+    // As if the dapp had booted and asked for its first input
+    client.set_iflags_y().await.unwrap();
+    Context {
+        _server: server,
+        client,
+        script,
+        methods,
+    }
+}
+
+#[fixture]
+async fn context_future() -> Context {
+    start(RollupConfig {
+        rx_buffer: Some(range(RX_BUFFER)),
+        tx_buffer: Some(range(TX_BUFFER)),
+        input_metadata: Some(range(INPUT_METADATA)),
+        voucher_hashes: Some(range(0x60600000)),
+        notice_hashes: Some(range(0x60800000)),
+    })
+    .await
+}
+
+/// ABI encoding of a word holding `value`
+fn word(value: u64) -> Vec<u8> {
+    let mut word = vec![0; 24];
+    word.extend_from_slice(&value.to_be_bytes());
+    word
+}
+
+/// ABI encoding of (bytes)
+fn bytes_output(payload: &[u8]) -> Vec<u8> {
+    let mut output = word(32);
+    output.extend(word(payload.len() as u64));
+    output.extend_from_slice(payload);
+    output.resize(64 + payload.len() + (32 - payload.len() % 32) % 32, 0);
+    output
+}
+
+/// ABI encoding of (address, bytes)
+fn voucher_output(destination: [u8; 20], payload: &[u8]) -> Vec<u8> {
+    let mut output = vec![0; 12];
+    output.extend_from_slice(&destination);
+    output.extend(word(64));
+    output.extend(word(payload.len() as u64));
+    output.extend_from_slice(payload);
+    output
+}
+
+fn automatic(reason: u64, output: Vec<u8>) -> Step {
+    Step::Yield {
+        manual: false,
+        reason,
+        output,
+    }
+}
+
+fn manual(reason: u64, output: Vec<u8>) -> Step {
+    Step::Yield {
+        manual: true,
+        reason,
+        output,
+    }
+}
+
+fn metadata() -> InputMetadata {
+    InputMetadata {
+        msg_sender: [0xaa; 20],
+        block_number: 10,
+        timestamp: 1700000000,
+        epoch_index: 2,
+        input_index: 3,
+    }
+}
+
+#[test]
+fn test_metadata_encoding() {
+    let encoded = metadata().encode();
+    assert_eq!(encoded.len(), 160);
+    assert_eq!(encoded[..12], [0; 12]);
+    assert_eq!(encoded[12..32], [0xaa; 20]);
+    assert_eq!(encoded[32..64], word(10)[..]);
+    assert_eq!(encoded[64..96], word(1700000000)[..]);
+    assert_eq!(encoded[96..128], word(2)[..]);
+    assert_eq!(encoded[128..160], word(3)[..]);
+}
+
+#[rstest]
+#[tokio::test]
+async fn test_accepted_with_outputs(
+    context_future: impl Future<Output = Context>,
+) -> Result<(), Error> {
+    let context = context_future.await;
+    context.script(vec![
+        automatic(VOUCHER, voucher_output([0x11; 20], b"transfer")),
+        automatic(0, Vec::new()),
+        automatic(NOTICE, bytes_output(b"notice")),
+        automatic(REPORT, bytes_output(b"")),
+        manual(ACCEPTED, Vec::new()),
+    ]);
+    let result = context.client.advance(b"hello", &metadata()).await?;
+    assert_eq!(
+        result,
+        AdvanceResult {
+            status: AdvanceStatus::Accepted,
+            vouchers: vec![Voucher {
+                destination: [0x11; 20],
+                payload: b"transfer".to_vec(),
+            }],
+            notices: vec![b"notice".to_vec()],
+            reports: vec![Vec::new()],
+        }
+    );
+    let client = &context.client;
+    assert_eq!(
+        client.read_memory(INPUT_METADATA, 160).await?,
+        metadata().encode()
+    );
+    assert_eq!(
+        client.read_memory(RX_BUFFER, 96).await?,
+        bytes_output(b"hello")
+    );
+    assert!(!client.read_iflags_x().await?);
+    assert!(client.read_iflags_y().await?);
+    Ok(())
+}
+
+#[rstest]
+#[tokio::test]
+async fn test_rejected_and_next_input(
+    context_future: impl Future<Output = Context>,
+) -> Result<(), Error> {
+    let context = context_future.await;
+    context.script(vec![
+        automatic(REPORT, bytes_output(b"invalid input")),
+        manual(REJECTED, Vec::new()),
+        manual(ACCEPTED, Vec::new()),
+    ]);
+    let result = context.client.advance(b"bad", &metadata()).await?;
+    assert_eq!(result.status, AdvanceStatus::Rejected);
+    assert_eq!(result.reports, vec![b"invalid input".to_vec()]);
+
+    let result = context.client.advance(b"good", &metadata()).await?;
+    assert_eq!(result.status, AdvanceStatus::Accepted);
+    assert!(result.reports.is_empty());
+    Ok(())
+}
+
+#[rstest]
+#[tokio::test]
+async fn test_exception(context_future: impl Future<Output = Context>) -> Result<(), Error> {
+    let context = context_future.await;
+    context.script(vec![manual(EXCEPTION, bytes_output(b"panic"))]);
+    let result = context.client.advance(b"", &metadata()).await?;
+    assert_eq!(result.status, AdvanceStatus::Exception(b"panic".to_vec()));
+    Ok(())
+}
+
+#[rstest]
+#[tokio::test]
+async fn test_halted(context_future: impl Future<Output = Context>) -> Result<(), Error> {
+    let context = context_future.await;
+    context.script(vec![automatic(NOTICE, bytes_output(b"last")), Step::Halt]);
+    let result = context.client.advance(b"", &metadata()).await?;
+    assert_eq!(result.status, AdvanceStatus::Halted);
+    assert_eq!(result.notices, vec![b"last".to_vec()]);
+    Ok(())
+}
+
+#[rstest]
+#[tokio::test]
+async fn test_protocol_violations(
+    context_future: impl Future<Output = Context>,
+) -> Result<(), Error> {
+    let context = context_future.await;
+    context.script(vec![manual(VOUCHER, Vec::new())]);
+    assert!(matches!(
+        context.client.advance(b"", &metadata()).await,
+        Err(Error::Rollup(_))
+    ));
+
+    let mut output = word(32);
+    output.extend(word(RANGE_LENGTH));
+    context.script(vec![automatic(NOTICE, output)]);
+    assert!(matches!(
+        context.client.advance(b"", &metadata()).await,
+        Err(Error::Rollup(_))
+    ));
+    Ok(())
+}
+
+#[rstest]
+#[tokio::test]
+async fn test_payload_too_large(context_future: impl Future<Output = Context>) {
+    let context = context_future.await;
+    let payload = vec![0; RANGE_LENGTH as usize];
+    match context.client.advance(&payload, &metadata()).await {
+        Err(Error::Rollup(message)) => assert_eq!(
+            message,
+            format!(
+                "encoded input payload of {} bytes does not fit in rx_buffer of {} bytes",
+                RANGE_LENGTH + 64,
+                RANGE_LENGTH
+            )
+        ),
+        other => panic!("unexpected result: {:?}", other.map(|_| ())),
+    }
+}
+
+#[tokio::test]
+async fn test_missing_rollup_ranges() {
+    let context = start(RollupConfig {
+        rx_buffer: Some(range(RX_BUFFER)),
+        ..Default::default()
+    })
+    .await;
+    match context.client.advance(b"", &metadata()).await {
+        Err(Error::InvalidConfig(problems)) => assert_eq!(problems.len(), 2),
+        other => panic!("expected missing ranges, got {:?}", other),
+    }
+}
+
+#[rstest]
+#[tokio::test]
+async fn test_not_at_manual_yield(
+    context_future: impl Future<Output = Context>,
+) -> Result<(), Error> {
+    let context = context_future.await;
+    context.script(vec![Step::Halt]);
+    let result = context.client.advance(b"", &metadata()).await?;
+    assert_eq!(result.status, AdvanceStatus::Halted);
+    assert!(matches!(
+        context.client.advance(b"", &metadata()).await,
+        Err(Error::Rollup(_))
+    ));
+    assert_eq!(context.calls("machine.run"), 1);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_metadata_too_large() {
+    let mut input_metadata = range(INPUT_METADATA);
+    input_metadata.length = 128;
+    let context = start(RollupConfig {
+        rx_buffer: Some(range(RX_BUFFER)),
+        tx_buffer: Some(range(TX_BUFFER)),
+        input_metadata: Some(input_metadata),
+        ..Default::default()
+    })
+    .await;
+    match context.client.advance(b"", &metadata()).await {
+        Err(Error::Rollup(message)) => assert_eq!(
+            message,
+            "input metadata of 160 bytes does not fit in input_metadata of 128 bytes"
+        ),
+        other => panic!("unexpected result: {:?}", other.map(|_| ())),
+    }
+    assert_eq!(context.calls("machine.write_memory"), 0);
+}
+
+#[rstest]
+#[tokio::test]
+async fn test_rollup_ranges_are_cached(
+    context_future: impl Future<Output = Context>,
+) -> Result<(), Error> {
+    let context = context_future.await;
+    context.script(vec![
+        manual(ACCEPTED, Vec::new()),
+        manual(ACCEPTED, Vec::new()),
+    ]);
+    context.client.advance(b"first", &metadata()).await?;
+    context.client.advance(b"second", &metadata()).await?;
+    assert_eq!(context.calls("machine.get_initial_config"), 1);
+
+    // A new machine may have other ranges
+    context.client.destroy().await?;
+    common::create_machine_with(&context.client, |config| {
+        config.rollup.rx_buffer = Some(range(RX_BUFFER));
+        config.rollup.tx_buffer = Some(range(TX_BUFFER));
+        config.rollup.input_metadata = Some(range(INPUT_METADATA));
+    })
+    .await?;
+    context.client.set_iflags_y().await?;
+    context.script(vec![manual(ACCEPTED, Vec::new())]);
+    context.client.advance(b"third", &metadata()).await?;
+    assert_eq!(context.calls("machine.get_initial_config"), 2);
+    Ok(())
+}
@@ -0,0 +1,117 @@
+// Copyright (C) 2021 Cartesi Pte. Ltd.
+//
+// Licensed under the Apache License, Version 2.0 (the "License"); you may not use
+// this file except in compliance with the License. You may obtain a copy of the
+// License at http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software distributed
+// under the License is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR
+// CONDITIONS OF ANY KIND, either express or implied. See the License for the
+// specific language governing permissions and limitations under the License.
+
+use cartesi_machine_json_rpc::client::*;
+use rstest::*;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+
+mod common;
+use common::*;
+
+/// Options with chunks of `chunk` mcycles, recording the mcycle of every report
+fn recording_options(chunk: u64) -> (RunOptions, Arc<Mutex<Vec<u64>>>) {
+    let reports = Arc::new(Mutex::new(Vec::new()));
+    let mut options = RunOptions::new();
+    options.chunk = chunk;
+    options.set_progress({
+        let reports = reports.clone();
+        move |progress| reports.lock().unwrap().push(progress.mcycle)
+    });
+    (options, reports)
+}
+
+#[rstest]
+#[tokio::test]
+async fn test_runs_in_chunks(
+    context_with_machine_future: impl Future<Output = Context>,
+) -> Result<(), Error> {
+    let context = context_with_machine_future.await;
+    let (options, reports) = recording_options(1000);
+    assert_eq!(
+        context.client.run_until(3500, &options).await?,
+        InterpreterBreakReason::ReachedTargetMcycle
+    );
+    assert_eq!(*reports.lock().unwrap(), vec![1000, 2000, 3000, 3500]);
+    assert_eq!(context.client.read_csr(Csr::Mcycle).await?, 3500);
+    Ok(())
+}
+
+#[rstest]
+#[tokio::test]
+async fn test_stops_on_yield(
+    context_with_machine_future: impl Future<Output = Context>,
+) -> Result<(), Error> {
+    let context = context_with_machine_future.await;
+    context.client.run(500).await?;
+    context.client.set_iflags_y().await?;
+    let (options, reports) = recording_options(1000);
+    assert_eq!(
+        context.client.run_until(3500, &options).await?,
+        InterpreterBreakReason::YieldedManually
+    );
+    assert_eq!(*reports.lock().unwrap(), vec![500]);
+    Ok(())
+}
+
+#[rstest]
+#[tokio::test]
+async fn test_cancel_between_chunks(
+    context_with_machine_future: impl Future<Output = Context>,
+) -> Result<(), Error> {
+    let context = context_with_machine_future.await;
+    let token = CancellationToken::new();
+    let mut options = RunOptions::new();
+    options.chunk = 1000;
+    options
+        .set_cancel(token.clone())
+        .set_progress(move |progress| {
+            if progress.mcycle >= 2000 {
+                token.cancel();
+            }
+        });
+    assert!(matches!(
+        context.client.run_until(5000, &options).await,
+        Err(Error::Cancelled)
+    ));
+    assert_eq!(context.client.read_csr(Csr::Mcycle).await?, 2000);
+    Ok(())
+}
+
+#[rstest]
+#[tokio::test]
+async fn test_cancelled_before_start(
+    context_with_machine_future: impl Future<Output = Context>,
+) -> Result<(), Error> {
+    let context = context_with_machine_future.await;
+    let token = CancellationToken::new();
+    token.cancel();
+    let mut options = RunOptions::new();
+    options.set_cancel(token);
+    assert!(matches!(
+        context.client.run_until(5000, &options).await,
+        Err(Error::Cancelled)
+    ));
+    assert_eq!(context.client.read_csr(Csr::Mcycle).await?, 0);
+    Ok(())
+}
+
+#[rstest]
+#[tokio::test]
+async fn test_zero_chunk(context_with_machine_future: impl Future<Output = Context>) {
+    let context = context_with_machine_future.await;
+    let mut options = RunOptions::new();
+    options.chunk = 0;
+    assert!(matches!(
+        context.client.run_until(5000, &options).await,
+        Err(Error::InvalidArgument(_))
+    ));
+}
@@ -0,0 +1,123 @@
+// Copyright (C) 2021 Cartesi Pte. Ltd.
+//
+// Licensed under the Apache License, Version 2.0 (the "License"); you may not use
+// this file except in compliance with the License. You may obtain a copy of the
+// License at http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software distributed
+// under the License is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR
+// CONDITIONS OF ANY KIND, either express or implied. See the License for the
+// specific language governing permissions and limitations under the License.
+
+use cartesi_machine_json_rpc::client::*;
+use rstest::*;
+use std::path::PathBuf;
+use std::time::Duration;
+
+mod common;
+use common::create_machine;
+
+#[fixture]
+fn pool() -> ServerPool {
+    let mut config = ServerPoolConfig::new();
+    config.size = 1;
+    config.server.binary = Some(PathBuf::from(env!(
+        "CARGO_BIN_EXE_fake-remote-cartesi-machine"
+    )));
+    ServerPool::new(config).unwrap()
+}
+
+#[rstest]
+#[tokio::test]
+async fn test_released_server_is_clean(pool: ServerPool) -> Result<(), Error> {
+    let lease = pool.acquire().await?;
+    let address = lease.address().to_string();
+    create_machine(lease.client()).await?;
+    lease.write_x(1, 7).await?;
+    lease.release().await;
+    assert_eq!(pool.idle(), 1);
+
+    let lease = pool.acquire().await?;
+    assert_eq!(lease.address(), address);
+    assert!(matches!(lease.read_x(1).await, Err(Error::NoMachine)));
+    Ok(())
+}
+
+#[rstest]
+#[tokio::test]
+async fn test_acquire_waits_for_a_free_server(pool: ServerPool) -> Result<(), Error> {
+    let lease = pool.acquire().await?;
+    assert!(
+        tokio::time::timeout(Duration::from_millis(200), pool.acquire())
+            .await
+            .is_err()
+    );
+    let waiting = tokio::spawn({
+        let pool = pool.clone();
+        async move {
+            pool.acquire()
+                .await
+                .map(|lease| lease.address().to_string())
+        }
+    });
+    let address = lease.address().to_string();
+    lease.release().await;
+    assert_eq!(waiting.await.unwrap()?, address);
+    Ok(())
+}
+
+#[rstest]
+#[tokio::test]
+async fn test_unhealthy_server_is_replaced(pool: ServerPool) -> Result<(), Error> {
+    let lease = pool.acquire().await?;
+    let address = lease.address().to_string();
+    lease.shutdown().await?;
+    lease.release().await;
+    assert_eq!(pool.idle(), 0);
+
+    let lease = pool.acquire().await?;
+    assert_ne!(lease.address(), address);
+    lease.get_version().await?;
+    Ok(())
+}
+
+#[rstest]
+#[tokio::test]
+async fn test_dropped_lease_returns_server(pool: ServerPool) -> Result<(), Error> {
+    let lease = pool.acquire().await?;
+    create_machine(lease.client()).await?;
+    drop(lease);
+    let lease = tokio::time::timeout(Duration::from_secs(5), pool.acquire())
+        .await
+        .expect("server was not returned");
+    assert!(matches!(
+        lease?.get_root_hash().await,
+        Err(Error::NoMachine)
+    ));
+    Ok(())
+}
+
+#[rstest]
+#[tokio::test]
+async fn test_failed_start_frees_slot() {
+    let mut config = ServerPoolConfig::new();
+    config.size = 1;
+    config.server.binary = Some(PathBuf::from("/nonexistent/jsonrpc-remote-cartesi-machine"));
+    let pool = ServerPool::new(config).unwrap();
+    for _ in 0..2 {
+        let result = tokio::time::timeout(Duration::from_secs(5), pool.acquire())
+            .await
+            .expect("slot was not freed");
+        assert!(matches!(result, Err(Error::Io(_))));
+    }
+}
+
+#[test]
+fn test_empty_pool() {
+    let mut config = ServerPoolConfig::new();
+    config.size = 0;
+    assert!(matches!(
+        ServerPool::new(config),
+        Err(Error::InvalidArgument(_))
+    ));
+}
@@ -0,0 +1,131 @@
+// Copyright (C) 2021 Cartesi Pte. Ltd.
+//
+// Licensed under the Apache License, Version 2.0 (the "License"); you may not use
+// this file except in compliance with the License. You may obtain a copy of the
+// License at http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software distributed
+// under the License is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR
+// CONDITIONS OF ANY KIND, either express or implied. See the License for the
+// specific language governing permissions and limitations under the License.
+
+use cartesi_machine_json_rpc::client::*;
+use rstest::*;
+use std::os::unix::fs::PermissionsExt;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+/// Write an executable shell script standing in for the server binary
+fn script(name: &str, body: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!(
+        "server_process_tests_{}_{}",
+        std::process::id(),
+        name
+    ));
+    std::fs::write(&path, format!("#!/bin/sh\n{}\n", body)).unwrap();
+    std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755)).unwrap();
+    path
+}
+
+fn config(binary: PathBuf) -> ServerProcessConfig {
+    let mut config = ServerProcessConfig::new();
+    config.binary = Some(binary);
+    config.startup_timeout = Duration::from_secs(5);
+    config
+}
+
+#[rstest]
+#[tokio::test]
+async fn test_start_and_shutdown() -> Result<(), Error> {
+    let config = config(PathBuf::from(env!(
+        "CARGO_BIN_EXE_fake-remote-cartesi-machine"
+    )));
+    let server = ServerProcess::start_with(&config).await?;
+    assert!(server.address().starts_with("127.0.0.1:"));
+    assert!(server.pid().is_some());
+    server.client().get_version().await?;
+    let status = server.shutdown().await?;
+    assert!(status.success());
+    Ok(())
+}
+
+#[rstest]
+#[tokio::test]
+async fn test_missing_binary() {
+    let config = config(PathBuf::from("/nonexistent/jsonrpc-remote-cartesi-machine"));
+    match ServerProcess::start_with(&config).await {
+        Err(Error::Io(err)) => assert_eq!(err.kind(), std::io::ErrorKind::NotFound),
+        other => panic!("expected an io error, got {:?}", other.err()),
+    }
+}
+
+#[rstest]
+#[tokio::test]
+async fn test_early_exit_reports_stderr() {
+    let mut config = config(script("exit", "echo \"failed to start: $@\" >&2\nexit 3"));
+    config.args.push("--log-level=debug".to_string());
+    match ServerProcess::start_with(&config).await {
+        Err(Error::ServerExited { status, stderr }) => {
+            assert_eq!(status.code(), Some(3));
+            assert!(stderr.starts_with("failed to start: --server-address=127.0.0.1:"));
+            assert!(stderr.trim_end().ends_with("--log-level=debug"));
+        }
+        other => panic!("expected the server to exit, got {:?}", other.err()),
+    }
+}
+
+#[rstest]
+#[tokio::test]
+async fn test_startup_timeout() {
+    let mut config = config(script("hang", "exec sleep 30"));
+    config.startup_timeout = Duration::from_millis(300);
+    let started = Instant::now();
+    match ServerProcess::start_with(&config).await {
+        Err(Error::Timeout { method, timeout }) => {
+            assert_eq!(method, "start");
+            assert_eq!(timeout, config.startup_timeout);
+        }
+        other => panic!("expected a timeout, got {:?}", other.err()),
+    }
+    assert!(started.elapsed() < Duration::from_secs(5));
+}
+
+#[rstest]
+#[tokio::test]
+async fn test_restart_when_port_is_taken() -> Result<(), Error> {
+    let marker = std::env::temp_dir().join(format!(
+        "server_process_tests_{}_port_taken",
+        std::process::id()
+    ));
+    let _ = std::fs::remove_file(&marker);
+    // Loses the race for its port on the first launch only
+    let binary = script(
+        "port_taken",
+        &format!(
+            "if [ ! -e {marker} ]; then\n  touch {marker}\n  echo 'bind: Address already in use' >&2\n  exit 1\nfi\nexec {fake} \"$@\"",
+            marker = marker.display(),
+            fake = env!("CARGO_BIN_EXE_fake-remote-cartesi-machine"),
+        ),
+    );
+    let server = ServerProcess::start_with(&config(binary)).await?;
+    std::fs::remove_file(&marker).unwrap();
+    server.client().get_version().await?;
+    assert!(!server.stderr().contains("Address already in use"));
+    server.shutdown().await?;
+    Ok(())
+}
+
+#[rstest]
+#[tokio::test]
+async fn test_port_taken_on_every_attempt() {
+    let config = config(script(
+        "port_always_taken",
+        "echo 'bind: Address already in use' >&2\nexit 1",
+    ));
+    match ServerProcess::start_with(&config).await {
+        Err(Error::ServerExited { stderr, .. }) => {
+            assert!(stderr.contains("Address already in use"))
+        }
+        other => panic!("expected the server to exit, got {:?}", other.err()),
+    }
+}
@@ -0,0 +1,167 @@
+// Copyright (C) 2021 Cartesi Pte. Ltd.
+//
+// Licensed under the Apache License, Version 2.0 (the "License"); you may not use
+// this file except in compliance with the License. You may obtain a copy of the
+// License at http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software distributed
+// under the License is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR
+// CONDITIONS OF ANY KIND, either express or implied. See the License for the
+// specific language governing permissions and limitations under the License.
+
+use cartesi_machine_json_rpc::client::*;
+use rstest::*;
+use std::future::Future;
+use std::time::{Duration, Instant};
+
+mod common;
+use common::*;
+
+/// Whether a server still answers at `address` after a second
+async fn is_running(address: &str) -> bool {
+    for _ in 0..20 {
+        if JsonRpcCartesiMachineClient::new(address.to_string())
+            .await
+            .is_err()
+        {
+            return false;
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+    true
+}
+
+#[rstest]
+#[tokio::test]
+async fn test_rollback(
+    context_with_machine_future: impl Future<Output = Context>,
+) -> Result<(), Error> {
+    let mut context = context_with_machine_future.await;
+    let client = &mut context.client;
+    client.write_x(1, 1).await?;
+    let root_hash = client.get_root_hash().await?;
+    let snapshot = client.snapshot().await?;
+    let snapshot_address = snapshot.address().to_string();
+    assert_eq!(client.snapshot_count(), 1);
+
+    client.write_x(1, 2).await?;
+    let diverged = client.clone();
+    client.rollback(snapshot).await?;
+    assert_eq!(client.snapshot_count(), 0);
+    assert_eq!(client.get_address(), &snapshot_address);
+    assert_eq!(client.read_x(1).await?, 1);
+    assert_eq!(client.get_root_hash().await?, root_hash);
+    assert!(diverged.get_version().await.is_err());
+    Ok(())
+}
+
+#[rstest]
+#[tokio::test]
+async fn test_commit(
+    context_with_machine_future: impl Future<Output = Context>,
+) -> Result<(), Error> {
+    let context = context_with_machine_future.await;
+    let client = &context.client;
+    let snapshot = client.snapshot().await?;
+    let snapshot_address = snapshot.address().to_string();
+    client.write_x(1, 2).await?;
+    client.commit(snapshot).await?;
+    assert_eq!(client.snapshot_count(), 0);
+    assert_eq!(client.read_x(1).await?, 2);
+    assert!(!is_running(&snapshot_address).await);
+    Ok(())
+}
+
+#[rstest]
+#[tokio::test]
+async fn test_nested_rollbacks(
+    context_with_machine_future: impl Future<Output = Context>,
+) -> Result<(), Error> {
+    let mut context = context_with_machine_future.await;
+    let client = &mut context.client;
+    let first = client.snapshot().await?;
+    client.write_x(1, 1).await?;
+    let second = client.snapshot().await?;
+    client.write_x(1, 2).await?;
+    assert_eq!(client.snapshot_count(), 2);
+
+    client.rollback(second).await?;
+    assert_eq!(client.read_x(1).await?, 1);
+    client.rollback(first).await?;
+    assert_eq!(client.read_x(1).await?, 0);
+    assert_eq!(client.snapshot_count(), 0);
+    Ok(())
+}
+
+#[rstest]
+#[tokio::test]
+async fn test_dropped_snapshot_shuts_down(
+    context_with_machine_future: impl Future<Output = Context>,
+) -> Result<(), Error> {
+    let context = context_with_machine_future.await;
+    let client = &context.client;
+    let snapshot = client.snapshot().await?;
+    let snapshot_address = snapshot.address().to_string();
+    drop(snapshot);
+    assert_eq!(client.snapshot_count(), 0);
+    assert!(!is_running(&snapshot_address).await);
+    Ok(())
+}
+
+#[rstest]
+#[tokio::test]
+async fn test_snapshot_from_other_client(
+    context_with_machine_future: impl Future<Output = Context>,
+) -> Result<(), Error> {
+    let mut context = context_with_machine_future.await;
+    let client = &mut context.client;
+    let other = client.fork_client().await?;
+    let snapshot = other.snapshot().await?;
+    assert_eq!(client.snapshot_count(), 0);
+    assert!(matches!(
+        client.rollback(snapshot).await,
+        Err(Error::InvalidArgument(_))
+    ));
+    assert_eq!(other.snapshot_count(), 0);
+    client.get_version().await?;
+    Ok(())
+}
+
+#[rstest]
+#[tokio::test]
+async fn test_rollback_after_server_gone(
+    context_with_machine_future: impl Future<Output = Context>,
+) -> Result<(), Error> {
+    let context = context_with_machine_future.await;
+    let mut child = context.client.fork_client().await?;
+    child.write_x(1, 1).await?;
+    let snapshot = child.snapshot().await?;
+    let snapshot_address = snapshot.address().to_string();
+    child.write_x(1, 2).await?;
+    child.shutdown().await?;
+    child.rollback(snapshot).await?;
+    assert_eq!(child.get_address(), &snapshot_address);
+    assert_eq!(child.read_x(1).await?, 1);
+    Ok(())
+}
+
+#[rstest]
+#[tokio::test]
+async fn test_rollback_returns_shutdown_errors(
+    context_with_machine_future: impl Future<Output = Context>,
+) -> Result<(), Error> {
+    let context = context_with_machine_future.await;
+    let address = context.client.get_address().to_string();
+    let snapshot = context.client.snapshot().await?;
+    let snapshot_address = snapshot.address().to_string();
+    // Calls past the deadline fail before reaching the server
+    let mut expired = context.client.with_deadline(Instant::now());
+    assert!(matches!(
+        expired.rollback(snapshot).await,
+        Err(Error::Timeout { .. })
+    ));
+    assert_eq!(expired.get_address(), &snapshot_address);
+    assert_eq!(context.client.snapshot_count(), 0);
+    assert!(is_running(&address).await);
+    Ok(())
+}
@@ -0,0 +1,220 @@
+// Copyright (C) 2021 Cartesi Pte. Ltd.
+//
+// Licensed under the Apache License, Version 2.0 (the "License"); you may not use
+// this file except in compliance with the License. You may obtain a copy of the
+// License at http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software distributed
+// under the License is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR
+// CONDITIONS OF ANY KIND, either express or implied. See the License for the
+// specific language governing permissions and limitations under the License.
+
+use async_trait::async_trait;
+use cartesi_machine_json_rpc::client::*;
+use cartesi_machine_json_rpc::fake::FakeServer;
+use rstest::*;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+mod common;
+use common::{create_machine, Intercept, Interceptor};
+
+/// Middleware that records the name of every method called through it
+#[derive(Clone)]
+struct Recorder(Arc<Mutex<Vec<String>>>);
+
+#[async_trait]
+impl Interceptor for Recorder {
+    async fn intercept(&self, method: &str) -> Result<(), jsonrpsee::core::Error> {
+        self.0.lock().unwrap().push(method.to_string());
+        Ok(())
+    }
+}
+
+// Drivers written against the trait accept any transport
+async fn bump_x1<M: CartesiMachine>(machine: &M) -> Result<u64, Error> {
+    let value = machine.read_x(1).await? + 1;
+    machine.write_x(1, value).await?;
+    Ok(value)
+}
+
+#[rstest]
+#[case::http("http")]
+#[case::web_socket("ws")]
+#[tokio::test]
+async fn test_connect_by_scheme(#[case] scheme: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let server = FakeServer::start().await?;
+    let client =
+        JsonRpcCartesiMachineClient::new(format!("{}://{}", scheme, server.address())).await?;
+    create_machine(&client).await?;
+    for i in 0..100 {
+        client.write_x(1, i).await?;
+        assert_eq!(client.read_x(1).await?, i);
+    }
+    Ok(())
+}
+
+#[rstest]
+#[case::http("http")]
+#[case::web_socket("ws")]
+#[tokio::test]
+async fn test_transport_address(#[case] scheme: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let server = FakeServer::start().await?;
+    let address = format!("{}://{}", scheme, server.address());
+    let address = match Transport::connect(&address).await? {
+        Transport::Http(connection) if scheme == "http" => connection.address().to_string(),
+        Transport::WebSocket(connection) if scheme == "ws" => connection.address().to_string(),
+        _ => panic!("wrong transport for {}", address),
+    };
+    assert_eq!(address, format!("{}://{}", scheme, server.address()));
+    Ok(())
+}
+
+#[rstest]
+#[tokio::test]
+async fn test_explicit_web_socket_transport() -> Result<(), Box<dyn std::error::Error>> {
+    let server = FakeServer::start().await?;
+    let address = format!("ws://{}", server.address());
+    let transport = Transport::web_socket(&address).await?;
+    assert!(matches!(transport, Transport::WebSocket(_)));
+    let client = JsonRpcCartesiMachineClient::from_transport(address, transport).await?;
+    create_machine(&client).await?;
+    client.write_x(1, 1).await?;
+    let child = client.fork_client().await?;
+    assert!(child.get_address().starts_with("ws://"));
+    child.write_x(1, 2).await?;
+    assert_eq!(client.read_x(1).await?, 1);
+    assert_eq!(child.read_x(1).await?, 2);
+    child.shutdown().await?;
+    Ok(())
+}
+
+#[rstest]
+#[tokio::test]
+async fn test_web_socket_connection_refused() {
+    // Bind and release a port so that nothing is listening on it
+    let address = std::net::TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap();
+    assert!(matches!(
+        JsonRpcCartesiMachineClient::new(format!("ws://{}", address)).await,
+        Err(Error::Transport(_))
+    ));
+}
+
+#[rstest]
+#[tokio::test]
+async fn test_explicit_constructors() -> Result<(), Box<dyn std::error::Error>> {
+    let server = FakeServer::start().await?;
+    let http = JsonRpcCartesiMachineClient::new_http(server.uri()).await?;
+    let ws = JsonRpcCartesiMachineClient::new_ws(format!("ws://{}", server.address())).await?;
+    create_machine(&http).await?;
+    http.write_x(1, 3).await?;
+    assert_eq!(ws.read_x(1).await?, 3);
+    assert!(JsonRpcCartesiMachineClient::new_ws(server.uri())
+        .await
+        .is_err());
+    Ok(())
+}
+
+#[rstest]
+#[tokio::test]
+async fn test_user_supplied_transport() -> Result<(), Box<dyn std::error::Error>> {
+    let server = FakeServer::start().await?;
+    let methods = Arc::new(Mutex::new(Vec::new()));
+    let transport = Intercept::new(Recorder(methods.clone()), Transport::http(&server.uri())?);
+    let client = JsonRpcCartesiMachineClient::from_transport(server.uri(), transport).await?;
+    create_machine(&client).await?;
+    assert_eq!(bump_x1(&client).await?, 1);
+    let child = client.fork_client().await?;
+    assert_eq!(bump_x1(&child).await?, 2);
+    assert_eq!(client.read_x(1).await?, 1);
+    child.shutdown().await?;
+    assert_eq!(
+        *methods.lock().unwrap(),
+        vec![
+            "get_version",
+            "machine.get_default_config",
+            "machine.machine.config",
+            "machine.read_x",
+            "machine.write_x",
+            "fork",
+            "get_version",
+            "machine.read_x",
+            "machine.write_x",
+            "machine.read_x",
+            "shutdown",
+        ]
+    );
+    Ok(())
+}
+
+#[rstest]
+#[case::http("http")]
+#[case::web_socket("ws")]
+#[tokio::test]
+async fn test_connect_with_retry_waits_for_server(
+    #[case] scheme: &str,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let address = std::net::TcpListener::bind("127.0.0.1:0")?.local_addr()?;
+    let start = tokio::spawn(async move {
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        FakeServer::start_at(address).await.unwrap()
+    });
+    let client = JsonRpcCartesiMachineClient::connect_with_retry(
+        format!("{}://{}", scheme, address),
+        Duration::from_secs(10),
+    )
+    .await?;
+    client.check_connection().await?;
+    let server = start.await?;
+    server.stop();
+    server.stopped().await;
+    assert!(matches!(
+        client.check_connection().await,
+        Err(Error::Transport(_))
+    ));
+    Ok(())
+}
+
+#[rstest]
+#[tokio::test]
+async fn test_connect_with_retry_timeout() {
+    let address = std::net::TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap();
+    let started = Instant::now();
+    let result = JsonRpcCartesiMachineClient::connect_with_retry(
+        format!("http://{}", address),
+        Duration::from_millis(300),
+    )
+    .await;
+    assert!(matches!(
+        result,
+        Err(Error::Timeout {
+            method: "connect_with_retry",
+            ..
+        })
+    ));
+    assert!(started.elapsed() < Duration::from_secs(2));
+}
+
+#[rstest]
+#[case::http("http")]
+#[case::https("https")]
+#[tokio::test]
+async fn test_reconnect_keeps_scheme(
+    #[case] scheme: &str,
+) -> Result<(), Box<dyn std::error::Error>> {
+    // HTTP transports connect on the first call, so no server is needed
+    let transport = Transport::http(&format!("{}://127.0.0.1:1", scheme))?;
+    assert_eq!(transport.scheme(), scheme);
+    let fork = transport
+        .reconnect(&format!("{}://127.0.0.1:2", transport.scheme()))
+        .await?;
+    assert!(matches!(fork, Transport::Http(_)));
+    assert_eq!(fork.scheme(), scheme);
+    Ok(())
+}
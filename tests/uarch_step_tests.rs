@@ -0,0 +1,232 @@
+// Copyright (C) 2021 Cartesi Pte. Ltd.
+//
+// Licensed under the Apache License, Version 2.0 (the "License"); you may not use
+// this file except in compliance with the License. You may obtain a copy of the
+// License at http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software distributed
+// under the License is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR
+// CONDITIONS OF ANY KIND, either express or implied. See the License for the
+// specific language governing permissions and limitations under the License.
+
+use cartesi_machine_json_rpc::client::merkle::*;
+use cartesi_machine_json_rpc::client::uarch_step::*;
+use cartesi_machine_json_rpc::client::*;
+use rstest::*;
+
+const UARCH_RAM_START: u64 = 0x70000000;
+const DATA_ADDRESS: u64 = UARCH_RAM_START + 0x100;
+
+fn x_address(reg: u64) -> u64 {
+    UARCH_X0_ADDRESS + 8 * reg
+}
+
+fn i_type(opcode: u32, funct3: u32, rd: u32, rs1: u32, imm: i32) -> u32 {
+    ((imm as u32 & 0xfff) << 20) | (rs1 << 15) | (funct3 << 12) | (rd << 7) | opcode
+}
+
+fn r_type(opcode: u32, funct3: u32, funct7: u32, rd: u32, rs1: u32, rs2: u32) -> u32 {
+    (funct7 << 25) | (rs2 << 20) | (rs1 << 15) | (funct3 << 12) | (rd << 7) | opcode
+}
+
+fn s_type(funct3: u32, rs1: u32, rs2: u32, imm: i32) -> u32 {
+    let imm = imm as u32;
+    (((imm >> 5) & 0x7f) << 25)
+        | (rs2 << 20)
+        | (rs1 << 15)
+        | (funct3 << 12)
+        | ((imm & 0x1f) << 7)
+        | 0x23
+}
+
+fn b_type(funct3: u32, rs1: u32, rs2: u32, imm: i32) -> u32 {
+    let imm = imm as u32;
+    (((imm >> 12) & 1) << 31)
+        | (((imm >> 5) & 0x3f) << 25)
+        | (rs2 << 20)
+        | (rs1 << 15)
+        | (funct3 << 12)
+        | (((imm >> 1) & 0xf) << 8)
+        | (((imm >> 11) & 1) << 7)
+        | 0x63
+}
+
+fn j_type(rd: u32, imm: i32) -> u32 {
+    let imm = imm as u32;
+    (((imm >> 20) & 1) << 31)
+        | (((imm >> 1) & 0x3ff) << 21)
+        | (((imm >> 11) & 1) << 20)
+        | (((imm >> 12) & 0xff) << 12)
+        | (rd << 7)
+        | 0x6f
+}
+
+/// Machine state with `insn` at the uarch pc, x2 pointing to data and x3 = -2
+fn machine(insn: u32) -> MerkleTree {
+    let mut tree = MerkleTree::new();
+    tree.write_word(UARCH_PC_ADDRESS, UARCH_RAM_START);
+    tree.write(UARCH_RAM_START, &insn.to_le_bytes());
+    tree.write_word(x_address(2), DATA_ADDRESS);
+    tree.write_word(x_address(3), -2i64 as u64);
+    tree.write_word(DATA_ADDRESS, 0x80000000fffffff0);
+    tree
+}
+
+/// Step `tree` while recording, and check the log replays to the new root hash
+fn recorded_step(tree: &mut MerkleTree) -> (UarchStepStatus, AccessLog) {
+    let root_hash_before = tree.root_hash();
+    let mut state = RecordingUarchState::new(tree);
+    let status = uarch_step(&mut state).unwrap();
+    let log = state.into_log();
+    log.verify_uarch_step_transition(&root_hash_before, &tree.root_hash())
+        .unwrap();
+    (status, log)
+}
+
+#[rstest]
+#[case::addi(i_type(0x13, 0, 1, 2, -1), DATA_ADDRESS - 1, 4)]
+#[case::slti(i_type(0x13, 2, 1, 3, -1), 1, 4)]
+#[case::sltiu(i_type(0x13, 3, 1, 3, -1), 1, 4)]
+#[case::srli(i_type(0x13, 5, 1, 3, 60), 0xf, 4)]
+#[case::srai(i_type(0x13, 5, 1, 3, 0x400 | 1), u64::MAX, 4)]
+#[case::addiw(i_type(0x1b, 0, 1, 3, 1), u64::MAX, 4)]
+#[case::lui(0x800000b7, 0xffffffff80000000, 4)]
+#[case::auipc(0x00001097, UARCH_RAM_START + 0x1000, 4)]
+#[case::add(r_type(0x33, 0, 0, 1, 2, 3), DATA_ADDRESS - 2, 4)]
+#[case::sub(r_type(0x33, 0, 0x20, 1, 2, 3), DATA_ADDRESS + 2, 4)]
+#[case::sltu(r_type(0x33, 3, 0, 1, 2, 3), 1, 4)]
+#[case::addw(r_type(0x3b, 0, 0, 1, 3, 3), -4i64 as u64, 4)]
+#[case::lb(i_type(0x03, 0, 1, 2, 7), 0xffffffffffffff80, 4)]
+#[case::lw(i_type(0x03, 2, 1, 2, 0), 0xfffffffffffffff0, 4)]
+#[case::lwu(i_type(0x03, 6, 1, 2, 4), 0x80000000, 4)]
+#[case::ld(i_type(0x03, 3, 1, 2, 0), 0x80000000fffffff0, 4)]
+#[case::jal(j_type(1, 16), UARCH_RAM_START + 4, 16)]
+#[case::jalr(i_type(0x67, 0, 1, 2, 3), UARCH_RAM_START + 4, DATA_ADDRESS + 2 - UARCH_RAM_START)]
+#[case::beq_taken(b_type(0, 2, 2, 8), 0, 8)]
+#[case::blt_not_taken(b_type(4, 2, 3, 8), 0, 4)]
+#[case::write_to_x0(i_type(0x13, 0, 0, 2, 1), 0, 4)]
+fn test_step(#[case] insn: u32, #[case] x1: u64, #[case] pc_offset: u64) {
+    let mut tree = machine(insn);
+    let (status, _) = recorded_step(&mut tree);
+    assert_eq!(status, UarchStepStatus::Success);
+    assert_eq!(tree.read_word(x_address(1)), x1);
+    assert_eq!(
+        tree.read_word(UARCH_PC_ADDRESS),
+        UARCH_RAM_START + pc_offset
+    );
+    assert_eq!(tree.read_word(UARCH_CYCLE_ADDRESS), 1);
+    assert_eq!(tree.read_word(x_address(0)), 0);
+}
+
+#[rstest]
+#[case::sh(s_type(1, 2, 3, 2), 0x80000000fffefff0)]
+#[case::sw(s_type(2, 2, 3, 4), 0xfffffffefffffff0)]
+#[case::sd(s_type(3, 2, 3, 0), -2i64 as u64)]
+fn test_step_store(#[case] insn: u32, #[case] word: u64) {
+    let mut tree = machine(insn);
+    recorded_step(&mut tree);
+    assert_eq!(tree.read_word(DATA_ADDRESS), word);
+}
+
+#[test]
+fn test_step_access_order() {
+    let mut tree = machine(i_type(0x13, 0, 1, 2, 1));
+    let (_, log) = recorded_step(&mut tree);
+    let accesses: Vec<_> = log
+        .accesses
+        .iter()
+        .map(|access| (access.r#type, access.address))
+        .collect();
+    assert_eq!(
+        accesses,
+        vec![
+            (AccessType::Read, UARCH_CYCLE_ADDRESS),
+            (AccessType::Read, UARCH_HALT_FLAG_ADDRESS),
+            (AccessType::Read, UARCH_PC_ADDRESS),
+            (AccessType::Read, UARCH_RAM_START),
+            (AccessType::Read, x_address(2)),
+            (AccessType::Write, x_address(1)),
+            (AccessType::Write, UARCH_PC_ADDRESS),
+            (AccessType::Write, UARCH_CYCLE_ADDRESS),
+        ]
+    );
+}
+
+#[test]
+fn test_step_ecall_halt() {
+    let mut tree = machine(0x73);
+    tree.write_word(x_address(17), UARCH_ECALL_FN_HALT);
+    recorded_step(&mut tree);
+    assert_eq!(tree.read_word(UARCH_HALT_FLAG_ADDRESS), 1);
+    assert_eq!(tree.read_word(UARCH_PC_ADDRESS), UARCH_RAM_START + 4);
+    let (status, log) = recorded_step(&mut tree);
+    assert_eq!(status, UarchStepStatus::UarchHalted);
+    assert_eq!(log.accesses.len(), 2);
+}
+
+#[test]
+fn test_step_cycle_overflow() {
+    let mut tree = machine(0x73);
+    tree.write_word(UARCH_CYCLE_ADDRESS, u64::MAX);
+    let (status, log) = recorded_step(&mut tree);
+    assert_eq!(status, UarchStepStatus::CycleOverflow);
+    assert_eq!(log.accesses.len(), 1);
+}
+
+#[test]
+fn test_step_illegal_instruction() {
+    let mut tree = machine(0xffffffff);
+    assert!(matches!(
+        uarch_step(&mut tree),
+        Err(Error::UarchException(_))
+    ));
+}
+
+#[fixture]
+fn step() -> (Hash, AccessLog) {
+    let mut tree = machine(i_type(0x13, 0, 1, 2, 1));
+    let root_hash_before = tree.root_hash();
+    let (_, log) = recorded_step(&mut tree);
+    (root_hash_before, log)
+}
+
+#[rstest]
+fn test_verify_uarch_step_wrong_result(step: (Hash, AccessLog)) {
+    let (root_hash_before, mut log) = step;
+    log.accesses[5].written_data = 0u64.to_le_bytes().to_vec();
+    assert!(matches!(
+        log.verify_uarch_step(&root_hash_before),
+        Err(Error::InconsistentAccess { index: 5, .. })
+    ));
+}
+
+#[rstest]
+fn test_verify_uarch_step_missing_access(step: (Hash, AccessLog)) {
+    let (root_hash_before, mut log) = step;
+    log.accesses.remove(4);
+    assert!(matches!(
+        log.verify_uarch_step(&root_hash_before),
+        Err(Error::InconsistentAccess { index: 4, .. })
+    ));
+}
+
+#[rstest]
+fn test_verify_uarch_step_truncated(step: (Hash, AccessLog)) {
+    let (root_hash_before, mut log) = step;
+    log.accesses.pop();
+    assert!(matches!(
+        log.verify_uarch_step(&root_hash_before),
+        Err(Error::InconsistentAccess { index: 7, .. })
+    ));
+}
+
+#[rstest]
+fn test_verify_uarch_step_extra_access(step: (Hash, AccessLog)) {
+    let (root_hash_before, mut log) = step;
+    let extra = log.accesses[0].clone();
+    log.accesses.push(extra);
+    assert!(matches!(
+        log.verify_uarch_step(&root_hash_before),
+        Err(Error::InconsistentAccess { index: 8, .. })
+    ));
+}